@@ -0,0 +1,188 @@
+//! Derive macros for objecthash
+//!
+//! `#[derive(ObjectHash)]` hashes a struct with named fields as the dict of
+//! its fields, like `objecthash_struct!` with a key for each of them. These
+//! attributes change how it's hashed:
+//!
+//! * `#[objecthash(schema = "invoice/2")]` on the struct hashes it along
+//!   with the name of its schema, like `objecthash::versioned::Versioned`
+//! * `#[objecthash(rename = "key")]` on a field hashes it under another key
+//!
+//! `#[derive(Schema)]` describes the same fields under the same keys, like
+//! `objecthash_schema!`, so a struct's schema is written down once, by its
+//! definition. Both reject structs with two fields hashed under one key.
+//!
+//! The derived code refers to the `objecthash` crate by name, so it has to
+//! be a dependency of the crate deriving the traits. It's usually used
+//! through objecthash's `derive` feature rather than directly.
+
+use std::collections::HashSet;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Ident, LitStr, Type};
+
+/// Implement `objecthash::ObjectHash` for a struct with named fields
+#[proc_macro_derive(ObjectHash, attributes(objecthash))]
+pub fn derive_objecthash(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_objecthash(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_objecthash(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let schema = container_schema(input)?;
+    let fields = named_fields(input, "ObjectHash")?;
+
+    let members = fields.iter().map(|field| {
+        let (key, ident) = (&field.key, field.ident);
+        quote!(::objecthash::types::hash_member(#key, &self.#ident, &*hasher))
+    });
+
+    let hash_fields = if fields.is_empty() {
+        quote! {
+            let mut members: [&[u8]; 0] = [];
+            ::objecthash::types::DictMembers::hash_dict_members(hasher, &mut members);
+        }
+    } else {
+        quote! {
+            let mut members = [#(#members),*];
+            ::objecthash::types::DictMembers::hash_dict_members(hasher, &mut members);
+        }
+    };
+
+    let body = match schema {
+        Some(schema) => quote!(::objecthash::versioned::hash_versioned(#schema, hasher, |hasher| { #hash_fields })),
+        None => hash_fields,
+    };
+
+    let name = &input.ident;
+    let generics = with_bound(&input.generics, quote!(::objecthash::ObjectHash));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::objecthash::ObjectHash for #name #ty_generics #where_clause {
+            fn objecthash<__H: ::objecthash::ObjectHasher>(&self, hasher: &mut __H) {
+                #body
+            }
+        }
+    })
+}
+
+/// Implement `objecthash::schema::Schema` for a struct with named fields,
+/// describing the fields a derived `ObjectHash` impl hashes
+#[proc_macro_derive(Schema, attributes(objecthash))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_schema(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_schema(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = named_fields(input, "Schema")?;
+
+    let described = fields.iter().map(|field| {
+        let (key, ty) = (&field.key, field.ty);
+        quote!((::std::string::String::from(#key), <#ty as ::objecthash::schema::Schema>::schema()))
+    });
+
+    let name = &input.ident;
+    let type_name = name.unraw().to_string();
+    let generics = with_bound(&input.generics, quote!(::objecthash::schema::Schema));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::objecthash::schema::Schema for #name #ty_generics #where_clause {
+            fn schema() -> ::objecthash::schema::TypeSchema {
+                ::objecthash::schema::TypeSchema::Struct {
+                    name: ::std::string::String::from(#type_name),
+                    fields: ::std::vec![#(#described),*],
+                }
+            }
+        }
+    })
+}
+
+// A field of the struct being derived for, and the key it's hashed under
+struct Field<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+    key: String,
+}
+
+// The schema name given by `#[objecthash(schema = "...")]` on the struct
+fn container_schema(input: &DeriveInput) -> syn::Result<Option<LitStr>> {
+    let mut schema = None;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("objecthash")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("schema") {
+                schema = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported objecthash attribute"))
+            }
+        })?;
+    }
+
+    Ok(schema)
+}
+
+// The struct's fields with their keys, which have to be unique
+fn named_fields<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<Vec<Field<'a>>> {
+    let named = match input.data {
+        Data::Struct(ref data) => {
+            match data.fields {
+                Fields::Named(ref named) => named,
+                _ => return Err(syn::Error::new_spanned(input, not_supported(derive))),
+            }
+        }
+        _ => return Err(syn::Error::new_spanned(input, not_supported(derive))),
+    };
+
+    let mut fields = Vec::with_capacity(named.named.len());
+    let mut keys = HashSet::new();
+
+    for field in &named.named {
+        let ident = field.ident.as_ref().expect("named fields have names");
+        let mut key = ident.unraw().to_string();
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("objecthash")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    key = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported objecthash field attribute"))
+                }
+            })?;
+        }
+
+        if !keys.insert(key.clone()) {
+            return Err(syn::Error::new_spanned(ident, format!("duplicate key {:?}", key)));
+        }
+
+        fields.push(Field {
+            ident,
+            ty: &field.ty,
+            key,
+        });
+    }
+
+    Ok(fields)
+}
+
+fn not_supported(derive: &str) -> String {
+    format!("{} can only be derived for structs with named fields", derive)
+}
+
+// The struct's generics, with each type parameter bound by the given trait
+fn with_bound(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+
+    generics
+}
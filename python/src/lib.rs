@@ -0,0 +1,364 @@
+//! Python bindings for objecthash
+//!
+//! Exposes this crate's hashing of Python values, JSON documents and
+//! redacted JSON documents to Python through PyO3, so Python services get
+//! digests identical to the Rust implementation's rather than maintaining a
+//! copy of it. Python values map onto ObjectHash types as:
+//!
+//! ```text
+//! None                    null
+//! bool                    boolean
+//! int                     integer, of any size
+//! float                   float
+//! str                     unicode string, normalized to NFC
+//! bytes, bytearray        octet string (a non-standard extension)
+//! list, tuple             list
+//! set, frozenset          set
+//! dict                    dict
+//! Redacted                the digest it carries
+//! ```
+//!
+//! Values nested more than 128 deep, including containers which contain
+//! themselves, raise `RecursionError`.
+//!
+//! Build the `objecthash` Python module with `maturin build` from this
+//! directory.
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyRecursionError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyFrozenSet, PyInt, PyList, PySet, PyString, PyTuple};
+
+use objecthash::json::redaction::{self, RedactedDocument};
+use objecthash::json::{self, NumberMode};
+use objecthash::stream::hash_dict_from_iter;
+use objecthash::types::INTEGER_TAG;
+use objecthash::{hasher, Digest, ObjectHash, ObjectHasher, Set};
+
+// Values are converted recursively, so how deeply they can be nested is
+// limited like parsed JSON documents are
+const MAX_DEPTH: usize = 128;
+
+create_exception!(objecthash, ObjectHashError, PyValueError, "Error raised while hashing a value");
+
+fn to_py_err(err: objecthash::Error) -> PyErr {
+    ObjectHashError::new_err(err.to_string())
+}
+
+/// A value which has been redacted down to its digest, which it stands in
+/// for wherever it's hashed
+#[pyclass(eq, frozen, module = "objecthash")]
+#[derive(PartialEq)]
+struct Redacted {
+    digest: Digest,
+}
+
+#[pymethods]
+impl Redacted {
+    #[new]
+    fn new(digest: &[u8]) -> PyResult<Self> {
+        let len = hasher::default().output_len();
+
+        if digest.len() != len {
+            return Err(PyValueError::new_err(format!("redacted digests are {} bytes, got {}", len, digest.len())));
+        }
+
+        Ok(Redacted { digest: Digest::new(digest).map_err(to_py_err)? })
+    }
+
+    #[getter]
+    fn digest<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.digest.as_ref())
+    }
+
+    fn hexdigest(&self) -> String {
+        hex(&self.digest)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Redacted('{}')", hex(&self.digest))
+    }
+}
+
+// A Python value, converted so it can be hashed without holding on to the
+// interpreter
+enum Object {
+    Null,
+    Bool(bool),
+    Int(i128),
+    BigInt(String),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<Object>),
+    Set(Vec<Object>),
+    Dict(Vec<(Object, Object)>),
+    Redacted(Digest),
+}
+
+impl Object {
+    fn convert(value: &Bound<'_, PyAny>) -> PyResult<Object> {
+        Object::convert_within(value, MAX_DEPTH)
+    }
+
+    // Containers which contain themselves are nested forever, so they too
+    // run into the limit
+    fn convert_within(value: &Bound<'_, PyAny>, depth: usize) -> PyResult<Object> {
+        if depth == 0 {
+            return Err(PyRecursionError::new_err("maximum recursion depth exceeded while hashing a value"));
+        }
+
+        let convert_all = |values: Bound<'_, pyo3::types::PyIterator>| {
+            values.map(|v| Object::convert_within(&v?, depth - 1)).collect::<PyResult<Vec<_>>>()
+        };
+
+        if value.is_none() {
+            Ok(Object::Null)
+        } else if let Ok(b) = value.cast::<PyBool>() {
+            Ok(Object::Bool(b.is_true()))
+        } else if let Ok(i) = value.cast::<PyInt>() {
+            match i.extract::<i128>() {
+                Ok(i) => Ok(Object::Int(i)),
+                Err(_) => Ok(Object::BigInt(i.str()?.extract()?)),
+            }
+        } else if let Ok(f) = value.cast::<PyFloat>() {
+            Ok(Object::Float(f.value()))
+        } else if let Ok(s) = value.cast::<PyString>() {
+            Ok(Object::Str(s.extract()?))
+        } else if let Ok(bytes) = value.cast::<PyBytes>() {
+            Ok(Object::Bytes(bytes.as_bytes().to_vec()))
+        } else if let Ok(bytes) = value.cast::<PyByteArray>() {
+            Ok(Object::Bytes(bytes.to_vec()))
+        } else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+            Ok(Object::List(convert_all(value.try_iter()?)?))
+        } else if value.is_instance_of::<PySet>() || value.is_instance_of::<PyFrozenSet>() {
+            Ok(Object::Set(convert_all(value.try_iter()?)?))
+        } else if let Ok(dict) = value.cast::<PyDict>() {
+            dict.iter()
+                .map(|(k, v)| Ok((Object::convert_within(&k, depth - 1)?, Object::convert_within(&v, depth - 1)?)))
+                .collect::<PyResult<_>>()
+                .map(Object::Dict)
+        } else if let Ok(redacted) = value.cast::<Redacted>() {
+            Ok(Object::Redacted(redacted.get().digest))
+        } else {
+            let name = value.get_type().name()?;
+            Err(PyTypeError::new_err(format!("cannot hash values of type '{}'", name)))
+        }
+    }
+}
+
+impl ObjectHash for Object {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            Object::Null => ().objecthash(hasher),
+            Object::Bool(b) => b.objecthash(hasher),
+            Object::Int(i) => i.objecthash(hasher),
+            Object::BigInt(ref digits) => {
+                // Python prints integers in the integer tag's canonical form
+                hasher.update(INTEGER_TAG);
+                hasher.update(digits.as_bytes());
+            }
+            Object::Float(f) => f.objecthash(hasher),
+            Object::Str(ref s) => s.objecthash(hasher),
+            Object::Bytes(ref bytes) => bytes[..].objecthash(hasher),
+            Object::List(ref elements) => elements.objecthash(hasher),
+            Object::Set(ref members) => Set(members).objecthash(hasher),
+            Object::Dict(ref members) => hash_dict_from_iter(members.iter().map(|(k, v)| (k, v)), hasher),
+            Object::Redacted(ref digest) => hasher.redact(digest),
+        }
+    }
+}
+
+fn hex(digest: &Digest) -> String {
+    digest.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn number_mode(common_json: bool) -> NumberMode {
+    if common_json {
+        NumberMode::CommonJson
+    } else {
+        NumberMode::Native
+    }
+}
+
+fn digest_of(value: &Bound<'_, PyAny>) -> PyResult<Digest> {
+    let object = Object::convert(value)?;
+    let mut hasher = hasher::default();
+    object.objecthash(&mut hasher);
+    hasher.try_finish().map_err(to_py_err)
+}
+
+/// The ObjectHash digest of a Python value
+#[pyfunction]
+fn digest<'py>(value: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyBytes>> {
+    Ok(PyBytes::new(value.py(), digest_of(value)?.as_ref()))
+}
+
+/// The ObjectHash digest of a Python value, in hex
+#[pyfunction]
+fn hexdigest(value: &Bound<'_, PyAny>) -> PyResult<String> {
+    Ok(hex(&digest_of(value)?))
+}
+
+/// Redact a Python value down to its digest
+#[pyfunction]
+fn redact(value: &Bound<'_, PyAny>) -> PyResult<Redacted> {
+    Ok(Redacted { digest: digest_of(value)? })
+}
+
+/// The digest of a JSON document, hashing integers as integers, or with
+/// `common_json` every number as a float like the reference implementations
+#[pyfunction]
+#[pyo3(signature = (document, common_json = false))]
+fn hash_json<'py>(py: Python<'py>, document: &str, common_json: bool) -> PyResult<Bound<'py, PyBytes>> {
+    let digest = json::hash_str_with(document, number_mode(common_json)).map_err(to_py_err)?;
+    Ok(PyBytes::new(py, digest.as_ref()))
+}
+
+/// The digest of the original of a JSON document which may contain
+/// redaction markers, from this crate or the reference implementations
+#[pyfunction]
+#[pyo3(signature = (document, common_json = false))]
+fn hash_redacted_json<'py>(py: Python<'py>, document: &str, common_json: bool) -> PyResult<Bound<'py, PyBytes>> {
+    let digest = redaction::hash_redacted_str_with(document, number_mode(common_json)).map_err(to_py_err)?;
+    Ok(PyBytes::new(py, digest.as_ref()))
+}
+
+/// Redact the subtrees at the given JSON pointers of a JSON document,
+/// returning the redacted document and the digest of the original
+#[pyfunction]
+fn redact_json<'py>(py: Python<'py>,
+                    document: &str,
+                    pointers: Vec<String>)
+                    -> PyResult<(String, Bound<'py, PyBytes>)> {
+    let value: serde_json::Value = serde_json::from_str(document).map_err(|e| to_py_err(e.into()))?;
+    let redacted = RedactedDocument::new(&value, &pointers).map_err(to_py_err)?;
+    let json = serde_json::to_string(&redacted.value).map_err(|e| to_py_err(e.into()))?;
+    Ok((json, PyBytes::new(py, redacted.digest.as_ref())))
+}
+
+/// Check a redacted JSON document against the digest of the original
+#[pyfunction]
+fn verify_redacted_json(document: &str, digest: &[u8]) -> PyResult<bool> {
+    let value: serde_json::Value = serde_json::from_str(document).map_err(|e| to_py_err(e.into()))?;
+    Ok(redaction::verify(&value, &Digest::new(digest).map_err(to_py_err)?))
+}
+
+#[pymodule]
+#[pyo3(name = "objecthash")]
+fn objecthash_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ObjectHashError", m.py().get_type::<ObjectHashError>())?;
+    m.add_class::<Redacted>()?;
+    m.add_function(wrap_pyfunction!(digest, m)?)?;
+    m.add_function(wrap_pyfunction!(hexdigest, m)?)?;
+    m.add_function(wrap_pyfunction!(redact, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_json, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_redacted_json, m)?)?;
+    m.add_function(wrap_pyfunction!(redact_json, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_redacted_json, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use pyo3::prelude::*;
+    use pyo3::types::{PyDict, PyModule};
+
+    use super::objecthash_module;
+
+    // Run Python code with the module imported as `objecthash`
+    fn run(code: &CStr) {
+        Python::attach(|py| {
+            let module = PyModule::new(py, "objecthash").unwrap();
+            objecthash_module(&module).unwrap();
+
+            let locals = PyDict::new(py);
+            locals.set_item("objecthash", module).unwrap();
+
+            if let Err(err) = py.run(code, None, Some(&locals)) {
+                panic!("{}", err);
+            }
+        });
+    }
+
+    #[test]
+    fn hashing_values() {
+        let digest = objecthash::digest(&b"foo"[..]);
+        let hex: String = digest.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+
+        run(&std::ffi::CString::new(format!(r#"
+value = ["foo", {{"bar": ["baz", None, 1, 1.5, 0.0001, 1000, 2, -23.1234, 2]}}]
+assert objecthash.hexdigest(value) == "726e7ae9e3fadf8a2228bf33e505a63df8db1638fa4f21429673d387dbd1c52a"
+assert objecthash.digest(value) == bytes.fromhex(objecthash.hexdigest(value))
+assert objecthash.digest(tuple(value)) == objecthash.digest(value)
+
+assert objecthash.hexdigest(2 ** 64) == "9d53531287138cb721d5fecf30aa8566faf44bc6265ed11a5ed13913bf67c0bd"
+assert objecthash.digest({{1, 2}}) == objecthash.digest(frozenset([2, 1]))
+assert objecthash.digest({{"b": 1, "a": True}}) == objecthash.digest({{"a": True, "b": 1}})
+assert objecthash.digest("café") == objecthash.digest("café")
+
+assert objecthash.hexdigest(b"foo") == "{}"
+assert objecthash.digest(bytearray(b"foo")) == objecthash.digest(b"foo")
+assert objecthash.digest(b"foo") != objecthash.digest("foo")
+"#, hex)).unwrap());
+    }
+
+    #[test]
+    fn redacting_values() {
+        run(cr#"
+redacted = objecthash.redact("bar")
+assert redacted.digest == objecthash.digest("bar")
+assert redacted == objecthash.Redacted(objecthash.digest("bar"))
+assert repr(redacted) == "Redacted('%s')" % redacted.hexdigest()
+assert objecthash.digest(["foo", {"bar": redacted}]) == objecthash.digest(["foo", {"bar": "bar"}])
+"#);
+    }
+
+    #[test]
+    fn hashing_json() {
+        run(cr#"
+document = '{"foo": [1, 1.5], "bar": {"baz": "qux"}}'
+assert objecthash.hash_json(document) == objecthash.digest({"foo": [1, 1.5], "bar": {"baz": "qux"}})
+assert objecthash.hash_json("[1, 2]", common_json=True) == objecthash.digest([1.0, 2.0])
+
+redacted, digest = objecthash.redact_json(document, ["/foo/1", "/bar"])
+assert digest == objecthash.hash_json(document)
+assert redacted.startswith('{"bar":"**REDACTED**:')
+assert objecthash.verify_redacted_json(redacted, digest)
+assert not objecthash.verify_redacted_json(document.replace("qux", "quux"), digest)
+assert objecthash.hash_redacted_json(redacted) == digest
+
+reference = '["foo", "**REDACTED**%s"]' % objecthash.hexdigest("bar")
+assert objecthash.hash_redacted_json(reference) == objecthash.digest(["foo", "bar"])
+"#);
+    }
+
+    #[test]
+    fn errors() {
+        run(cr#"
+def raises(error, f, *args):
+    try:
+        f(*args)
+    except error:
+        return True
+    return False
+
+cyclic = []
+cyclic.append(cyclic)
+assert raises(RecursionError, objecthash.digest, cyclic)
+
+nested = None
+for _ in range(127):
+    nested = [nested]
+assert objecthash.digest(nested)
+assert raises(RecursionError, objecthash.digest, [nested])
+assert raises(TypeError, objecthash.digest, [object()])
+assert raises(ValueError, objecthash.Redacted, b"foo")
+assert raises(objecthash.ObjectHashError, objecthash.hash_json, "[1, 2")
+assert raises(objecthash.ObjectHashError, objecthash.redact_json, "[1]", ["/2"])
+assert issubclass(objecthash.ObjectHashError, ValueError)
+"#);
+    }
+}
@@ -0,0 +1,197 @@
+//! Hashing data which arrives asynchronously
+//!
+//! `hash_stream` hashes the items of a `Stream` as a list, and (with the
+//! `octet-strings` feature) `hash_async_reader` hashes everything read from
+//! an `AsyncRead` as an octet string. Both return futures which hash the data
+//! as it arrives, without buffering it, and resolve to the digest once the
+//! stream or reader is exhausted. Errors recorded by the hasher (such as an
+//! exceeded limit) are returned from the future, as from `try_finish`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "octet-strings")]
+use std::io;
+
+#[cfg(feature = "octet-strings")]
+use futures::io::AsyncRead;
+use futures::stream::Stream;
+
+#[cfg(feature = "objecthash-ring")]
+use hasher;
+use {Digest, Error, ObjectHash, ObjectHasher};
+#[cfg(feature = "octet-strings")]
+use types::{check_len, update_extension_tag, Extension};
+use types::LIST_TAG;
+
+/// Future which hashes the items of a stream as a list
+#[must_use = "futures do nothing unless polled"]
+pub struct HashStream<S, H> {
+    stream: S,
+    hasher: Option<H>,
+}
+
+/// Hash the items of a stream as a list into the given hasher, resolving to
+/// the digest of the list
+pub fn hash_stream<S, H>(stream: S, mut hasher: H) -> HashStream<S, H>
+    where S: Stream + Unpin,
+          S::Item: ObjectHash,
+          H: ObjectHasher + Unpin
+{
+    hasher.update(LIST_TAG);
+
+    HashStream {
+        stream,
+        hasher: Some(hasher),
+    }
+}
+
+/// Hash the items of a stream as a list, using the default hasher
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_stream<S>(stream: S) -> HashStream<S, hasher::ring::Hasher>
+    where S: Stream + Unpin,
+          S::Item: ObjectHash
+{
+    hash_stream(stream, hasher::default())
+}
+
+impl<S, H> Future for HashStream<S, H>
+    where S: Stream + Unpin,
+          S::Item: ObjectHash,
+          H: ObjectHasher + Unpin
+{
+    type Output = Result<Digest, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        loop {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(element)) => {
+                    this.hasher
+                        .as_mut()
+                        .expect("HashStream polled after completion")
+                        .update_nested(|h| element.objecthash(h));
+                }
+                Poll::Ready(None) => {
+                    let hasher = this.hasher.take().expect("HashStream polled after completion");
+                    return Poll::Ready(hasher.try_finish());
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Future which hashes everything read from a reader as an octet string
+#[cfg(feature = "octet-strings")]
+#[must_use = "futures do nothing unless polled"]
+pub struct HashAsyncReader<R, H> {
+    reader: R,
+    hasher: Option<H>,
+    buf: Box<[u8]>,
+    len: u64,
+}
+
+/// Hash everything read from the reader as a single octet string into the
+/// given hasher, resolving to its digest. Reading stops early if it exceeds
+/// the hasher's length limit
+#[cfg(feature = "octet-strings")]
+pub fn hash_async_reader<R, H>(reader: R, mut hasher: H) -> HashAsyncReader<R, H>
+    where R: AsyncRead + Unpin,
+          H: ObjectHasher + Unpin
+{
+    update_extension_tag(&mut hasher, Extension::Octets);
+
+    HashAsyncReader {
+        reader,
+        hasher: Some(hasher),
+        buf: vec![0u8; 8192].into_boxed_slice(),
+        len: 0,
+    }
+}
+
+/// Hash everything read from the reader as a single octet string, using the
+/// default hasher
+#[cfg(all(feature = "objecthash-ring", feature = "octet-strings"))]
+pub fn digest_async_reader<R>(reader: R) -> HashAsyncReader<R, hasher::ring::Hasher>
+    where R: AsyncRead + Unpin
+{
+    hash_async_reader(reader, hasher::default())
+}
+
+#[cfg(feature = "octet-strings")]
+impl<R, H> Future for HashAsyncReader<R, H>
+    where R: AsyncRead + Unpin,
+          H: ObjectHasher + Unpin
+{
+    type Output = Result<Digest, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        loop {
+            match Pin::new(&mut this.reader).poll_read(cx, &mut this.buf) {
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(n)) => {
+                    let hasher = this.hasher.as_mut().expect("HashAsyncReader polled after completion");
+                    hasher.update(&this.buf[..n]);
+                    this.len += n as u64;
+
+                    // Don't keep reading an oversized stream just to hash it
+                    if !check_len(this.len, hasher) {
+                        break;
+                    }
+                }
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => (),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let hasher = this.hasher.take().expect("HashAsyncReader polled after completion");
+        Poll::Ready(hasher.try_finish())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use futures::executor::block_on;
+    use futures::stream;
+
+    use super::{digest_stream, hash_stream};
+    use {digest, hasher, Error, HashProfile, Limits};
+
+    #[test]
+    fn hashing_streams() {
+        let list: Vec<String> = (0..100).map(|i| format!("row {}", i)).collect();
+
+        assert_eq!(block_on(digest_stream(stream::iter(list.clone()))).unwrap(), digest(&list));
+        assert_eq!(block_on(digest_stream(stream::iter(Vec::<String>::new()))).unwrap(),
+                   digest(&Vec::<String>::new()));
+
+        let profile = HashProfile::default().with_limits(Limits::default().with_max_elements(10));
+        match block_on(hash_stream(stream::iter(list), hasher::default().with_profile(profile))) {
+            Err(Error::LimitExceeded(_)) => (),
+            other => panic!("expected an exceeded limit, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "octet-strings")]
+    #[test]
+    fn hashing_readers() {
+        use super::{digest_async_reader, hash_async_reader};
+
+        let blob: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+        assert_eq!(block_on(digest_async_reader(&blob[..])).unwrap(), digest(&blob[..]));
+        assert_eq!(block_on(digest_async_reader(&b""[..])).unwrap(), digest(&b""[..]));
+
+        let profile = HashProfile::default().with_limits(Limits::default().with_max_len(100));
+        match block_on(hash_async_reader(&blob[..], hasher::default().with_profile(profile))) {
+            Err(Error::LimitExceeded(_)) => (),
+            other => panic!("expected an exceeded limit, got {:?}", other),
+        }
+    }
+}
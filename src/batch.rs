@@ -0,0 +1,124 @@
+//! Hashing many values of the same kind
+//!
+//! A `BatchHasher` hashes values one after another with the same algorithm,
+//! profile and context, reusing what it can between them: the state that
+//! hashers with a rejecting profile share, and the `Vec` the digests are
+//! collected into. For jobs which hash millions of records, this saves the
+//! setup which would otherwise be repeated for each one.
+
+use hasher;
+use {Digest, Error, HashProfile, ObjectHash, ObjectHasher};
+
+/// Hashes values one at a time with the same settings
+pub struct BatchHasher {
+    template: hasher::ring::Hasher,
+    digests: Vec<Digest>,
+}
+
+impl BatchHasher {
+    /// Hash values with SHA-256 and the default profile
+    pub fn new() -> Self {
+        BatchHasher::with_hasher(hasher::default())
+    }
+
+    /// Hash values with the settings of the given hasher, which should be
+    /// fresh (as returned by `with_profile` or `with_context`, say)
+    pub fn with_hasher(template: hasher::ring::Hasher) -> Self {
+        BatchHasher {
+            template,
+            digests: Vec::new(),
+        }
+    }
+
+    /// Hash values with SHA-256 and the given profile
+    pub fn with_profile(profile: HashProfile) -> Self {
+        BatchHasher::with_hasher(hasher::default().with_profile(profile))
+    }
+
+    /// Compute the digest of a single value
+    pub fn hash<T: ObjectHash + ?Sized>(&mut self, value: &T) -> Result<Digest, Error> {
+        let mut hasher = self.template.renew();
+        value.objecthash(&mut hasher);
+        hasher.try_finish()
+    }
+
+    /// Compute the digests of the given values, in order, appending them to
+    /// `out`. Stops at the first value the profile rejects
+    pub fn hash_into<T, I>(&mut self, values: I, out: &mut Vec<Digest>) -> Result<(), Error>
+        where T: ObjectHash,
+              I: IntoIterator<Item = T>
+    {
+        let values = values.into_iter();
+        out.reserve(values.size_hint().0);
+
+        for value in values {
+            out.push(self.hash(&value)?);
+        }
+
+        Ok(())
+    }
+
+    /// Compute the digests of the given values, in order, into a buffer which
+    /// is reused by the next call
+    pub fn hash_all<T, I>(&mut self, values: I) -> Result<&[Digest], Error>
+        where T: ObjectHash,
+              I: IntoIterator<Item = T>
+    {
+        let mut digests = std::mem::take(&mut self.digests);
+        digests.clear();
+        let result = self.hash_into(values, &mut digests);
+        self.digests = digests;
+
+        result.map(move |()| &self.digests[..])
+    }
+}
+
+impl Default for BatchHasher {
+    fn default() -> Self {
+        BatchHasher::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchHasher;
+    use {digest, hasher, Error, HashProfile, Limits, ObjectHash, ObjectHasher};
+
+    #[test]
+    fn hashing_batches() {
+        let records: Vec<Vec<String>> = (0..100).map(|i| vec![format!("row {}", i), "é".to_owned()]).collect();
+        let expected: Vec<_> = records.iter().map(digest).collect();
+
+        let mut batch = BatchHasher::new();
+        assert_eq!(batch.hash_all(&records).unwrap(), &expected[..]);
+        assert_eq!(batch.hash_all(&records[..10]).unwrap(), &expected[..10]);
+        assert_eq!(batch.hash("foo").unwrap(), digest("foo"));
+
+        let mut out = Vec::new();
+        batch.hash_into(&records, &mut out).unwrap();
+        assert_eq!(out, expected);
+
+        let mut contexts = BatchHasher::with_hasher(hasher::default().with_context("records"));
+        let mut hasher = hasher::default().with_context("records");
+        records[0].objecthash(&mut hasher);
+        assert_eq!(contexts.hash(&records[0]).unwrap(), hasher.finish());
+    }
+
+    #[test]
+    fn rejected_values() {
+        let profile = HashProfile::default().with_limits(Limits::default().with_max_elements(2));
+        let mut batch = BatchHasher::with_profile(profile);
+
+        // Limits and errors apply to each value separately
+        for _ in 0..3 {
+            assert_eq!(batch.hash(&vec![1, 2]).unwrap(), digest(&vec![1, 2]));
+        }
+
+        match batch.hash_all(vec![vec![1], vec![1, 2, 3], vec![2]]) {
+            Err(Error::LimitExceeded(_)) => (),
+            other => panic!("expected an exceeded limit, got {:?}", other),
+        }
+
+        assert_eq!(batch.hash(&vec![3]).unwrap(), digest(&vec![3]));
+    }
+}
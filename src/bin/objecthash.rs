@@ -0,0 +1,374 @@
+//! Command-line tool for working with the digests of JSON documents
+//!
+//! ```text
+//! objecthash redact [--common-json] <document> <pointer>...
+//! objecthash verify [--common-json] <document> <hex digest>
+//! objecthash vectors [--common-json] [<records>]
+//! objecthash vectors --check [--common-json] [<vectors>]
+//! ```
+//!
+//! `redact` replaces the subtrees of a document at the given JSON pointers
+//! with redaction markers carrying their digests, and writes the redacted
+//! document to standard output. It has the same digest as the original.
+//!
+//! `verify` checks a document, which may be redacted, against a digest. It
+//! exits with status 1 if they don't match.
+//!
+//! `vectors` reads newline-delimited JSON records and writes each of them
+//! followed by its digest, in the format of the reference implementations'
+//! `common_json.test`. With `--check`, it reads a file in that format and
+//! checks every digest in it instead, exiting with status 1 if any of them
+//! don't match.
+//!
+//! Documents are read from the given path, or from standard input if it's
+//! `-` or missing. Numbers are hashed with the integer tag, unless
+//! `--common-json` says to hash them all as floats like the reference
+//! implementations' common JSON mode. The exit status is 2 if the arguments
+//! are wrong or a document can't be read, parsed or hashed.
+
+extern crate objecthash;
+extern crate serde_json;
+
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::ExitCode;
+
+use objecthash::{verify_hex, Error, HashProfile};
+use objecthash::json::{self, NumberMode};
+use objecthash::json::redaction::{RedactedDocument, RedactedJson};
+use objecthash::json::vectors;
+use serde_json::Value;
+
+const USAGE: &str = "usage: objecthash redact [--common-json] <document> <pointer>...
+       objecthash verify [--common-json] <document> <hex digest>
+       objecthash vectors [--common-json] [<records>]
+       objecthash vectors --check [--common-json] [<vectors>]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let stdout = io::stdout();
+
+    match run(&args, &mut stdout.lock()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Reasons a command can't run to completion
+#[derive(Debug)]
+enum CliError {
+    Usage,
+    Error(Error),
+
+    // Error in the record on the given line
+    Record(usize, Error),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::Usage => f.write_str(USAGE),
+            CliError::Error(ref err) => write!(f, "objecthash: {}", err),
+            CliError::Record(line, ref err) => write!(f, "objecthash: line {}: {}", line, err),
+        }
+    }
+}
+
+impl From<Error> for CliError {
+    fn from(err: Error) -> Self {
+        CliError::Error(err)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError::Error(Error::Io(err))
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(err: serde_json::Error) -> Self {
+        CliError::Error(Error::Json(err))
+    }
+}
+
+// Options which any command may be given, wherever they appear, and the
+// rest of the arguments in order
+struct Options {
+    mode: NumberMode,
+    check: bool,
+    operands: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, CliError> {
+        let mut options = Options {
+            mode: NumberMode::Native,
+            check: false,
+            operands: Vec::new(),
+        };
+
+        for arg in args {
+            match arg.as_str() {
+                "--common-json" => options.mode = NumberMode::CommonJson,
+                "--check" => options.check = true,
+                "-" => options.operands.push(arg.clone()),
+                _ if arg.starts_with('-') => return Err(CliError::Usage),
+                _ => options.operands.push(arg.clone()),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn profile(&self) -> HashProfile {
+        HashProfile::default().with_json_numbers(self.mode)
+    }
+
+    // The only operand, or standard input if there are none
+    fn input(&self) -> Result<&str, CliError> {
+        match self.operands.len() {
+            0 => Ok("-"),
+            1 => Ok(&self.operands[0]),
+            _ => Err(CliError::Usage),
+        }
+    }
+}
+
+// Run the command the arguments name, returning whether what it checked
+// turned out to be valid
+fn run<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    let (command, args) = args.split_first().ok_or(CliError::Usage)?;
+    let options = Options::parse(args)?;
+
+    if options.check && command != "vectors" {
+        return Err(CliError::Usage);
+    }
+
+    match command.as_str() {
+        "redact" => redact(&options, out).map(|()| true),
+        "verify" => verify(&options),
+        "vectors" if options.check => check_vectors(&options, out),
+        "vectors" => write_vectors(&options, out).map(|()| true),
+        _ => Err(CliError::Usage),
+    }
+}
+
+fn redact<W: Write>(options: &Options, out: &mut W) -> Result<(), CliError> {
+    let (document, pointers) = match options.operands.split_first() {
+        Some((document, pointers)) if !pointers.is_empty() => (document, pointers),
+        _ => return Err(CliError::Usage),
+    };
+
+    let value = read_document(document)?;
+    let redacted = RedactedDocument::new_with(&value, pointers, options.profile())?;
+
+    serde_json::to_writer(&mut *out, &redacted.value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn verify(options: &Options) -> Result<bool, CliError> {
+    let (document, digest) = match options.operands.as_slice() {
+        [document, digest] => (document, digest),
+        _ => return Err(CliError::Usage),
+    };
+
+    let value = read_document(document)?;
+
+    if verify_hex(&RedactedJson::new(&value).with_json_numbers(options.mode), digest) {
+        Ok(true)
+    } else {
+        eprintln!("objecthash: {} doesn't have digest {}", document, digest);
+        Ok(false)
+    }
+}
+
+fn write_vectors<W: Write>(options: &Options, out: &mut W) -> Result<(), CliError> {
+    for (i, line) in open(options.input()?)?.lines().enumerate() {
+        let line = line?;
+        let record = line.trim();
+
+        if record.is_empty() {
+            continue;
+        }
+
+        let digest = json::hash_str_with(record, options.mode).map_err(|err| CliError::Record(i + 1, err))?;
+        writeln!(out, "{}\n{}\n", record, to_hex(digest))?;
+    }
+
+    Ok(())
+}
+
+fn check_vectors<W: Write>(options: &Options, out: &mut W) -> Result<bool, CliError> {
+    let reader = open(options.input()?)?;
+    let report = match options.mode {
+        NumberMode::CommonJson => vectors::verify_vectors(reader)?,
+        mode => vectors::verify_vectors_with(reader, |json| json::hash_str_with(json, mode))?,
+    };
+
+    for failure in &report.failures {
+        let (line, expected) = (failure.vector.line, to_hex(&failure.vector.digest));
+
+        match failure.actual {
+            Ok(ref digest) => eprintln!("line {}: expected {}, got {}", line, expected, to_hex(digest)),
+            Err(ref err) => eprintln!("line {}: {}", line, err),
+        }
+    }
+
+    writeln!(out, "{} passed, {} failed", report.passed, report.failures.len())?;
+    Ok(report.is_success())
+}
+
+fn to_hex<B: AsRef<[u8]>>(bytes: B) -> String {
+    bytes.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_document(path: &str) -> Result<Value, CliError> {
+    let mut json = String::new();
+    open(path)?.read_to_string(&mut json)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn open(path: &str) -> Result<Box<dyn BufRead>, CliError> {
+    if path == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use objecthash::{digest, Error};
+    use objecthash::json::{hash_str_with, NumberMode};
+    use objecthash::json::redaction::{self, REDACTED_PREFIX};
+    use serde_json::{self, Value};
+
+    use super::{run, to_hex, CliError};
+
+    const DOCUMENT: &str = r#"{"name": "Alice", "ssn": "078-05-1120", "address": {"city": "Paris", "zip": 75001}}"#;
+
+    // Write a file for a test to read, named after the test
+    fn fixture(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("objecthash-cli-{}-{}", name, ::std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn objecthash(args: &[&str]) -> Result<String, CliError> {
+        match check(args)? {
+            (true, out) => Ok(out),
+            (false, out) => panic!("{:?} failed, writing {:?}", args, out),
+        }
+    }
+
+    // Run a command which checks something, returning whether it passed
+    fn check(args: &[&str]) -> Result<(bool, String), CliError> {
+        let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+        let mut out = Vec::new();
+        let passed = run(&args, &mut out)?;
+        Ok((passed, String::from_utf8(out).unwrap()))
+    }
+
+    #[test]
+    fn redacting() {
+        let path = fixture("redact", DOCUMENT);
+        let document = path.to_str().unwrap();
+        let original: Value = serde_json::from_str(DOCUMENT).unwrap();
+
+        let output = objecthash(&["redact", document, "/ssn", "/address/zip"]).unwrap();
+        let redacted: Value = serde_json::from_str(&output).unwrap();
+        assert!(redacted["ssn"].as_str().unwrap().starts_with(REDACTED_PREFIX));
+        assert_eq!(redacted["address"]["city"], "Paris");
+        assert!(redaction::verify(&redacted, &digest(&original)));
+
+        let output = objecthash(&["redact", "--common-json", document, "/address"]).unwrap();
+        let root = hash_str_with(DOCUMENT, NumberMode::CommonJson).unwrap();
+        assert_eq!(redaction::hash_redacted_str_with(&output, NumberMode::CommonJson).unwrap(), root);
+
+        match objecthash(&["redact", document, "/phone"]) {
+            Err(CliError::Error(Error::InvalidPointer(_))) => (),
+            other => panic!("expected an invalid pointer error, got {:?}", other),
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn verifying() {
+        let path = fixture("verify", DOCUMENT);
+        let document = path.to_str().unwrap();
+        let root = to_hex(hash_str_with(DOCUMENT, NumberMode::Native).unwrap());
+        let common_root = to_hex(hash_str_with(DOCUMENT, NumberMode::CommonJson).unwrap());
+
+        assert_eq!(objecthash(&["verify", document, &root]).unwrap(), "");
+        assert_eq!(objecthash(&["verify", document, &root.to_uppercase()]).unwrap(), "");
+        assert_eq!(objecthash(&["verify", "--common-json", document, &common_root]).unwrap(), "");
+        assert!(!check(&["verify", document, &common_root]).unwrap().0);
+        assert!(!check(&["verify", document, "00"]).unwrap().0);
+
+        // Redacted documents verify against the digest of the original
+        let redacted = fixture("verify-redacted", &objecthash(&["redact", document, "/name"]).unwrap());
+        assert!(check(&["verify", redacted.to_str().unwrap(), &root]).unwrap().0);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&redacted).unwrap();
+    }
+
+    #[test]
+    fn vectors() {
+        let records = fixture("vectors", "[1, 2.5]\n\n{\"foo\": \"bar\"}\n");
+        let vectors = objecthash(&["vectors", "--common-json", records.to_str().unwrap()]).unwrap();
+        assert_eq!(vectors,
+                   format!("[1, 2.5]\n{}\n\n{{\"foo\": \"bar\"}}\n{}\n\n",
+                           to_hex(digest(&vec![1.0, 2.5])),
+                           to_hex(hash_str_with(r#"{"foo": "bar"}"#, NumberMode::Native).unwrap())));
+
+        // What's written checks out, but only with the same number mode
+        let written = fixture("vectors-written", &vectors);
+        let written = written.to_str().unwrap();
+        assert_eq!(objecthash(&["vectors", "--check", "--common-json", written]).unwrap(), "2 passed, 0 failed\n");
+        assert_eq!(check(&["vectors", "--check", written]).unwrap(), (false, String::from("1 passed, 1 failed\n")));
+
+        let reference = concat!(env!("CARGO_MANIFEST_DIR"), "/vectors/common_json.test");
+        assert_eq!(objecthash(&["vectors", "--check", "--common-json", reference]).unwrap(), "20 passed, 0 failed\n");
+
+        let invalid = fixture("vectors-invalid", "[1]\n{\n");
+        match objecthash(&["vectors", invalid.to_str().unwrap()]) {
+            Err(CliError::Record(2, Error::Json(_))) => (),
+            other => panic!("expected a JSON error on line 2, got {:?}", other),
+        }
+
+        for path in &[records.to_str().unwrap(), written, invalid.to_str().unwrap()] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn usage() {
+        for args in &[&[][..], &["redact"], &["redact", "doc.json"], &["redact", "--verbose", "doc.json", "/a"],
+                      &["hash", "doc.json"], &["verify", "doc.json"], &["verify", "--check", "doc.json", "00"],
+                      &["vectors", "a.json", "b.json"]] {
+            match objecthash(args) {
+                Err(CliError::Usage) => (),
+                other => panic!("expected a usage error for {:?}, got {:?}", args, other),
+            }
+        }
+
+        match objecthash(&["redact", "/nonexistent/objecthash.json", "/a"]) {
+            Err(CliError::Error(Error::Io(_))) => (),
+            other => panic!("expected an IO error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Persistent caches of computed digests
+//!
+//! Hashing a large blob takes as long as reading it, and `Memoized` only
+//! remembers the digest for as long as the process runs. A `HashCache` maps
+//! a cheap identity key, such as a file's path, length and modification time
+//! from `file_key`, to the digest computed for it, so blobs which haven't
+//! changed aren't rehashed after a restart.
+//!
+//! The cache trusts its keys: a key which stays the same while the content
+//! changes returns a stale digest. Keys should also tell apart the hashers
+//! and profiles digests were computed with, e.g. by keeping a cache for each.
+//!
+//! `MemoryCache` keeps digests in a map, `FileCache` in small files named
+//! after the SHA-256 of their keys, and (with the `sled` feature)
+//! `SledCache` in a sled database.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[cfg(feature = "sled")]
+use sled;
+
+use {hasher, hex, Digest, Error, ObjectHasher};
+use store::write_atomically;
+
+/// Storage for digests under identity keys
+pub trait HashCache {
+    /// The digest cached under the given key, if there is one
+    fn get(&self, key: &[u8]) -> Result<Option<Digest>, Error>;
+
+    /// Cache a digest under the given key, replacing any cached before
+    fn put(&mut self, key: &[u8], digest: &Digest) -> Result<(), Error>;
+
+    /// The digest cached under the given key, or else the one computed by
+    /// `hash`, which is cached under the key unless it fails
+    fn get_or_insert_with<F>(&mut self, key: &[u8], hash: F) -> Result<Digest, Error>
+        where F: FnOnce() -> Result<Digest, Error>
+    {
+        if let Some(digest) = self.get(key)? {
+            return Ok(digest);
+        }
+
+        let digest = hash()?;
+        self.put(key, &digest)?;
+        Ok(digest)
+    }
+}
+
+/// A cache which keeps digests in memory
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCache {
+    digests: HashMap<Vec<u8>, Digest>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        MemoryCache::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+}
+
+impl HashCache for MemoryCache {
+    fn get(&self, key: &[u8]) -> Result<Option<Digest>, Error> {
+        Ok(self.digests.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &[u8], digest: &Digest) -> Result<(), Error> {
+        self.digests.insert(key.to_vec(), *digest);
+        Ok(())
+    }
+}
+
+/// A cache which keeps each digest in a file named after the SHA-256 of its
+/// key in hex, under a subdirectory named after that hash's first byte
+#[derive(Clone, Debug)]
+pub struct FileCache {
+    root: PathBuf,
+}
+
+impl FileCache {
+    /// Open a cache in the given directory, creating it if need be
+    pub fn open<P: Into<PathBuf>>(root: P) -> Result<Self, Error> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(FileCache { root })
+    }
+
+    fn path(&self, key: &[u8]) -> PathBuf {
+        let mut hasher = hasher::default();
+        hasher.update(key);
+        let name = hex::encode(hasher.finish().as_ref());
+        self.root.join(&name[..2]).join(&name)
+    }
+}
+
+impl HashCache for FileCache {
+    fn get(&self, key: &[u8]) -> Result<Option<Digest>, Error> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(Digest::new(&bytes)?)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&mut self, key: &[u8], digest: &Digest) -> Result<(), Error> {
+        write_atomically(&self.path(key), digest.as_ref())?;
+        Ok(())
+    }
+}
+
+/// A cache which keeps digests in a sled database
+#[cfg(feature = "sled")]
+#[derive(Clone, Debug)]
+pub struct SledCache {
+    tree: sled::Tree,
+
+    // The database the tree belongs to, if the cache opened it itself, so
+    // it's closed along with the cache
+    _db: Option<sled::Db>,
+}
+
+#[cfg(feature = "sled")]
+impl SledCache {
+    /// Open (or create) a database at the given path, and cache digests in
+    /// its default tree
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let db = sled::open(path).map_err(sled_error)?;
+
+        Ok(SledCache {
+            tree: (*db).clone(),
+            _db: Some(db),
+        })
+    }
+
+    /// Cache digests in the given tree of an open database
+    pub fn from_tree(tree: sled::Tree) -> Self {
+        SledCache { tree, _db: None }
+    }
+
+    /// Write cached digests out to disk, which sled otherwise does
+    /// periodically in the background
+    pub fn flush(&self) -> Result<(), Error> {
+        self.tree.flush().map_err(sled_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sled")]
+impl HashCache for SledCache {
+    fn get(&self, key: &[u8]) -> Result<Option<Digest>, Error> {
+        match self.tree.get(key).map_err(sled_error)? {
+            Some(bytes) => Ok(Some(Digest::new(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&mut self, key: &[u8], digest: &Digest) -> Result<(), Error> {
+        self.tree.insert(key, digest.as_ref()).map_err(sled_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sled")]
+fn sled_error(err: sled::Error) -> Error {
+    match err {
+        sled::Error::Io(err) => Error::Io(err),
+        other => Error::Io(io::Error::other(other.to_string())),
+    }
+}
+
+/// A cheap identity key for a file: its canonical path, length and
+/// modification time. Tools which rewrite a file without changing its
+/// length or modification time will go unnoticed
+pub fn file_key<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Error> {
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+    let modified = metadata.modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+
+    let mut key = path_bytes(&path);
+    key.push(0);
+    key.extend_from_slice(&metadata.len().to_be_bytes());
+    key.extend_from_slice(&modified.to_be_bytes());
+    Ok(key)
+}
+
+// The path as the OS stores it, so paths which aren't valid Unicode don't
+// share a key with the paths their lossy conversions spell
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(windows)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str().encode_wide().flat_map(u16::to_be_bytes).collect()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// The digest of a file's contents as a single octet string, cached under
+/// its `file_key`
+#[cfg(feature = "octet-strings")]
+pub fn digest_file<C: HashCache, P: AsRef<Path>>(cache: &mut C, path: P) -> Result<Digest, Error> {
+    let path = path.as_ref();
+    cache.get_or_insert_with(&file_key(path)?, || ::stream::digest_reader(&mut fs::File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::{file_key, FileCache, HashCache, MemoryCache};
+    use {digest, Error};
+
+    fn check<C: HashCache>(cache: &mut C) {
+        assert_eq!(cache.get(b"foo").unwrap(), None);
+        cache.put(b"foo", &digest("foo")).unwrap();
+        assert_eq!(cache.get(b"foo").unwrap(), Some(digest("foo")));
+
+        // Cached digests aren't recomputed, and failures aren't cached
+        let cached = cache.get_or_insert_with(b"foo", || panic!("digest recomputed"));
+        assert_eq!(cached.unwrap(), digest("foo"));
+
+        match cache.get_or_insert_with(b"bar", || Err(Error::Random)) {
+            Err(Error::Random) => (),
+            other => panic!("expected the hashing error, got {:?}", other),
+        }
+        assert_eq!(cache.get(b"bar").unwrap(), None);
+        assert_eq!(cache.get_or_insert_with(b"bar", || Ok(digest("bar"))).unwrap(), digest("bar"));
+        assert_eq!(cache.get(b"bar").unwrap(), Some(digest("bar")));
+    }
+
+    #[test]
+    fn memory_cache() {
+        let mut cache = MemoryCache::new();
+        check(&mut cache);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn file_cache() {
+        let root = env::temp_dir().join(format!("objecthash-cache-{}", ::std::process::id()));
+        check(&mut FileCache::open(&root).unwrap());
+
+        // Digests outlive the cache they were put in
+        assert_eq!(FileCache::open(&root).unwrap().get(b"foo").unwrap(), Some(digest("foo")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_cache() {
+        use std::thread;
+        use std::time::Duration;
+
+        use super::SledCache;
+
+        let root = env::temp_dir().join(format!("objecthash-sled-{}", ::std::process::id()));
+        {
+            let mut cache = SledCache::open(&root).unwrap();
+            check(&mut cache);
+            cache.flush().unwrap();
+        }
+
+        // sled's IO threads can hold the database's lock for a moment after
+        // it's closed, which only another process would otherwise wait out
+        let reopen = || {
+            for _ in 0..100 {
+                if let Ok(cache) = SledCache::open(&root) {
+                    return cache;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            SledCache::open(&root).unwrap()
+        };
+
+        // Digests outlive the database they were put in
+        assert_eq!(reopen().get(b"foo").unwrap(), Some(digest("foo")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn file_keys() {
+        let path = env::temp_dir().join(format!("objecthash-key-{}", ::std::process::id()));
+        fs::write(&path, b"foo").unwrap();
+        let key = file_key(&path).unwrap();
+        assert_eq!(file_key(&path).unwrap(), key);
+
+        fs::write(&path, b"foobar").unwrap();
+        assert!(file_key(&path).unwrap() != key);
+
+        #[cfg(feature = "octet-strings")]
+        {
+            use super::digest_file;
+
+            let mut cache = MemoryCache::new();
+            assert_eq!(digest_file(&mut cache, &path).unwrap(), digest(&b"foobar"[..]));
+            assert_eq!(cache.get(&file_key(&path).unwrap()).unwrap(), Some(digest(&b"foobar"[..])));
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert!(file_key(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_unicode_file_keys() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::time::UNIX_EPOCH;
+
+        let dir = env::temp_dir().join(format!("objecthash-keys-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // Both are converted to "\u{fffd}" lossily
+        let (a, b) = (dir.join(OsStr::from_bytes(b"\xff")), dir.join(OsStr::from_bytes(b"\xfe")));
+        for path in &[&a, &b] {
+            fs::write(path, b"foo").unwrap();
+            fs::File::options().write(true).open(path).unwrap().set_modified(UNIX_EPOCH).unwrap();
+        }
+        assert!(file_key(&a).unwrap() != file_key(&b).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
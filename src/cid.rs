@@ -0,0 +1,228 @@
+//! IPFS content identifiers for digests
+//!
+//! A CIDv1 names content by a multicodec code saying how it's encoded and a
+//! multihash of it: the code of the hash function, the digest length and the
+//! digest itself. `Digest::to_cid` wraps a SHA-256 digest (as computed by
+//! `hasher::default()`) into one, so objecthashed structures can be
+//! referenced from IPFS and IPLD. CIDs are formatted as text in the base32
+//! multibase, like IPFS does.
+//!
+//! Note that ObjectHash digests aren't the hashes of any IPLD encoding of a
+//! value, so the codec is only a label: nothing stored under the CID in IPFS
+//! will hash to it unless it was stored as the ObjectHash input itself.
+
+use std::fmt;
+use std::str::FromStr;
+
+use {Digest, Error};
+
+/// Multicodec code for raw bytes
+pub const RAW: u64 = 0x55;
+
+/// Multicodec code for DAG-CBOR
+pub const DAG_CBOR: u64 = 0x71;
+
+/// Multicodec code for DAG-JSON
+pub const DAG_JSON: u64 = 0x0129;
+
+/// Multihash code for SHA-256
+pub const SHA2_256: u64 = 0x12;
+
+const VERSION: u64 = 1;
+const SHA2_256_LEN: usize = 32;
+
+// Prefix of the base32 multibase (RFC 4648, lowercase, without padding)
+const BASE32_PREFIX: char = 'b';
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// A version 1 content identifier for a SHA-256 digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cid {
+    codec: u64,
+    digest: Digest,
+}
+
+impl Cid {
+    /// Identify the content with the given SHA-256 digest, encoded as the
+    /// given multicodec says
+    pub fn new(codec: u64, digest: &Digest) -> Result<Self, Error> {
+        if digest.as_ref().len() != SHA2_256_LEN {
+            return Err(Error::DigestLength(digest.as_ref().len()));
+        }
+
+        Ok(Cid {
+            codec,
+            digest: *digest,
+        })
+    }
+
+    /// The multicodec code of the content's encoding
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    /// The SHA-256 digest of the content
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    /// The binary form of the CID
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SHA2_256_LEN + 8);
+        write_varint(VERSION, &mut bytes);
+        write_varint(self.codec, &mut bytes);
+        write_varint(SHA2_256, &mut bytes);
+        write_varint(SHA2_256_LEN as u64, &mut bytes);
+        bytes.extend_from_slice(self.digest.as_ref());
+        bytes
+    }
+
+    /// Parse the binary form of a CID
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut input = bytes;
+
+        if read_varint(&mut input)? != VERSION {
+            return Err(Error::InvalidCid("only CIDv1 is supported".to_owned()));
+        }
+
+        let codec = read_varint(&mut input)?;
+
+        if read_varint(&mut input)? != SHA2_256 {
+            return Err(Error::InvalidCid("only SHA-256 multihashes are supported".to_owned()));
+        }
+
+        if read_varint(&mut input)? != SHA2_256_LEN as u64 || input.len() != SHA2_256_LEN {
+            return Err(Error::InvalidCid("wrong digest length".to_owned()));
+        }
+
+        Cid::new(codec, &Digest::new(input)?)
+    }
+}
+
+impl Digest {
+    /// Wrap this digest, computed with SHA-256, into a CIDv1 for content
+    /// encoded as the given multicodec says
+    pub fn to_cid(&self, codec: u64) -> Result<Cid, Error> {
+        Cid::new(codec, self)
+    }
+}
+
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.to_bytes();
+        let mut text = String::with_capacity(1 + bytes.len().div_ceil(5) * 8);
+        text.push(BASE32_PREFIX);
+
+        let mut buffer = 0u32;
+        let mut bits = 0;
+
+        for &byte in &bytes {
+            buffer = (buffer << 8) | u32::from(byte);
+            bits += 8;
+
+            while bits >= 5 {
+                bits -= 5;
+                text.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+            }
+        }
+
+        if bits > 0 {
+            text.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+        }
+
+        f.write_str(&text)
+    }
+}
+
+impl FromStr for Cid {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let mut chars = text.chars();
+
+        if chars.next() != Some(BASE32_PREFIX) {
+            return Err(Error::InvalidCid("only base32 CIDs are supported".to_owned()));
+        }
+
+        let mut bytes = Vec::with_capacity(text.len() * 5 / 8);
+        let mut buffer = 0u32;
+        let mut bits = 0;
+
+        for c in chars {
+            let value = BASE32_ALPHABET.iter()
+                .position(|&b| b as char == c)
+                .ok_or_else(|| Error::InvalidCid(format!("invalid base32 character {:?}", c)))?;
+
+            buffer = (buffer << 5) | value as u32;
+            bits += 5;
+
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+            }
+        }
+
+        Cid::from_bytes(&bytes)
+    }
+}
+
+// Unsigned LEB128, as used by multiformats
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0u64;
+
+    // Multiformats varints are at most 9 bytes long
+    for (i, &byte) in input.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            *input = &input[i + 1..];
+            return Ok(value);
+        }
+    }
+
+    Err(Error::InvalidCid("truncated or overlong varint".to_owned()))
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use super::{Cid, DAG_JSON, RAW};
+    use {digest, hasher, Error, ObjectHasher};
+
+    #[test]
+    fn known_cids() {
+        let mut hasher = hasher::default();
+        hasher.update(b"hello world");
+        let digest = hasher.finish();
+
+        let cid = digest.to_cid(RAW).unwrap();
+        assert_eq!(cid.to_string(), "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e");
+        assert_eq!(cid.to_bytes()[..4], [0x01, 0x55, 0x12, 0x20]);
+        assert_eq!(cid.codec(), RAW);
+        assert_eq!(cid.digest(), &digest);
+    }
+
+    #[test]
+    fn round_trips() {
+        let cid = digest("foo").to_cid(DAG_JSON).unwrap();
+        assert_eq!(cid.to_bytes()[..5], [0x01, 0xa9, 0x02, 0x12, 0x20]);
+        assert_eq!(Cid::from_bytes(&cid.to_bytes()).unwrap(), cid);
+        assert_eq!(cid.to_string().parse::<Cid>().unwrap(), cid);
+
+        for invalid in &["", "zfoo", "bafkrei", "bafkrei!"] {
+            match invalid.parse::<Cid>() {
+                Err(Error::InvalidCid(_)) => (),
+                other => panic!("expected an invalid CID for {:?}, got {:?}", invalid, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Selective disclosure of dict entries
+//!
+//! A dict's digest is computed from the sorted digests of its entries, so
+//! its entries can be proven to a verifier who knows the digest while only
+//! revealing some of them: hidden entries are replaced with the digests of
+//! their key and value, which is all that's needed to recompute the digest.
+//!
+//! Hidden entries are sorted by their digests, so a disclosure doesn't leak
+//! the order they were given in.
+
+#[cfg(feature = "json")]
+use serde_json::{Map, Value};
+
+#[cfg(feature = "objecthash-ring")]
+use hasher;
+#[cfg(feature = "json")]
+use {hex, Error};
+use {Digest, ObjectHash, ObjectHasher};
+use types::{dict_member, digest_with, hash_dict_members, hash_member};
+
+/// Proof of some of the entries of a dict, which hashes the same as the dict
+#[derive(Clone, Debug, PartialEq)]
+pub struct Disclosure<K, V> {
+    /// Entries shown in cleartext
+    pub revealed: Vec<(K, V)>,
+
+    /// Digests of the keys and values of the hidden entries
+    pub hidden: Vec<(Digest, Digest)>,
+}
+
+impl<K: ObjectHash, V: ObjectHash> Disclosure<K, V> {
+    /// Disclose the entries of a dict for which `reveal` returns true, using
+    /// the default hasher for the digests of hidden entries
+    #[cfg(feature = "objecthash-ring")]
+    pub fn new<I, F>(entries: I, reveal: F) -> Self
+        where I: IntoIterator<Item = (K, V)>,
+              F: FnMut(&K, &V) -> bool
+    {
+        Self::new_with(entries, reveal, &hasher::default())
+    }
+
+    /// Disclose the entries of a dict for which `reveal` returns true, using
+    /// hashers nested within the given one for the digests of hidden entries
+    pub fn new_with<I, F, H>(entries: I, mut reveal: F, hasher: &H) -> Self
+        where I: IntoIterator<Item = (K, V)>,
+              F: FnMut(&K, &V) -> bool,
+              H: ObjectHasher
+    {
+        let mut disclosure = Disclosure {
+            revealed: Vec::new(),
+            hidden: Vec::new(),
+        };
+
+        for (k, v) in entries {
+            if reveal(&k, &v) {
+                disclosure.revealed.push((k, v));
+            } else {
+                disclosure.hidden.push((digest_with(&k, hasher), digest_with(&v, hasher)));
+            }
+        }
+
+        disclosure.hidden.sort_by(|a, b| (a.0.as_ref(), a.1.as_ref()).cmp(&(b.0.as_ref(), b.1.as_ref())));
+        disclosure
+    }
+
+    /// Check the disclosure against the digest of the whole dict
+    #[cfg(feature = "objecthash-ring")]
+    pub fn verify(&self, root: &Digest) -> bool {
+        ::digest(self) == *root
+    }
+}
+
+impl<K: ObjectHash, V: ObjectHash> ObjectHash for Disclosure<K, V> {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let mut members: Vec<_> = self.revealed
+            .iter()
+            .map(|(k, v)| hash_member(k, v, hasher))
+            .chain(self.hidden.iter().map(|(k, v)| dict_member(k, v)))
+            .collect();
+
+        hash_dict_members(&mut members, hasher);
+    }
+}
+
+#[cfg(feature = "json")]
+impl Disclosure<String, Value> {
+    /// Disclose the members of a JSON object with the given keys. Keys which
+    /// aren't in the object are ignored
+    #[cfg(feature = "objecthash-ring")]
+    pub fn from_object(object: &Map<String, Value>, keys: &[&str]) -> Self {
+        Disclosure::new(object.iter().map(|(k, v)| (k.clone(), v.clone())),
+                        |k, _| keys.contains(&k.as_str()))
+    }
+
+    /// Serialize the disclosure as JSON, with the revealed members as an
+    /// object and the hidden ones as pairs of hex digests
+    pub fn to_value(&self) -> Value {
+        let revealed: Map<String, Value> = self.revealed.iter().cloned().collect();
+        let hidden = self.hidden
+            .iter()
+            .map(|(k, v)| {
+                Value::Array(vec![Value::String(hex::encode(k.as_ref())),
+                                  Value::String(hex::encode(v.as_ref()))])
+            })
+            .collect();
+
+        let mut proof = Map::new();
+        proof.insert("revealed".to_owned(), Value::Object(revealed));
+        proof.insert("hidden".to_owned(), Value::Array(hidden));
+        Value::Object(proof)
+    }
+
+    /// Restore a disclosure serialized with `to_value`
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let invalid = |msg: &str| Error::InvalidProof(msg.to_owned());
+
+        let revealed = value.get("revealed")
+            .and_then(Value::as_object)
+            .ok_or_else(|| invalid("expected an object of revealed members"))?;
+
+        let hidden = value.get("hidden")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("expected an array of hidden members"))?;
+
+        let hidden = hidden.iter()
+            .map(|pair| {
+                match pair.as_array().map(Vec::as_slice) {
+                    Some([k, v]) => Ok((parse_digest(k)?, parse_digest(v)?)),
+                    _ => Err(invalid("expected a pair of digests")),
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Disclosure {
+            revealed: revealed.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            hidden,
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+fn parse_digest(value: &Value) -> Result<Digest, Error> {
+    value.as_str()
+        .and_then(hex::decode)
+        .and_then(|bytes| Digest::new(&bytes).ok())
+        .ok_or_else(|| Error::InvalidProof(format!("bad digest: {}", value)))
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Disclosure;
+    use digest;
+
+    #[test]
+    fn disclosing_entries() {
+        let mut dict = HashMap::new();
+        dict.insert("name".to_owned(), "Alice".to_owned());
+        dict.insert("dob".to_owned(), "1990-01-01".to_owned());
+        dict.insert("ssn".to_owned(), "078-05-1120".to_owned());
+        let root = digest(&dict);
+
+        let disclosure = Disclosure::new(&dict, |k, _| *k == "dob");
+        assert_eq!(disclosure.revealed, vec![(&"dob".to_owned(), &"1990-01-01".to_owned())]);
+        assert_eq!(disclosure.hidden.len(), 2);
+        assert!(disclosure.verify(&root));
+
+        let mut forged = disclosure.clone();
+        let dob = "2001-01-01".to_owned();
+        forged.revealed[0].1 = &dob;
+        assert!(!forged.verify(&root));
+
+        let mut truncated = disclosure.clone();
+        truncated.hidden.pop();
+        assert!(!truncated.verify(&root));
+
+        assert!(Disclosure::new(&dict, |_, _| false).verify(&root));
+        assert!(Disclosure::new(&dict, |_, _| true).verify(&root));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_disclosures() {
+        use serde_json::{self, Value};
+        use Error;
+
+        let value: Value = serde_json::from_str(r#"{"name": "Alice", "dob": "1990-01-01", "tags": [1, 2]}"#)
+            .unwrap();
+        let root = digest(&value);
+
+        let disclosure = Disclosure::from_object(value.as_object().unwrap(), &["dob", "tags"]);
+        assert!(disclosure.verify(&root));
+
+        let proof = disclosure.to_value();
+        assert_eq!(proof["revealed"],
+                   serde_json::from_str::<Value>(r#"{"dob": "1990-01-01", "tags": [1, 2]}"#).unwrap());
+        assert_eq!(proof["hidden"].as_array().unwrap().len(), 1);
+
+        let restored = Disclosure::from_value(&proof).unwrap();
+        assert!(restored.verify(&root));
+
+        match Disclosure::from_value(&serde_json::from_str(r#"{"revealed": {}, "hidden": [["00"]]}"#).unwrap()) {
+            Err(Error::InvalidProof(_)) => (),
+            other => panic!("expected an invalid proof error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use {hex, Digest, Limit};
+
+#[cfg(feature = "serde")]
+use serde;
+
+#[cfg(feature = "json")]
+use serde_json;
+
+#[cfg(feature = "schema")]
+use json::validation::Violation;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Digest bytes of a length which isn't supported, e.g. longer than the
+    /// maximum output length
+    DigestLength(usize),
+
+    /// Error raised while serializing a value through `serde`
+    Serialize(String),
+
+    /// Number which can't be represented without rounding
+    InexactNumber(String),
+
+    /// I/O error while reading input
+    Io(io::Error),
+
+    /// Malformed entry in a test vector file, at the given line
+    MalformedVector(usize),
+
+    /// Failure of the system random number generator
+    Random,
+
+    /// Malformed salts for a salted document
+    InvalidSalts(String),
+
+    /// JSON pointer which doesn't refer to a value in the document
+    InvalidPointer(String),
+
+    /// Malformed proof
+    InvalidProof(String),
+
+    /// NaN or infinite float, rejected by a hasher with strict floats
+    NonFiniteFloat(f64),
+
+    /// Extension tag which conflicts with a standard or other extension tag
+    InvalidTag(u8),
+
+    /// Value of an extension type which the hasher has no tag for
+    UnsupportedExtension(&'static str),
+
+    /// Value exceeding one of the hasher's limits
+    LimitExceeded(Limit),
+
+    /// Dict key which isn't a string, given its tag, rejected by a hasher
+    /// which only accepts string keys
+    NonStringKey(u8),
+
+    /// Dict with two keys that hash the same, given the digest of the key
+    DuplicateKey(Digest),
+
+    /// Digest with nothing stored under it in a content store
+    MissingContent(Digest),
+
+    /// Stored content which can't be decoded, or doesn't match its digest
+    InvalidContent(String),
+
+    /// Malformed or mismatched signing key
+    InvalidKey,
+
+    /// Signature which doesn't verify, or envelope for some other value
+    InvalidSignature,
+
+    /// Value behind a lock which a thread panicked while holding, rejected
+    /// by a hasher which rejects poisoned locks
+    PoisonedLock,
+
+    /// Shared value which contains itself, rejected by a hasher which
+    /// detects cycles
+    Cycle,
+
+    /// Malformed or unsupported content identifier
+    InvalidCid(String),
+
+    /// Verifiable map key which isn't as long as the map's digests, given
+    /// its length
+    KeyLength(usize),
+
+    /// Malformed JSON document
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+
+    /// Malformed JSON Schema
+    #[cfg(feature = "schema")]
+    InvalidSchema(String),
+
+    /// JSON document which doesn't match its schema, and everywhere it doesn't
+    #[cfg(feature = "schema")]
+    SchemaViolations(Vec<Violation>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::DigestLength(len) => write!(f, "unsupported digest length: {} bytes", len),
+            Error::Serialize(ref msg) => write!(f, "serialization error: {}", msg),
+            Error::InexactNumber(ref n) => write!(f, "number can't be represented exactly: {}", n),
+            Error::Io(ref err) => write!(f, "I/O error: {}", err),
+            Error::MalformedVector(line) => write!(f, "malformed test vector at line {}", line),
+            Error::Random => write!(f, "couldn't generate random bytes"),
+            Error::InvalidSalts(ref msg) => write!(f, "invalid salts: {}", msg),
+            Error::InvalidPointer(ref pointer) => write!(f, "no value at JSON pointer {:?}", pointer),
+            Error::InvalidProof(ref msg) => write!(f, "invalid proof: {}", msg),
+            Error::NonFiniteFloat(n) => write!(f, "non-finite float: {}", n),
+            Error::InvalidTag(tag) => write!(f, "tag {:?} is already in use", tag as char),
+            Error::UnsupportedExtension(name) => write!(f, "no tag for hashing {} values", name),
+            Error::LimitExceeded(limit) => write!(f, "value exceeds the {} limit", limit),
+            Error::NonStringKey(tag) => write!(f, "dict key with tag {:?} isn't a string", tag as char),
+            Error::DuplicateKey(ref key) => write!(f, "duplicate dict key: {}", hex::encode(key.as_ref())),
+            Error::MissingContent(ref digest) => write!(f, "no content for digest {}", hex::encode(digest.as_ref())),
+            Error::InvalidContent(ref msg) => write!(f, "invalid content: {}", msg),
+            Error::InvalidKey => write!(f, "invalid signing key"),
+            Error::InvalidSignature => write!(f, "invalid signature"),
+            Error::PoisonedLock => write!(f, "lock poisoned by a panicked thread"),
+            Error::Cycle => write!(f, "value contains itself"),
+            Error::InvalidCid(ref msg) => write!(f, "invalid CID: {}", msg),
+            Error::KeyLength(len) => write!(f, "map key of the wrong length: {} bytes", len),
+            #[cfg(feature = "json")]
+            Error::Json(ref err) => write!(f, "invalid JSON: {}", err),
+            #[cfg(feature = "schema")]
+            Error::InvalidSchema(ref msg) => write!(f, "invalid JSON Schema: {}", msg),
+            #[cfg(feature = "schema")]
+            Error::SchemaViolations(ref violations) => {
+                write!(f, "document doesn't match its schema")?;
+
+                for violation in violations {
+                    write!(f, "; at {:?}: {}", violation.instance_path, violation.message)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            #[cfg(feature = "json")]
+            Error::Json(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Serialize(msg.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
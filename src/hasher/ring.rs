@@ -1,15 +1,107 @@
 extern crate ring;
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use self::ring::rand::{SecureRandom, SystemRandom};
+
 use Digest;
+use Error;
+use HashProfile;
+use Limit;
+use ObjectHash;
 use ObjectHasher;
+use types::CONTEXT_TAG;
 
 pub struct Hasher {
     ctx: ring::digest::Context,
+    profile: HashProfile,
+    redacted: Option<Digest>,
+    tag: Option<u8>,
+    depth: usize,
+
+    // Purpose the outermost digest is bound to. Nested hashers have none
+    context: Option<Arc<str>>,
+
+    // State shared between this hasher and those nested within it. Only
+    // allocated by hashers which can reject values
+    shared: Option<Arc<Shared>>,
+
+    // Shared allocations being hashed by this hasher and the ones it's
+    // nested within, innermost first. Only tracked when detecting cycles
+    ancestors: Option<Arc<Ancestor>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    // First error recorded by any of the hashers
+    error: Mutex<Option<Error>>,
+
+    // Number of nested hashers created, i.e. of values hashed within the
+    // outermost one
+    elements: AtomicU64,
+}
+
+struct Ancestor {
+    address: usize,
+    parent: Option<Arc<Ancestor>>,
 }
 
 impl Hasher {
     pub fn new(alg: &'static ring::digest::Algorithm) -> Hasher {
-        Hasher { ctx: ring::digest::Context::new(alg) }
+        Hasher {
+            ctx: ring::digest::Context::new(alg),
+            profile: HashProfile::default(),
+            redacted: None,
+            tag: None,
+            depth: 0,
+            context: None,
+            shared: None,
+            ancestors: None,
+        }
+    }
+
+    /// Hash values with the given profile, here and in nested hashers. If
+    /// the profile rejects some values, `try_finish` returns an error when
+    /// any of them are hashed
+    pub fn with_profile(mut self, profile: HashProfile) -> Hasher {
+        if profile.rejects_values() {
+            self.shared.get_or_insert_with(Arc::default);
+        }
+
+        self.profile = profile;
+        self
+    }
+
+    /// Bind the digest to a context string naming what it's for (such as
+    /// `"myapp:invoice:v1"`), so the same value hashed for different purposes
+    /// has unrelated digests.
+    ///
+    /// Only the outermost digest is bound: it's `H("c" || H("u" || context)
+    /// || digest)`, where `digest` is the value's usual digest. The digests of
+    /// values nested within it, which redaction and proofs rely on, are
+    /// unaffected
+    pub fn with_context(mut self, context: &str) -> Hasher {
+        self.context = Some(Arc::from(context));
+        self
+    }
+
+    /// A fresh outermost hasher with the same settings, which reuses this
+    /// one's shared state rather than allocating its own. The state is reset,
+    /// so this is only for hashing values one at a time, once every hasher
+    /// previously renewed from this one is finished
+    pub(crate) fn renew(&self) -> Hasher {
+        if let Some(ref shared) = self.shared {
+            shared.elements.store(0, Ordering::Relaxed);
+            *shared.error.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+
+        Hasher {
+            profile: self.profile,
+            context: self.context.clone(),
+            shared: self.shared.clone(),
+            ..Hasher::new(self.ctx.algorithm)
+        }
     }
 }
 
@@ -25,8 +117,38 @@ impl ObjectHasher for Hasher {
         self.ctx.algorithm.output_len
     }
 
+    #[inline]
+    fn nested(&self) -> Self {
+        let mut nested = Hasher {
+            profile: self.profile,
+            depth: self.depth + 1,
+            shared: self.shared.clone(),
+            ancestors: self.ancestors.clone(),
+            ..Hasher::new(self.ctx.algorithm)
+        };
+
+        if let Some(ref shared) = self.shared {
+            let limits = self.profile.limits();
+            let elements = shared.elements.fetch_add(1, Ordering::Relaxed) + 1;
+
+            if limits.max_depth().is_some_and(|max| nested.depth > max) {
+                nested.fail(Error::LimitExceeded(Limit::Depth));
+            }
+
+            if limits.max_elements().is_some_and(|max| elements > max) {
+                nested.fail(Error::LimitExceeded(Limit::Elements));
+            }
+        }
+
+        nested
+    }
+
     #[inline]
     fn update(&mut self, bytes: &[u8]) {
+        if self.tag.is_none() {
+            self.tag = bytes.first().cloned();
+        }
+
         self.ctx.update(bytes);
     }
 
@@ -34,27 +156,121 @@ impl ObjectHasher for Hasher {
     fn update_nested<F>(&mut self, nested: F)
         where F: Fn(&mut Self)
     {
-        let mut nested_hasher = Hasher::new(self.ctx.algorithm);
+        let mut nested_hasher = self.nested();
         nested(&mut nested_hasher);
         self.update(nested_hasher.finish().as_ref());
     }
 
+    #[inline]
+    fn profile(&self) -> HashProfile {
+        self.profile
+    }
+
+    #[inline]
+    fn tag(&self) -> Option<u8> {
+        match self.redacted {
+            Some(_) => None,
+            None => self.tag,
+        }
+    }
+
+    fn fail(&mut self, error: Error) {
+        if let Some(ref shared) = self.shared {
+            let mut slot = shared.error.lock().unwrap_or_else(|e| e.into_inner());
+
+            if slot.is_none() {
+                *slot = Some(error);
+            }
+        }
+    }
+
+    fn enter_shared(&mut self, address: usize) -> bool {
+        if !self.profile.detect_cycles() {
+            return true;
+        }
+
+        let mut ancestor = self.ancestors.as_ref();
+
+        while let Some(a) = ancestor {
+            if a.address == address {
+                self.fail(Error::Cycle);
+                return false;
+            }
+
+            ancestor = a.parent.as_ref();
+        }
+
+        self.ancestors = Some(Arc::new(Ancestor {
+            address,
+            parent: self.ancestors.take(),
+        }));
+
+        true
+    }
+
+    fn leave_shared(&mut self) {
+        if let Some(ancestor) = self.ancestors.take() {
+            self.ancestors = ancestor.parent.clone();
+        }
+    }
+
+    #[inline]
+    fn redact(&mut self, digest: &Digest) {
+        self.redacted = Some(*digest);
+    }
+
     #[inline]
     fn finish(self) -> Digest {
-        Digest::new(self.ctx.finish().as_ref()).unwrap()
+        let alg = self.ctx.algorithm;
+        let digest = match self.redacted {
+            Some(digest) => digest,
+            None => Digest::new(self.ctx.finish().as_ref()).unwrap(),
+        };
+
+        match self.context {
+            Some(ref context) => bind_context(alg, context, &digest),
+            None => digest,
+        }
+    }
+
+    fn try_finish(mut self) -> Result<Digest, Error> {
+        let error = self.shared
+            .take()
+            .and_then(|shared| shared.error.lock().unwrap_or_else(|e| e.into_inner()).take());
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(self.finish()),
+        }
     }
 }
 
+fn bind_context(alg: &'static ring::digest::Algorithm, context: &str, digest: &Digest) -> Digest {
+    let mut context_hasher = Hasher::new(alg);
+    context.objecthash(&mut context_hasher);
+
+    let mut ctx = ring::digest::Context::new(alg);
+    ctx.update(CONTEXT_TAG);
+    ctx.update(context_hasher.finish().as_ref());
+    ctx.update(digest.as_ref());
+    Digest::new(ctx.finish().as_ref()).unwrap()
+}
+
+/// Fill the given buffer from the system random number generator
+pub fn fill_random(dest: &mut [u8]) -> Result<(), Error> {
+    SystemRandom::new().fill(dest).map_err(|_| Error::Random)
+}
+
 #[cfg(test)]
 mod tests {
     use super::Hasher;
-    use ObjectHasher;
+    use {digest, ObjectHash, ObjectHasher};
     use rustc_serialize::hex::ToHex;
 
     // From Project NESSIE
     // https://www.cosic.esat.kuleuven.be/nessie/testvectors/hash/sha/Sha-2-256.unverified.test-vectors
-    const SHA256_VECTOR_STRING: &'static str = "abcdefghijklmnopqrstuvwxyz";
-    const SHA256_VECTOR_DIGEST: &'static str = "71c480df93d6ae2f1efad1447c66c9525e316218cf51fc8d9ed832f2daf18b73";
+    const SHA256_VECTOR_STRING: &str = "abcdefghijklmnopqrstuvwxyz";
+    const SHA256_VECTOR_DIGEST: &str = "71c480df93d6ae2f1efad1447c66c9525e316218cf51fc8d9ed832f2daf18b73";
 
     #[test]
     fn sha256() {
@@ -62,4 +278,25 @@ mod tests {
         hasher.update(SHA256_VECTOR_STRING.as_bytes());
         assert_eq!(hasher.finish().as_ref().to_hex(), SHA256_VECTOR_DIGEST);
     }
+
+    #[test]
+    fn contexts() {
+        let with = |context| {
+            let mut hasher = Hasher::default().with_context(context);
+            vec!["foo", "bar"].objecthash(&mut hasher);
+            hasher.finish()
+        };
+
+        let invoice = with("myapp:invoice:v1");
+        assert!(invoice != digest(&vec!["foo", "bar"]));
+        assert!(invoice != with("myapp:receipt:v1"));
+        assert_eq!(invoice, with("myapp:invoice:v1"));
+
+        // H("c" || H("u" || context) || digest)
+        let mut hasher = Hasher::default();
+        hasher.update(b"c");
+        hasher.update(digest("myapp:invoice:v1").as_ref());
+        hasher.update(digest(&vec!["foo", "bar"]).as_ref());
+        assert_eq!(invoice, hasher.finish());
+    }
 }
@@ -0,0 +1,74 @@
+//! Hex encoding for digests and salts in text formats
+
+use std::hint;
+
+pub fn encode(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(bytes.len() * 2);
+
+    for byte in bytes {
+        hex.push(DIGITS[(byte >> 4) as usize] as char);
+        hex.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+
+    hex
+}
+
+/// Compare two byte strings in constant time. Only their contents are kept
+/// secret, not their lengths
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let difference = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    hint::black_box(difference) == 0
+}
+
+/// Compare bytes with a hex string in constant time, without decoding it
+/// into a buffer
+pub fn ct_eq_hex(bytes: &[u8], hex: &str) -> bool {
+    if hex.len() != bytes.len() * 2 {
+        return false;
+    }
+
+    let mut difference = 0u8;
+    let mut invalid = 0u8;
+
+    for (byte, pair) in bytes.iter().zip(hex.as_bytes().chunks(2)) {
+        let (high, high_invalid) = digit(pair[0]);
+        let (low, low_invalid) = digit(pair[1]);
+        difference |= byte ^ ((high << 4) | low);
+        invalid |= high_invalid | low_invalid;
+    }
+
+    hint::black_box(difference | invalid) == 0
+}
+
+// The value of a hex digit, and 1 if it isn't one, without branching on which
+// kind of digit it is
+fn digit(c: u8) -> (u8, u8) {
+    let lower = c | 0x20;
+    let is_digit = ((c as i16 - b'0' as i16) | (b'9' as i16 - c as i16)) >= 0;
+    let is_letter = ((lower as i16 - b'a' as i16) | (b'f' as i16 - lower as i16)) >= 0;
+
+    let value = (c.wrapping_sub(b'0') & 0u8.wrapping_sub(is_digit as u8)) |
+                (lower.wrapping_sub(b'a' - 10) & 0u8.wrapping_sub(is_letter as u8));
+    (value & 0xf, !(is_digit | is_letter) as u8)
+}
+
+#[cfg(feature = "json")]
+pub fn decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let high = (pair[0] as char).to_digit(16)?;
+            let low = (pair[1] as char).to_digit(16)?;
+            Some((high * 16 + low) as u8)
+        })
+        .collect()
+}
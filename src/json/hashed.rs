@@ -0,0 +1,628 @@
+//! JSON documents with cached digests for every node
+//!
+//! A `HashedValue` keeps the digest of each node of a JSON document alongside
+//! its data. Updating a node only rehashes that node and its ancestors, so
+//! keeping the digest of a large document current after small changes costs
+//! time proportional to the depth of the change rather than the size of the
+//! document.
+//!
+//! Comparing the cached digests of two documents also finds what changed
+//! between them without visiting the subtrees they have in common: see
+//! `diff`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter;
+
+use serde_json::{Map, Value};
+
+use super::hash_value;
+use {hasher, Digest, Error, HashProfile, Limit, ObjectHash, ObjectHasher, MAX_OUTPUT_LEN};
+use path::{Path, Segment};
+use types::{dict_member, hash_dict_members, key_digest_with, LIST_TAG};
+
+/// A JSON value together with its digest and those of its descendants
+#[derive(Clone, Debug, PartialEq)]
+pub struct HashedValue {
+    digest: Digest,
+    profile: HashProfile,
+    node: Node,
+
+    // Depth of the deepest value nested within this one, and how many there
+    // are, so the profile's limits are checked against the whole document
+    height: usize,
+    elements: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Leaf(Value),
+    List(Vec<HashedValue>),
+    Dict(Dict),
+}
+
+// A dict's members by key, along with their key and value digests in the
+// order they're hashed, so changing a member only moves its own entry
+#[derive(Clone, Debug, PartialEq)]
+struct Dict {
+    members: BTreeMap<String, Member>,
+
+    // How many members have each pair of digests, which is more than one
+    // where keys which normalize alike have the same value
+    sorted: BTreeMap<[u8; 2 * MAX_OUTPUT_LEN], usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Member {
+    key: Digest,
+    value: HashedValue,
+}
+
+impl HashedValue {
+    /// Hash a JSON value with the default profile, keeping the digests of
+    /// all its nodes
+    pub fn new(value: Value) -> Self {
+        Self::new_with(value, HashProfile::default()).expect("the default profile doesn't reject values")
+    }
+
+    /// Hash a JSON value with the given profile, keeping the digests of all
+    /// its nodes. Nodes set later are hashed with the same profile, and its
+    /// limits apply to the whole document as it changes
+    pub fn new_with(value: Value, profile: HashProfile) -> Result<Self, Error> {
+        let mut hasher = hasher::default().with_profile(profile);
+        let mut hashed = build(value, &mut hasher);
+        hashed.digest = hasher.try_finish()?;
+        Ok(hashed)
+    }
+
+    /// Digest of the value
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    /// Profile the value is hashed with
+    pub fn profile(&self) -> HashProfile {
+        self.profile
+    }
+
+    /// Convert back into a plain JSON value
+    pub fn to_value(&self) -> Value {
+        match self.node {
+            Node::Leaf(ref value) => value.clone(),
+            Node::List(ref l) => Value::Array(l.iter().map(HashedValue::to_value).collect()),
+            Node::Dict(ref d) => {
+                Value::Object(d.members
+                    .iter()
+                    .map(|(k, m)| (k.clone(), m.value.to_value()))
+                    .collect::<Map<_, _>>())
+            }
+        }
+    }
+
+    /// Get the node at the given path
+    pub fn get(&self, path: &Path) -> Option<&HashedValue> {
+        self.get_at(path.segments())
+    }
+
+    /// Replace the value at the given path, or add it if the path names a
+    /// missing member of an existing dict, and rehash its ancestors. Fails
+    /// without changing the document if the value is rejected by the
+    /// document's profile, or would take the document past its limits
+    pub fn set(&mut self, path: &Path, value: Value) -> Result<(), Error> {
+        let segments = path.segments();
+
+        // How many values the one being replaced counts for, if any
+        let replaced = match self.get_at(segments) {
+            Some(old) => Some(old.elements),
+            None => {
+                match segments.split_last() {
+                    Some((_, parent)) if self.get_at(parent).is_some_and(|p| matches!(p.node, Node::Dict(_))) => None,
+                    _ => return Err(Error::InvalidPointer(path.to_string())),
+                }
+            }
+        };
+
+        let value = HashedValue::new_with(value, self.profile)?;
+        let limits = self.profile.limits();
+
+        if limits.max_depth().is_some_and(|max| segments.len() + value.height > max) {
+            return Err(Error::LimitExceeded(Limit::Depth));
+        }
+
+        // A new member adds a key and a value
+        let elements = match replaced {
+            Some(old) => self.elements - old + value.elements,
+            None => self.elements + 2 + value.elements,
+        };
+
+        if limits.max_elements().is_some_and(|max| elements > max) {
+            return Err(Error::LimitExceeded(Limit::Elements));
+        }
+
+        if self.set_at(segments, value)? {
+            Ok(())
+        } else {
+            Err(Error::InvalidPointer(path.to_string()))
+        }
+    }
+
+    /// Remove the dict member or list element at the given path, and rehash
+    /// its ancestors
+    pub fn remove(&mut self, path: &Path) -> Result<Value, Error> {
+        match self.remove_at(path.segments())? {
+            Some(removed) => Ok(removed.to_value()),
+            None => Err(Error::InvalidPointer(path.to_string())),
+        }
+    }
+
+    fn get_at(&self, segments: &[Segment]) -> Option<&HashedValue> {
+        segments.iter().try_fold(self, |node, segment| node.child(segment))
+    }
+
+    fn child(&self, segment: &Segment) -> Option<&HashedValue> {
+        match self.node {
+            Node::Leaf(_) => None,
+            Node::List(ref l) => l.get(segment.as_index()?),
+            Node::Dict(ref d) => d.members.get(&segment.as_key()).map(|m| &m.value),
+        }
+    }
+
+    fn set_at(&mut self, segments: &[Segment], value: HashedValue) -> Result<bool, Error> {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => {
+                *self = value;
+                return Ok(true);
+            }
+        };
+
+        let profile = self.profile;
+        let updated = match self.node {
+            Node::Leaf(_) => false,
+            Node::List(ref mut l) => {
+                match segment.as_index().and_then(|index| l.get_mut(index)) {
+                    Some(child) => child.set_at(rest, value)?,
+                    None => false,
+                }
+            }
+            Node::Dict(ref mut d) => {
+                let key = segment.as_key();
+
+                if rest.is_empty() && !d.members.contains_key(&key) {
+                    let member = Member::new(&key, value)?;
+
+                    // Checked before it's added, so a rejected key leaves
+                    // the dict as it was
+                    if profile.reject_duplicate_keys() && d.members.values().any(|m| m.key == member.key) {
+                        return Err(Error::DuplicateKey(member.key));
+                    }
+
+                    d.insert(key, member);
+                    true
+                } else {
+                    d.update(&key, |child| child.set_at(rest, value)).unwrap_or(Ok(false))?
+                }
+            }
+        };
+
+        if updated {
+            self.rehash()?;
+        }
+
+        Ok(updated)
+    }
+
+    fn remove_at(&mut self, segments: &[Segment]) -> Result<Option<HashedValue>, Error> {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+
+        let removed = match self.node {
+            Node::Leaf(_) => None,
+            Node::List(ref mut l) => {
+                match segment.as_index() {
+                    Some(index) if index < l.len() && rest.is_empty() => Some(l.remove(index)),
+                    Some(index) if index < l.len() => l[index].remove_at(rest)?,
+                    _ => None,
+                }
+            }
+            Node::Dict(ref mut d) => {
+                if rest.is_empty() {
+                    d.remove(&segment.as_key()).map(|m| m.value)
+                } else {
+                    d.update(&segment.as_key(), |child| child.remove_at(rest)).unwrap_or(Ok(None))?
+                }
+            }
+        };
+
+        if removed.is_some() {
+            self.rehash()?;
+        }
+
+        Ok(removed)
+    }
+
+    // Recompute this node's digest and counts from its children's
+    fn rehash(&mut self) -> Result<(), Error> {
+        let mut hasher = hasher::default().with_profile(self.profile);
+        self.hash_node(&mut hasher);
+        self.digest = hasher.try_finish()?;
+        self.count();
+        Ok(())
+    }
+
+    // Hash this node into the given hasher from its children's digests
+    fn hash_node<H: ObjectHasher>(&self, hasher: &mut H) {
+        match self.node {
+            Node::Leaf(ref value) => hash_value(value, self.profile.json_numbers(), hasher),
+            Node::List(ref l) => {
+                hasher.update(LIST_TAG);
+
+                for child in l {
+                    hasher.update(child.digest.as_ref());
+                }
+            }
+            Node::Dict(ref d) => {
+                // Already sorted, but checked for duplicate keys the same way
+                // as any other dict's members
+                let mut members: Vec<&[u8; 2 * MAX_OUTPUT_LEN]> = d.sorted
+                    .iter()
+                    .flat_map(|(member, &count)| iter::repeat_n(member, count))
+                    .collect();
+
+                hash_dict_members(&mut members, hasher);
+            }
+        }
+    }
+
+    // Count the values nested within this node from its children's counts,
+    // the way hashers count them against their profile's limits: the depth
+    // of the deepest, and one for each list element, dict key and dict value
+    fn count(&mut self) {
+        let (height, elements) = match self.node {
+            Node::Leaf(_) => (0, 0),
+            Node::List(ref l) => {
+                (l.iter().map(|child| child.height + 1).max().unwrap_or(0),
+                 l.iter().map(|child| child.elements + 1).sum())
+            }
+            Node::Dict(ref d) => {
+                (d.members.values().map(|m| m.value.height + 1).max().unwrap_or(0),
+                 d.members.values().map(|m| m.value.elements + 2).sum())
+            }
+        };
+
+        self.height = height;
+        self.elements = elements;
+    }
+}
+
+// Build the node of a value, hashing it into the given hasher and each of its
+// children into hashers nested within it, so they're checked and counted
+// like those of the value itself. The caller fills in its digest
+fn build<H: ObjectHasher>(value: Value, hasher: &mut H) -> HashedValue {
+    let node = match value {
+        Value::Array(a) => Node::List(a.into_iter().map(|v| build_nested(v, hasher)).collect()),
+        Value::Object(o) => {
+            let mut dict = Dict {
+                members: BTreeMap::new(),
+                sorted: BTreeMap::new(),
+            };
+
+            for (k, v) in o {
+                let key = key_digest_with(&k, hasher);
+                let value = build_nested(v, hasher);
+                dict.insert(k, Member { key, value });
+            }
+
+            Node::Dict(dict)
+        }
+        leaf => Node::Leaf(leaf),
+    };
+
+    let mut hashed = HashedValue {
+        digest: Digest::new(&[]).unwrap(),
+        profile: hasher.profile(),
+        node,
+        height: 0,
+        elements: 0,
+    };
+
+    hashed.hash_node(hasher);
+    hashed.count();
+    hashed
+}
+
+fn build_nested<H: ObjectHasher>(value: Value, hasher: &H) -> HashedValue {
+    let mut nested = hasher.nested();
+    let mut hashed = build(value, &mut nested);
+    hashed.digest = nested.finish();
+    hashed
+}
+
+impl Dict {
+    fn insert(&mut self, key: String, member: Member) {
+        *self.sorted.entry(member.digests()).or_insert(0) += 1;
+
+        if let Some(replaced) = self.members.insert(key, member) {
+            unsort(&mut self.sorted, &replaced);
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Member> {
+        let removed = self.members.remove(key)?;
+        unsort(&mut self.sorted, &removed);
+        Some(removed)
+    }
+
+    // Change the value of the member with the given key, moving its entry
+    // among the sorted digests to match
+    fn update<T, F: FnOnce(&mut HashedValue) -> T>(&mut self, key: &str, f: F) -> Option<T> {
+        let member = self.members.get_mut(key)?;
+        unsort(&mut self.sorted, member);
+        let result = f(&mut member.value);
+        *self.sorted.entry(member.digests()).or_insert(0) += 1;
+        Some(result)
+    }
+}
+
+fn unsort(sorted: &mut BTreeMap<[u8; 2 * MAX_OUTPUT_LEN], usize>, member: &Member) {
+    let digests = member.digests();
+
+    if let Some(count) = sorted.get_mut(&digests) {
+        *count -= 1;
+
+        if *count == 0 {
+            sorted.remove(&digests);
+        }
+    }
+}
+
+impl Member {
+    // A member to add to a dict, with its key hashed like any other and
+    // rejected if the value's profile doesn't accept it
+    fn new(key: &str, value: HashedValue) -> Result<Self, Error> {
+        let hasher = hasher::default().with_profile(value.profile);
+        let key = key_digest_with(key, &hasher);
+        hasher.try_finish()?;
+        Ok(Member { key, value })
+    }
+
+    fn digests(&self) -> [u8; 2 * MAX_OUTPUT_LEN] {
+        dict_member(&self.key, &self.value.digest)
+    }
+}
+
+/// Paths of the nodes which differ between two documents, in order.
+///
+/// Subtrees with the same digest are skipped without being visited. Where
+/// two lists or dicts differ, their elements or members are compared in
+/// turn, and those present in only one of them are reported by their own
+/// paths. Any other nodes which differ, including a list or dict replaced by
+/// a node of another type, are reported as a whole.
+pub fn diff(a: &HashedValue, b: &HashedValue) -> Vec<Path> {
+    let mut paths = Vec::new();
+    diff_at(a, b, &mut Path::root(), &mut paths);
+    paths
+}
+
+fn diff_at(a: &HashedValue, b: &HashedValue, path: &mut Path, paths: &mut Vec<Path>) {
+    if a.digest == b.digest {
+        return;
+    }
+
+    match (&a.node, &b.node) {
+        (Node::List(l), Node::List(m)) => {
+            for index in 0..l.len().max(m.len()) {
+                path.push(Segment::Index(index));
+
+                match (l.get(index), m.get(index)) {
+                    (Some(x), Some(y)) => diff_at(x, y, path, paths),
+                    _ => paths.push(path.clone()),
+                }
+
+                path.pop();
+            }
+        }
+        (Node::Dict(d), Node::Dict(e)) => {
+            let keys: BTreeSet<&String> = d.members.keys().chain(e.members.keys()).collect();
+
+            for key in keys {
+                path.push(Segment::Key(key.clone()));
+
+                match (d.members.get(key), e.members.get(key)) {
+                    (Some(x), Some(y)) => diff_at(&x.value, &y.value, path, paths),
+                    _ => paths.push(path.clone()),
+                }
+
+                path.pop();
+            }
+        }
+        _ => paths.push(path.clone()),
+    }
+}
+
+/// Contributes the cached digest to the structure containing it
+impl ObjectHash for HashedValue {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hasher.redact(&self.digest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::{diff, HashedValue};
+    use path::Path;
+    use {digest, hasher, Digest, Error, HashProfile, Limit, Limits, ObjectHash, ObjectHasher};
+
+    fn document() -> Value {
+        serde_json::from_str(r#"{"users": [{"name": "Alice", "tags": []}, {"name": "Bob"}], "count": 2}"#).unwrap()
+    }
+
+    fn digest_with(value: &Value, profile: HashProfile) -> Result<Digest, Error> {
+        let mut hasher = hasher::default().with_profile(profile);
+        value.objecthash(&mut hasher);
+        hasher.try_finish()
+    }
+
+    #[test]
+    fn hashing() {
+        let value = document();
+        let hashed = HashedValue::new(value.clone());
+
+        assert_eq!(*hashed.digest(), digest(&value));
+        assert_eq!(hashed.to_value(), value);
+        assert_eq!(*hashed.get(&"/users/1".parse().unwrap()).unwrap().digest(),
+                   digest(&value["users"][1]));
+        assert_eq!(digest(&vec![hashed.clone()]), digest(&vec![value]));
+    }
+
+    #[test]
+    fn updating() {
+        let mut value = document();
+        let mut hashed = HashedValue::new(value.clone());
+
+        let path = Path::root().key("users").index(0).key("name");
+        hashed.set(&path, "Carol".into()).unwrap();
+        value["users"][0]["name"] = "Carol".into();
+        assert_eq!(*hashed.digest(), digest(&value));
+
+        hashed.set(&Path::root().key("users").index(1).key("id"), 7.into()).unwrap();
+        value["users"][1]["id"] = 7.into();
+        assert_eq!(*hashed.digest(), digest(&value));
+
+        assert_eq!(hashed.remove(&"/users/0".parse().unwrap()).unwrap(), value["users"][0]);
+        value["users"].as_array_mut().unwrap().remove(0);
+        assert_eq!(*hashed.digest(), digest(&value));
+        assert_eq!(hashed.to_value(), value);
+
+        hashed.set(&Path::root(), Value::Null).unwrap();
+        assert_eq!(*hashed.digest(), digest(&Value::Null));
+    }
+
+    #[test]
+    fn profiles() {
+        let profile = HashProfile::common_json();
+        let mut value = document();
+        let mut hashed = HashedValue::new_with(value.clone(), profile).unwrap();
+        assert_eq!(hashed.profile(), profile);
+        assert_eq!(*hashed.digest(), digest_with(&value, profile).unwrap());
+        assert!(*hashed.digest() != digest(&value));
+
+        hashed.set(&"/users/1/id".parse().unwrap(), 7.into()).unwrap();
+        value["users"][1]["id"] = 7.into();
+        assert_eq!(*hashed.digest(), digest_with(&value, profile).unwrap());
+        assert_eq!(*hashed.get(&"/users/1".parse().unwrap()).unwrap().digest(),
+                   digest_with(&value["users"][1], profile).unwrap());
+    }
+
+    #[test]
+    fn rejected_values() {
+        let reject = HashProfile::default().with_reject_duplicate_keys(true);
+        let json = r#"{"caf\u00e9": 1, "cafe\u0301": 2}"#;
+        match HashedValue::new_with(serde_json::from_str(json).unwrap(), reject) {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, digest("caf\u{e9}")),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+
+        let mut hashed = HashedValue::new_with(serde_json::from_str(r#"{"caf\u00e9": 1}"#).unwrap(), reject).unwrap();
+        let before = hashed.clone();
+        match hashed.set(&Path::root().key("cafe\u{301}"), 2.into()) {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, digest("caf\u{e9}")),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+        assert_eq!(hashed, before);
+    }
+
+    #[test]
+    fn limits() {
+        // The document nests 12 values, 3 deep
+        let value = document();
+        for &(limits, ok) in &[(Limits::default().with_max_elements(12), true),
+                               (Limits::default().with_max_elements(11), false),
+                               (Limits::default().with_max_depth(3), true),
+                               (Limits::default().with_max_depth(2), false)] {
+            let profile = HashProfile::default().with_limits(limits);
+            assert_eq!(HashedValue::new_with(value.clone(), profile).is_ok(), ok);
+            assert_eq!(digest_with(&value, profile).is_ok(), ok);
+        }
+
+        // Limits apply to the whole document, not each node on its own
+        let profile = HashProfile::default().with_limits(Limits::default().with_max_depth(3).with_max_elements(13));
+        let mut hashed = HashedValue::new_with(value, profile).unwrap();
+        let before = hashed.clone();
+
+        match hashed.set(&"/users/0/tags".parse().unwrap(), vec![1].into()) {
+            Err(Error::LimitExceeded(Limit::Depth)) => (),
+            other => panic!("expected a depth limit error, got {:?}", other),
+        }
+        match hashed.set(&"/users/1/id".parse().unwrap(), 7.into()) {
+            Err(Error::LimitExceeded(Limit::Elements)) => (),
+            other => panic!("expected an element limit error, got {:?}", other),
+        }
+        assert_eq!(hashed, before);
+
+        hashed.remove(&"/count".parse().unwrap()).unwrap();
+        hashed.set(&"/users/1/id".parse().unwrap(), 7.into()).unwrap();
+        assert!(hashed.set(&"/users/1/tags".parse().unwrap(), Value::Array(vec![])).is_err());
+        assert!(hashed.set(&"/users/1".parse().unwrap(), vec![1, 2, 3, 4, 5, 6].into()).is_err());
+        hashed.set(&"/users/1".parse().unwrap(), vec![1, 2, 3].into()).unwrap();
+        assert_eq!(*hashed.digest(), digest_with(&hashed.to_value(), profile).unwrap());
+    }
+
+    #[test]
+    fn keys_which_normalize_alike() {
+        let mut value: Value = serde_json::from_str(r#"{"caf\u00e9": 1, "cafe\u0301": 1, "a": 2}"#).unwrap();
+        let mut hashed = HashedValue::new(value.clone());
+        assert_eq!(*hashed.digest(), digest(&value));
+
+        hashed.set(&Path::root().key("a"), 1.into()).unwrap();
+        value["a"] = 1.into();
+        assert_eq!(*hashed.digest(), digest(&value));
+
+        hashed.remove(&Path::root().key("cafe\u{301}")).unwrap();
+        value.as_object_mut().unwrap().remove("cafe\u{301}");
+        assert_eq!(*hashed.digest(), digest(&value));
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let mut hashed = HashedValue::new(document());
+        let before = hashed.clone();
+
+        for pointer in &["/users/2", "/users/0/name/x", "/missing/name", "/count/0"] {
+            match hashed.set(&pointer.parse().unwrap(), Value::Null) {
+                Err(Error::InvalidPointer(ref p)) if p == pointer => (),
+                other => panic!("expected an invalid pointer error, got {:?}", other),
+            }
+
+            assert!(hashed.remove(&pointer.parse().unwrap()).is_err());
+        }
+
+        assert!(hashed.remove(&Path::root()).is_err());
+        assert_eq!(hashed, before);
+    }
+
+    #[test]
+    fn diffing() {
+        let a = HashedValue::new(document());
+        assert!(diff(&a, &a.clone()).is_empty());
+
+        let mut b = a.clone();
+        b.set(&"/users/1/name".parse().unwrap(), "Robert".into()).unwrap();
+        b.set(&"/count".parse().unwrap(), "two".into()).unwrap();
+        b.set(&"/added".parse().unwrap(), true.into()).unwrap();
+        b.remove(&"/users/0/tags".parse().unwrap()).unwrap();
+
+        let paths: Vec<String> = diff(&a, &b).iter().map(Path::to_string).collect();
+        assert_eq!(paths, vec!["/added", "/count", "/users/0/tags", "/users/1/name"]);
+
+        let c = HashedValue::new(serde_json::from_str(r#"{"users": [], "count": {"n": 2}}"#).unwrap());
+        let paths: Vec<String> = diff(&a, &c).iter().map(Path::to_string).collect();
+        assert_eq!(paths, vec!["/count", "/users/0", "/users/1"]);
+
+        assert_eq!(diff(&a, &HashedValue::new(Value::Null)), vec![Path::root()]);
+    }
+}
@@ -0,0 +1,248 @@
+//! RFC 8785 JSON Canonicalization Scheme (JCS) output alongside ObjectHash
+//!
+//! Produces the JCS serialization of a JSON value and its ObjectHash digest
+//! in a single traversal, for cross-checking systems which use either
+//! representation. Note that JCS leaves strings as-is, whereas ObjectHash
+//! hashes them in NFC form, so two documents which differ only in their
+//! Unicode normalization have the same digest but different JCS bytes.
+
+#[cfg(all(feature = "objecthash-ring", feature = "serde"))]
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+
+use super::{hash_number, NumberMode};
+#[cfg(feature = "objecthash-ring")]
+use {hasher, Digest};
+use {Error, ObjectHash, ObjectHasher};
+use types::{dict_member, format_double, hash_dict_members, key_digest_with, LIST_TAG};
+
+/// ObjectHash digest of a JSON value, together with its JCS serialization
+#[cfg(feature = "objecthash-ring")]
+pub struct Canonical {
+    pub digest: Digest,
+    pub json: Vec<u8>,
+}
+
+/// Compute the ObjectHash digest and JCS serialization of a JSON value
+#[cfg(feature = "objecthash-ring")]
+pub fn canonicalize(value: &Value) -> Result<Canonical, Error> {
+    let mut hasher = hasher::default();
+    let mut json = Vec::new();
+    canonicalize_with(value, NumberMode::Native, &mut hasher, &mut json)?;
+
+    Ok(Canonical {
+        digest: hasher.finish(),
+        json,
+    })
+}
+
+/// Compute the ObjectHash digest and JCS serialization of a `Serialize` type,
+/// by way of its `serde_json` representation
+#[cfg(all(feature = "objecthash-ring", feature = "serde"))]
+pub fn canonicalize_serialize<T: Serialize + ?Sized>(value: &T) -> Result<Canonical, Error> {
+    canonicalize(&::serde_json::to_value(value)?)
+}
+
+/// Hash a JSON value into the given hasher using the given number mode,
+/// while appending its JCS serialization to `out`
+pub fn canonicalize_with<H: ObjectHasher>(value: &Value,
+                                          mode: NumberMode,
+                                          hasher: &mut H,
+                                          out: &mut Vec<u8>)
+                                          -> Result<(), Error> {
+    match *value {
+        Value::Null => {
+            out.extend_from_slice(b"null");
+            ().objecthash(hasher);
+        }
+        Value::Bool(b) => {
+            out.extend_from_slice(if b { b"true" } else { b"false" });
+            b.objecthash(hasher);
+        }
+        Value::Number(ref n) => {
+            write_number(n, out)?;
+            hash_number(n, mode, hasher);
+        }
+        Value::String(ref s) => {
+            write_string(s, out);
+            s.objecthash(hasher);
+        }
+        Value::Array(ref a) => {
+            out.push(b'[');
+            hasher.update(LIST_TAG);
+
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+
+                let mut nested_hasher = hasher.nested();
+                canonicalize_with(v, mode, &mut nested_hasher, out)?;
+                hasher.update(nested_hasher.finish().as_ref());
+            }
+
+            out.push(b']');
+        }
+        Value::Object(ref o) => canonicalize_object(o, mode, hasher, out)?,
+    }
+
+    Ok(())
+}
+
+fn canonicalize_object<H: ObjectHasher>(object: &Map<String, Value>,
+                                        mode: NumberMode,
+                                        hasher: &mut H,
+                                        out: &mut Vec<u8>)
+                                        -> Result<(), Error> {
+    // JCS orders members by the UTF-16 code units of their keys
+    let mut members: Vec<(&String, &Value)> = object.iter().collect();
+    members.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()));
+
+    let mut digests = Vec::with_capacity(members.len());
+
+    out.push(b'{');
+
+    for (i, &(k, v)) in members.iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+
+        write_string(k, out);
+        out.push(b':');
+
+        let key_digest = key_digest_with(k, hasher);
+
+        let mut value_hasher = hasher.nested();
+        canonicalize_with(v, mode, &mut value_hasher, out)?;
+        digests.push(dict_member(&key_digest, &value_hasher.finish()));
+    }
+
+    out.push(b'}');
+    hash_dict_members(&mut digests, hasher);
+    Ok(())
+}
+
+fn write_string(s: &str, out: &mut Vec<u8>) {
+    out.push(b'"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.extend_from_slice(b"\\\""),
+            '\\' => out.extend_from_slice(b"\\\\"),
+            '\u{08}' => out.extend_from_slice(b"\\b"),
+            '\u{0c}' => out.extend_from_slice(b"\\f"),
+            '\n' => out.extend_from_slice(b"\\n"),
+            '\r' => out.extend_from_slice(b"\\r"),
+            '\t' => out.extend_from_slice(b"\\t"),
+            c if c < ' ' => out.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes()),
+            c => out.extend_from_slice(c.encode_utf8(&mut [0u8; 4]).as_bytes()),
+        }
+    }
+
+    out.push(b'"');
+}
+
+// JCS numbers are IEEE 754 doubles, so numbers which would change value on
+// the way to one are rejected rather than silently rounded. Integers have to
+// be exactly representable. Decimals are read as the nearest double, which
+// 17 significant digits are enough to pick out, so with arbitrary precision
+// those with more digits than that would lose precision doubles don't have
+fn write_number(n: &Number, out: &mut Vec<u8>) -> Result<(), Error> {
+    let float = n.as_f64().unwrap_or(f64::NAN);
+
+    let exact = if let Some(i) = n.as_i64() {
+        float as i64 == i && float != 9_223_372_036_854_775_808.0
+    } else if let Some(u) = n.as_u64() {
+        float as u64 == u && float != 18_446_744_073_709_551_616.0
+    } else {
+        float.is_finite() && significant_digits(&n.to_string()) <= 17
+    };
+
+    if !exact {
+        return Err(Error::InexactNumber(n.to_string()));
+    }
+
+    out.extend_from_slice(format_double(float).as_bytes());
+    Ok(())
+}
+
+// Number of digits in a JSON number's mantissa, not counting leading or
+// trailing zeros
+fn significant_digits(number: &str) -> usize {
+    let mantissa = number.split(['e', 'E']).next().unwrap_or("");
+    let digits: Vec<u8> = mantissa.bytes().filter(u8::is_ascii_digit).collect();
+
+    let start = digits.iter().position(|&d| d != b'0').unwrap_or(digits.len());
+    let end = digits.iter().rposition(|&d| d != b'0').map_or(start, |i| i + 1);
+    end - start
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::canonicalize;
+    use types::format_double;
+    use {digest, Error};
+
+    fn jcs(json: &str) -> String {
+        let value: Value = serde_json::from_str(json).unwrap();
+        let canonical = canonicalize(&value).unwrap();
+        assert_eq!(canonical.digest.as_ref(), digest(&value).as_ref());
+        String::from_utf8(canonical.json).unwrap()
+    }
+
+    #[test]
+    fn rfc8785_example() {
+        let input = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#;
+
+        assert_eq!(jcs(input),
+                   r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#);
+    }
+
+    #[test]
+    fn utf16_key_ordering() {
+        let input = r#"{"\u20ac": 1, "\r": 2, "\ufb33": 3, "1": 4, "\ud83d\ude00": 5, "\u0080": 6, "\u00f6": 7}"#;
+        assert_eq!(jcs(input), "{\"\\r\":2,\"1\":4,\"\u{80}\":6,\"ö\":7,\"€\":1,\"😀\":5,\"\u{fb33}\":3}");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(format_double(-0.0), "0");
+        assert_eq!(format_double(1.0), "1");
+        assert_eq!(format_double(-1.5), "-1.5");
+        assert_eq!(format_double(1e20), "100000000000000000000");
+        assert_eq!(format_double(1e21), "1e+21");
+        assert_eq!(format_double(1e-6), "0.000001");
+        assert_eq!(format_double(1e-7), "1e-7");
+        assert_eq!(format_double(123e-20), "1.23e-18");
+        assert_eq!(format_double(5e-324), "5e-324");
+        assert_eq!(format_double(1.7976931348623157e308), "1.7976931348623157e+308");
+        assert_eq!(format_double(9007199254740992.0), "9007199254740992");
+
+        assert_eq!(jcs("[9007199254740992, -9007199254740992]"), "[9007199254740992,-9007199254740992]");
+
+        match canonicalize(&serde_json::from_str("9007199254740993").unwrap()) {
+            Err(Error::InexactNumber(_)) => (),
+            _ => panic!("expected an inexact number error"),
+        }
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn arbitrary_precision() {
+        assert_eq!(jcs("[4.50000000000000000000, 1E+30, 0.00100]"), "[4.5,1e+30,0.001]");
+
+        for json in &["0.10000000000000000001", "18446744073709551616", "[1.000000000000000001]", "1e400"] {
+            match canonicalize(&serde_json::from_str(json).unwrap()) {
+                Err(Error::InexactNumber(_)) => (),
+                other => panic!("expected an inexact number error for {}, got {:?}", json, other.map(|c| c.json)),
+            }
+        }
+    }
+}
@@ -0,0 +1,627 @@
+//! ObjectHash support for `serde_json::Value`
+//!
+//! By default, JSON numbers which are integers (i.e. representable as `i64`
+//! or `u64`) are hashed with the integer tag, and all other numbers as
+//! floats. The reference implementations' "common JSON" mode, which hashes
+//! every number as a float, is available through the `CommonJson` wrapper,
+//! or for every JSON value hashed by a hasher whose `HashProfile` selects it.
+
+#[cfg(feature = "objecthash-ring")]
+use serde_json;
+use std::iter;
+use std::slice;
+
+use serde_json::map;
+use serde_json::{Map, Number, Value};
+
+#[cfg(feature = "objecthash-ring")]
+pub mod hashed;
+pub mod jcs;
+#[cfg(feature = "objecthash-ring")]
+pub mod ndjson;
+pub mod reader;
+pub mod redaction;
+#[cfg(feature = "schema")]
+pub mod validation;
+pub mod vectors;
+
+pub use profile::NumberMode;
+
+#[cfg(feature = "objecthash-ring")]
+use hasher;
+use Error;
+use {Digest, ObjectHash, ObjectHasher, MAX_OUTPUT_LEN};
+use path::Path;
+use types::{dict_member, hash_dict_members, LIST_TAG};
+#[cfg(feature = "arbitrary-precision")]
+use types::INTEGER_TAG;
+
+/// Hashes the wrapped value in common-JSON mode, i.e. with all numbers
+/// (including integers) hashed as floats
+pub struct CommonJson<'a>(pub &'a Value);
+
+impl<'a> ObjectHash for CommonJson<'a> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_value(self.0, NumberMode::CommonJson, hasher);
+    }
+}
+
+impl ObjectHash for Value {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_value(self, hasher.profile().json_numbers(), hasher);
+    }
+}
+
+impl ObjectHash for Number {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_number(self, hasher.profile().json_numbers(), hasher);
+    }
+}
+
+impl ObjectHash for Map<String, Value> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_object(self, hasher.profile().json_numbers(), hasher);
+    }
+}
+
+/// Parse a JSON document and compute its ObjectHash digest, hashing
+/// integers with the integer tag
+#[cfg(feature = "objecthash-ring")]
+pub fn hash_str(json: &str) -> Result<Digest, Error> {
+    hash_str_with(json, NumberMode::Native)
+}
+
+/// Parse a JSON document and compute its ObjectHash digest using the given
+/// number mode
+#[cfg(feature = "objecthash-ring")]
+pub fn hash_str_with(json: &str, mode: NumberMode) -> Result<Digest, Error> {
+    let value: Value = serde_json::from_str(json)?;
+    let mut hasher = hasher::default();
+    hash_value(&value, mode, &mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Get the value at the given path within a JSON document
+pub fn select<'a>(value: &'a Value, path: &Path) -> Option<&'a Value> {
+    path.segments().iter().try_fold(value, |value, segment| {
+        match *value {
+            Value::Array(ref a) => a.get(segment.as_index()?),
+            Value::Object(ref o) => o.get(&segment.as_key()),
+            _ => None,
+        }
+    })
+}
+
+/// Compute the ObjectHash digest of the value at the given path within a
+/// JSON document, without copying it out of the document
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_at_path(value: &Value, path: &Path) -> Option<Digest> {
+    select(value, path).map(|subtree| {
+        let mut hasher = hasher::default();
+        hash_value(subtree, NumberMode::Native, &mut hasher);
+        hasher.finish()
+    })
+}
+
+/// Get a mutable reference to the value at the given path within a JSON
+/// document
+pub fn select_mut<'a>(value: &'a mut Value, path: &Path) -> Option<&'a mut Value> {
+    path.segments().iter().try_fold(value, |value, segment| {
+        match *value {
+            Value::Array(ref mut a) => a.get_mut(segment.as_index()?),
+            Value::Object(ref mut o) => o.get_mut(&segment.as_key()),
+            _ => None,
+        }
+    })
+}
+
+// Lists and dicts are hashed with an explicit stack rather than recursively,
+// so that hashing deeply nested documents can't overflow the call stack
+fn hash_value<H: ObjectHasher>(value: &Value, mode: NumberMode, hasher: &mut H) {
+    hash_value_with(value, &mut Scalars(mode), hasher);
+}
+
+fn hash_value_with<L: Leaves, H: ObjectHasher>(value: &Value, leaves: &mut L, hasher: &mut H) {
+    match Frame::of(value) {
+        Some(frame) => hash_nested(frame, leaves, hasher),
+        None => leaves.hash_leaf(value, hasher),
+    }
+}
+
+// Hashes the leaves of a document (nulls, booleans, numbers and strings) as
+// `hash_nested` reaches them, which tells it where they are in the document
+trait Leaves {
+    fn hash_leaf<H: ObjectHasher>(&mut self, value: &Value, hasher: &mut H);
+
+    // Called before and after hashing each element or member value
+    fn enter(&mut self, _token: Token) {}
+    fn leave(&mut self) {}
+}
+
+// Where an element or member value is within its list or dict
+enum Token<'a> {
+    Index(usize),
+    Key(&'a str),
+}
+
+// Hashes leaves as they are
+struct Scalars(NumberMode);
+
+impl Leaves for Scalars {
+    fn hash_leaf<H: ObjectHasher>(&mut self, value: &Value, hasher: &mut H) {
+        hash_scalar(value, self.0, hasher);
+    }
+}
+
+fn hash_scalar<H: ObjectHasher>(value: &Value, mode: NumberMode, hasher: &mut H) {
+    match *value {
+        Value::Null => ().objecthash(hasher),
+        Value::Bool(ref b) => b.objecthash(hasher),
+        Value::Number(ref n) => hash_number(n, mode, hasher),
+        Value::String(ref s) => s.objecthash(hasher),
+        Value::Array(_) | Value::Object(_) => hash_value(value, mode, hasher),
+    }
+}
+
+fn hash_number<H: ObjectHasher>(number: &Number, mode: NumberMode, hasher: &mut H) {
+    if mode == NumberMode::Native {
+        if let Some(n) = number.as_i64() {
+            return n.objecthash(hasher);
+        } else if let Some(n) = number.as_u64() {
+            return n.objecthash(hasher);
+        }
+
+        // With arbitrary precision, integers too big for 64 bits keep their
+        // digits, which are already in the integer tag's canonical form
+        #[cfg(feature = "arbitrary-precision")]
+        {
+            let text = number.to_string();
+
+            if !text.contains(['.', 'e', 'E']) {
+                hasher.update(INTEGER_TAG);
+                hasher.update(text.as_bytes());
+                return;
+            }
+        }
+    }
+
+    // Only numbers out of a float's range (with arbitrary precision) have no
+    // float, and parsing them gives infinity
+    let n = number.as_f64().unwrap_or_else(|| number.to_string().parse().unwrap_or(f64::NAN));
+
+    if hasher.profile().exact_numbers() && !is_exact(number, n) {
+        hasher.fail(Error::InexactNumber(number.to_string()));
+    }
+
+    n.objecthash(hasher);
+}
+
+/// Is the given float exactly the number it was parsed from? Integers past
+/// 2^53 and (with arbitrary precision) decimals with more digits than a
+/// float holds are rounded
+fn is_exact(number: &Number, float: f64) -> bool {
+    if let Some(i) = number.as_i64() {
+        float as i64 == i && float != 9_223_372_036_854_775_808.0
+    } else if let Some(u) = number.as_u64() {
+        float as u64 == u && float != 18_446_744_073_709_551_616.0
+    } else {
+        float.is_finite() && same_decimal(number, float)
+    }
+}
+
+#[cfg(not(feature = "arbitrary-precision"))]
+fn same_decimal(_number: &Number, _float: f64) -> bool {
+    // The number was parsed as this float in the first place
+    true
+}
+
+#[cfg(feature = "arbitrary-precision")]
+fn same_decimal(number: &Number, float: f64) -> bool {
+    // Rust's exponential formatting yields the shortest digits which round
+    // trip to the float
+    decimal_parts(&number.to_string()) == decimal_parts(&format!("{:e}", float))
+}
+
+// Sign, significant digits and exponent of a number's text, which are the
+// same for texts of the same number
+#[cfg(feature = "arbitrary-precision")]
+fn decimal_parts(text: &str) -> (bool, String, i64) {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], text[i + 1..].parse::<i64>().unwrap_or(i64::MAX)),
+        None => (text, 0),
+    };
+
+    let (int, frac) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+        None => (mantissa, ""),
+    };
+
+    let digits = format!("{}{}", int, frac);
+    let digits = digits.trim_start_matches('0');
+    let significant = digits.trim_end_matches('0');
+
+    if significant.is_empty() {
+        return (false, String::new(), 0);
+    }
+
+    let exponent = exponent.saturating_sub(frac.len() as i64)
+        .saturating_add((digits.len() - significant.len()) as i64);
+
+    (negative, significant.to_owned(), exponent)
+}
+
+fn hash_object<H: ObjectHasher>(object: &Map<String, Value>, mode: NumberMode, hasher: &mut H) {
+    hash_nested(Frame::dict(object), &mut Scalars(mode), hasher);
+}
+
+// A list or dict which is partway through being hashed
+enum Frame<'a> {
+    List(iter::Enumerate<slice::Iter<'a, Value>>),
+    Dict {
+        entries: map::Iter<'a>,
+        key: Option<Digest>,
+        members: Vec<[u8; 2 * MAX_OUTPUT_LEN]>,
+    },
+}
+
+impl<'a> Frame<'a> {
+    fn of(value: &'a Value) -> Option<Self> {
+        match *value {
+            Value::Array(ref a) => Some(Frame::List(a.iter().enumerate())),
+            Value::Object(ref o) => Some(Frame::dict(o)),
+            _ => None,
+        }
+    }
+
+    fn dict(object: &'a Map<String, Value>) -> Self {
+        Frame::Dict {
+            entries: object.iter(),
+            key: None,
+            members: Vec::with_capacity(object.len()),
+        }
+    }
+
+    fn open<H: ObjectHasher>(&self, hasher: &mut H) {
+        if let Frame::List(_) = *self {
+            hasher.update(LIST_TAG);
+        }
+    }
+
+    // The next element or member value and where it is, hashing its key
+    fn next<H: ObjectHasher>(&mut self, hasher: &H) -> Option<(Token<'a>, &'a Value)> {
+        match *self {
+            Frame::List(ref mut elements) => elements.next().map(|(i, v)| (Token::Index(i), v)),
+            Frame::Dict { ref mut entries, ref mut key, .. } => {
+                entries.next().map(|(k, v)| {
+                    let mut key_hasher = hasher.nested();
+                    k.objecthash(&mut key_hasher);
+                    *key = Some(key_hasher.finish());
+                    (Token::Key(k), v)
+                })
+            }
+        }
+    }
+
+    // Add the digest of the last element or member value returned by `next`
+    fn add<H: ObjectHasher>(&mut self, digest: &Digest, hasher: &mut H) {
+        match *self {
+            Frame::List(_) => hasher.update(digest.as_ref()),
+            Frame::Dict { ref mut key, ref mut members, .. } => {
+                let key = key.take().expect("member value without a key");
+                members.push(dict_member(&key, digest));
+            }
+        }
+    }
+
+    fn close<H: ObjectHasher>(self, hasher: &mut H) {
+        if let Frame::Dict { mut members, .. } = self {
+            hash_dict_members(&mut members, hasher);
+        }
+    }
+}
+
+fn hash_nested<L: Leaves, H: ObjectHasher>(root: Frame, leaves: &mut L, hasher: &mut H) {
+    // Each frame but the outermost one is hashed into a nested hasher
+    let mut stack: Vec<(Frame, Option<H>)> = Vec::new();
+    root.open(hasher);
+    stack.push((root, None));
+
+    while let Some((mut frame, mut nested)) = stack.pop() {
+        let child = {
+            let frame_hasher = nested.as_mut().unwrap_or(&mut *hasher);
+
+            match frame.next(frame_hasher) {
+                Some((token, child)) => {
+                    leaves.enter(token);
+                    let mut child_hasher = frame_hasher.nested();
+
+                    match Frame::of(child) {
+                        Some(child_frame) => {
+                            child_frame.open(&mut child_hasher);
+                            Some((child_frame, child_hasher))
+                        }
+                        None => {
+                            leaves.hash_leaf(child, &mut child_hasher);
+                            frame.add(&child_hasher.finish(), frame_hasher);
+                            leaves.leave();
+                            None
+                        }
+                    }
+                }
+                None => {
+                    frame.close(frame_hasher);
+
+                    if let Some(finished) = nested {
+                        let (parent, parent_nested) = stack.last_mut().expect("nested frame without a parent");
+                        parent.add(&finished.finish(), parent_nested.as_mut().unwrap_or(&mut *hasher));
+                        leaves.leave();
+                    }
+
+                    continue;
+                }
+            }
+        };
+
+        stack.push((frame, nested));
+
+        if let Some((child_frame, child_hasher)) = child {
+            stack.push((child_frame, Some(child_hasher)));
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use serde_json;
+
+    use super::{digest_at_path, hash_str, hash_str_with, CommonJson, NumberMode};
+    use path::Path;
+    use Error;
+    use {digest, hasher, HashProfile, ObjectHash, ObjectHasher};
+    use rustc_serialize::hex::ToHex;
+
+    macro_rules! h {
+        ($json:expr) => {
+            {
+                let value: serde_json::Value = serde_json::from_str($json).unwrap();
+                let mut hasher = hasher::default();
+                value.objecthash(&mut hasher);
+                hasher.finish().as_ref().to_hex()
+            }
+        };
+    }
+
+    macro_rules! common {
+        ($json:expr) => {
+            {
+                let value: serde_json::Value = serde_json::from_str($json).unwrap();
+                let mut hasher = hasher::default();
+                CommonJson(&value).objecthash(&mut hasher);
+                hasher.finish().as_ref().to_hex()
+            }
+        };
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(h!("null"), "1b16b1df538ba12dc3f97edbb85caa7050d46c148134290feba80f8236c83db9");
+        assert_eq!(h!("true"), "7dc96f776c8423e57a2785489a3f9c43fb6e756876d6ad9a9cac4aa4e72ec193");
+        assert_eq!(h!("1000"), "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b");
+        assert_eq!(h!("1.5"), "7d9d2d2489ee3a73c6e6e7b84469a5f697e902793cbbb3b4b1c0da46b9b4bdec");
+        assert_eq!(h!("\"ԱԲաբ\""), "2a2a4485a4e338d8df683971956b1090d2f5d33955a81ecaad1a75125f7a316c");
+        assert_eq!(h!("[18446744073709551615, -9223372036854775808]"),
+                   "944fe16b4917ab8e2039f6c177ba3e6703cca21724317a87a350af6670622927");
+    }
+
+    #[test]
+    fn collections() {
+        assert_eq!(h!("[]"), "acac86c0e609ca906f632b0e2dacccb2b77d22b0621f20ebece1a4835b93f6f0");
+        assert_eq!(h!("{}"), "18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4");
+        assert_eq!(h!(r#"["foo", "bar"]"#), "32ae896c413cfdc79eec68be9139c86ded8b279238467c216cf2bec4d5f1e4a2");
+        assert_eq!(h!(r#"{"foo": "bar"}"#), "7ef5237c3027d6c58100afadf37796b3d351025cf28038280147d42fdc53b960");
+        assert_eq!(h!(r#"{"k3": "v3", "k1": "v1", "k2": "v2"}"#),
+                   "ddd65f1f7568269a30df7cafc26044537dc2f02a1a0d830da61762fc3e687057");
+    }
+
+    #[test]
+    fn reference_vectors() {
+        assert_eq!(h!(r#"["foo", {"bar": ["baz", null, 1.0, 1.5, 0.0001, 1000.0, 2.0, -23.1234, 2.0]}]"#),
+                   "783a423b094307bcb28d005bc2f026ff44204442ef3513585e7e73b66e3c2213");
+        assert_eq!(h!(r#"["foo", {"bar": ["baz", null, 1, 1.5, 0.0001, 1000, 2, -23.1234, 2]}]"#),
+                   "726e7ae9e3fadf8a2228bf33e505a63df8db1638fa4f21429673d387dbd1c52a");
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        // The same key, precomposed and decomposed
+        let value: serde_json::Value = serde_json::from_str(r#"{"caf\u00e9": 1, "cafe\u0301": 2}"#).unwrap();
+        assert_eq!(value.as_object().unwrap().len(), 2);
+
+        let reject = HashProfile::default().with_reject_duplicate_keys(true);
+        let mut hasher = hasher::default().with_profile(reject);
+        value.objecthash(&mut hasher);
+        match hasher.try_finish() {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, digest("caf\u{e9}")),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+
+        let value: serde_json::Value = serde_json::from_str(r#"{"a": {"b": 1, "c": 2}, "b": 3}"#).unwrap();
+        let mut hasher = hasher::default().with_profile(reject);
+        value.objecthash(&mut hasher);
+        assert!(hasher.try_finish().is_ok());
+    }
+
+    #[test]
+    fn limits() {
+        use {Limit, Limits};
+
+        let check = |json: &str, limits: Limits| {
+            let value: serde_json::Value = serde_json::from_str(json).unwrap();
+            let mut hasher = hasher::default().with_profile(HashProfile::default().with_limits(limits));
+            value.objecthash(&mut hasher);
+            match hasher.try_finish() {
+                Ok(_) => None,
+                Err(Error::LimitExceeded(limit)) => Some(limit),
+                Err(err) => panic!("unexpected error: {}", err),
+            }
+        };
+
+        let json = r#"{"a": [1, [2, "foo"]], "b": null}"#;
+        assert_eq!(check(json, Limits::default()), None);
+        assert_eq!(check(json, Limits::default().with_max_depth(3)), None);
+        assert_eq!(check(json, Limits::default().with_max_depth(2)), Some(Limit::Depth));
+        assert_eq!(check(json, Limits::default().with_max_elements(8)), None);
+        assert_eq!(check(json, Limits::default().with_max_elements(7)), Some(Limit::Elements));
+        assert_eq!(check(json, Limits::default().with_max_len(3)), None);
+        assert_eq!(check(json, Limits::default().with_max_len(2)), Some(Limit::Length));
+    }
+
+    #[test]
+    fn exact_numbers() {
+        let exact = |json: &str, mode| {
+            let value: serde_json::Value = serde_json::from_str(json).unwrap();
+            let profile = HashProfile::default().with_exact_numbers(true).with_json_numbers(mode);
+            let mut hasher = hasher::default().with_profile(profile);
+            value.objecthash(&mut hasher);
+            hasher.try_finish().map(|digest| digest.as_ref().to_hex())
+        };
+
+        assert_eq!(exact("[9007199254740993, 1.5]", NumberMode::Native).unwrap(), h!("[9007199254740993, 1.5]"));
+        assert!(exact("9007199254740992", NumberMode::CommonJson).is_ok());
+        match exact("9007199254740993", NumberMode::CommonJson) {
+            Err(Error::InexactNumber(n)) => assert_eq!(n, "9007199254740993"),
+            other => panic!("expected an inexact number error, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn arbitrary_precision() {
+        // 2^64 and -2^63 - 1, hashed as integers by the reference implementation
+        assert_eq!(h!("18446744073709551616"), "9d53531287138cb721d5fecf30aa8566faf44bc6265ed11a5ed13913bf67c0bd");
+        assert_eq!(h!("-9223372036854775809"), "74927edd5126ca1b5f2614beae566b00f6096e69c53bc165eb0164ee13528575");
+
+        let exact = |json: &str| {
+            let value: serde_json::Value = serde_json::from_str(json).unwrap();
+            let mut hasher = hasher::default().with_profile(HashProfile::default().with_exact_numbers(true));
+            value.objecthash(&mut hasher);
+            hasher.try_finish().is_ok()
+        };
+
+        assert!(exact("[0.1, 1.50, 1e2, 12.5e-1, -0.0, 0.000]"));
+        assert!(exact("123456789012345678901234567890"));
+        assert!(!exact("0.10000000000000000001"));
+        assert!(!exact("12345678901234567890.5"));
+        assert!(!exact("1e400"));
+        assert_eq!(h!("0.10000000000000000001"), h!("0.1"));
+    }
+
+    #[test]
+    fn common_json() {
+        assert_eq!(common!("1"), "f01adc732390ab024d64080e0b173f0ee3a1610efbdd4ce2a13bbf8d9b26c639");
+        assert_eq!(common!("1000"), "09b29bf3f8bea85fbf7dd5b3e185e9c3a007761f8824a54d4d518578c9360419");
+        assert_eq!(common!(r#"["foo", {"bar": ["baz", null, 1, 1.5, 0.0001, 1000, 2, -23.1234, 2]}]"#),
+                   "783a423b094307bcb28d005bc2f026ff44204442ef3513585e7e73b66e3c2213");
+        assert_eq!(common!(r#"["foo", {"bar": ["baz", null, 1.0, 1.5, 0.0001, 1000.0, 2.0, -23.1234, 2.0]}]"#),
+                   "783a423b094307bcb28d005bc2f026ff44204442ef3513585e7e73b66e3c2213");
+
+        let value: serde_json::Value = serde_json::from_str("[1, 1000]").unwrap();
+        assert_eq!(HashProfile::common_json(), HashProfile::default().with_json_numbers(NumberMode::CommonJson));
+        let mut hasher = hasher::default().with_profile(HashProfile::common_json());
+        value.objecthash(&mut hasher);
+        assert_eq!(hasher.finish().as_ref().to_hex(), common!("[1, 1000]"));
+    }
+
+    #[test]
+    fn hashing_strings() {
+        let digest = hash_str(r#"{"foo": "bar"}"#).unwrap();
+        assert_eq!(digest.as_ref().to_hex(), "7ef5237c3027d6c58100afadf37796b3d351025cf28038280147d42fdc53b960");
+
+        let digest = hash_str("\n\t{ \"k3\":\"v3\", \"k1\" : \"v1\",\"k2\":\"v2\" }\n").unwrap();
+        assert_eq!(digest.as_ref().to_hex(), "ddd65f1f7568269a30df7cafc26044537dc2f02a1a0d830da61762fc3e687057");
+
+        let native = hash_str("[1, 1.5]").unwrap();
+        assert_eq!(native.as_ref(), hash_str_with("[1, 1.5]", NumberMode::Native).unwrap().as_ref());
+        assert!(native.as_ref() != hash_str_with("[1, 1.5]", NumberMode::CommonJson).unwrap().as_ref());
+
+        let common = hash_str_with(r#"["foo", {"bar": ["baz", null, 1, 1.5, 0.0001, 1000, 2, -23.1234, 2]}]"#,
+                                   NumberMode::CommonJson)
+            .unwrap();
+        assert_eq!(common.as_ref().to_hex(), "783a423b094307bcb28d005bc2f026ff44204442ef3513585e7e73b66e3c2213");
+    }
+
+    #[test]
+    fn hashing_invalid_strings() {
+        for json in &["", "{", "[1, 2", r#"{"foo": }"#, "[1] 2"] {
+            match hash_str(json) {
+                Err(Error::Json(_)) => (),
+                Err(other) => panic!("unexpected error for {:?}: {}", json, other),
+                Ok(_) => panic!("expected an error for {:?}", json),
+            }
+        }
+    }
+
+    #[test]
+    fn digests_at_paths() {
+        let value: serde_json::Value = serde_json::from_str(r#"{"foo": ["bar", "baz"], "qux": {"0": [1]}}"#).unwrap();
+
+        assert_eq!(digest_at_path(&value, &Path::root()), Some(digest(&value)));
+        assert_eq!(digest_at_path(&value, &Path::root().key("foo").index(1)), Some(digest("baz")));
+        assert_eq!(digest_at_path(&value, &"/qux/0".parse().unwrap()), Some(digest(&value["qux"]["0"])));
+        assert_eq!(digest_at_path(&value, &Path::root().key("foo").index(2)), None);
+        assert_eq!(digest_at_path(&value, &Path::root().key("foo").key("bar")), None);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        use serde_json::{Map, Value};
+        use types::{DICT_TAG, LIST_TAG};
+
+        const DEPTH: usize = 100_000;
+
+        let mut value = Value::Null;
+        let mut expected = digest(&());
+
+        for i in 0..DEPTH {
+            if i % 2 == 0 {
+                value = Value::Array(vec![value]);
+
+                let mut hasher = hasher::default();
+                hasher.update(LIST_TAG);
+                hasher.update(expected.as_ref());
+                expected = hasher.finish();
+            } else {
+                let mut object = Map::new();
+                object.insert("a".to_owned(), value);
+                value = Value::Object(object);
+
+                let mut hasher = hasher::default();
+                hasher.update(DICT_TAG);
+                hasher.update(digest("a").as_ref());
+                hasher.update(expected.as_ref());
+                expected = hasher.finish();
+            }
+        }
+
+        assert_eq!(digest(&value), expected);
+
+        // Dropping a Value recurses, so take it apart one level at a time
+        loop {
+            value = match value {
+                Value::Array(mut a) => a.pop().unwrap(),
+                Value::Object(mut o) => o.remove("a").unwrap(),
+                _ => break,
+            };
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Hashing newline-delimited JSON
+//!
+//! `DigestLines` reads a batch of NDJSON records from a `BufRead`, one per
+//! line, and yields the digest of each along with its line number. It reads
+//! every line into the same buffer, so hashing a file of millions of records
+//! takes a single pass without setting anything up per line. Blank lines are
+//! skipped.
+//!
+//! It can also compute a rollup digest of the whole batch: the digest of the
+//! list of its records, i.e. of the JSON array holding them in order.
+
+use std::io::BufRead;
+
+use serde_json::{self, Value};
+
+use {hasher, Digest, Error, HashProfile, ObjectHash, ObjectHasher};
+use stream::ListHasher;
+
+/// Iterator over the line numbers and digests of the records in an NDJSON
+/// batch. Line numbers start at 1
+pub struct DigestLines<R> {
+    reader: R,
+    buf: String,
+    line_number: u64,
+    profile: HashProfile,
+    rollup: bool,
+
+    // Only created for the first record, so it's hashed with the profile
+    // set by then whichever order the builder methods were called in
+    records: Option<ListHasher<hasher::ring::Hasher>>,
+    failed: bool,
+}
+
+impl<R: BufRead> DigestLines<R> {
+    /// Hash the records read from the given reader with the default profile
+    pub fn new(reader: R) -> Self {
+        DigestLines {
+            reader,
+            buf: String::new(),
+            line_number: 0,
+            profile: HashProfile::default(),
+            rollup: false,
+            records: None,
+            failed: false,
+        }
+    }
+
+    /// Hash the records with the given profile
+    pub fn with_profile(mut self, profile: HashProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Also compute the digest of the list of records, to be collected
+    /// through `rollup` once they've all been read
+    pub fn with_rollup(mut self) -> Self {
+        self.rollup = true;
+        self
+    }
+
+    /// The digest of the list of records read so far, if `with_rollup` was
+    /// called and none of them failed to parse or hash
+    pub fn rollup(self) -> Option<Digest> {
+        if !self.rollup || self.failed {
+            return None;
+        }
+
+        let profile = self.profile;
+        Some(self.records.unwrap_or_else(|| Self::list_hasher(profile)).finish())
+    }
+
+    fn list_hasher(profile: HashProfile) -> ListHasher<hasher::ring::Hasher> {
+        ListHasher::new(hasher::default().with_profile(profile))
+    }
+
+    fn digest_line(&self) -> Result<Digest, Error> {
+        let value: Value = serde_json::from_str(&self.buf)?;
+        let mut hasher = hasher::default().with_profile(self.profile);
+        value.objecthash(&mut hasher);
+        hasher.try_finish()
+    }
+}
+
+impl<R: BufRead> Iterator for DigestLines<R> {
+    type Item = Result<(u64, Digest), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => self.line_number += 1,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e.into()));
+                }
+            }
+
+            if self.buf.trim().is_empty() {
+                continue;
+            }
+
+            let result = self.digest_line();
+
+            match result {
+                Ok(ref digest) if self.rollup => {
+                    let profile = self.profile;
+                    self.records.get_or_insert_with(|| Self::list_hasher(profile)).push_digest(digest);
+                }
+                Ok(_) => (),
+                Err(_) => self.failed = true,
+            }
+
+            return Some(result.map(|digest| (self.line_number, digest)));
+        }
+    }
+}
+
+/// Hash the records of an NDJSON batch with the default profile, yielding
+/// their line numbers and digests
+pub fn digest_lines<R: BufRead>(reader: R) -> DigestLines<R> {
+    DigestLines::new(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::{digest_lines, DigestLines};
+    use {digest, hasher, Error, HashProfile, ObjectHash, ObjectHasher};
+
+    const BATCH: &str = "{\"id\": 1, \"msg\": \"foo\"}\n\n{\"id\": 2, \"msg\": \"bar\"}\r\n[1.5, null]";
+
+    #[test]
+    fn hashing_lines() {
+        let digests: Vec<_> = digest_lines(BATCH.as_bytes()).map(Result::unwrap).collect();
+
+        let records: Vec<Value> = BATCH.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(digests,
+                   vec![(1, digest(&records[0])), (3, digest(&records[1])), (4, digest(&records[2]))]);
+
+        let mut lines = DigestLines::new(BATCH.as_bytes()).with_rollup();
+        assert_eq!(lines.by_ref().count(), 3);
+        assert_eq!(lines.rollup(), Some(digest(&records)));
+
+        assert_eq!(DigestLines::new(BATCH.as_bytes()).rollup(), None);
+        assert_eq!(DigestLines::new(&b""[..]).with_rollup().rollup(),
+                   Some(digest(&Vec::<Value>::new())));
+    }
+
+    #[test]
+    fn hashing_lines_with_profiles() {
+        let profile = HashProfile::common_json();
+        let records: Vec<Value> = serde_json::from_str("[1, [2.5, \"foo\"]]").unwrap();
+        let mut expected = hasher::default().with_profile(profile);
+        records.objecthash(&mut expected);
+
+        // The rollup is hashed with the profile whether it's set before or
+        // after asking for the rollup
+        let mut lines = DigestLines::new(&b"1\n[2.5, \"foo\"]\n"[..]).with_rollup().with_profile(profile);
+        assert_eq!(lines.by_ref().count(), 2);
+        assert_eq!(lines.rollup(), Some(expected.finish()));
+
+        let mut expected = hasher::default().with_profile(profile);
+        Vec::<Value>::new().objecthash(&mut expected);
+        assert_eq!(DigestLines::new(&b""[..]).with_rollup().with_profile(profile).rollup(),
+                   Some(expected.finish()));
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let mut lines = DigestLines::new(&b"1\n{\"foo\":\n2\n"[..]).with_rollup();
+
+        assert_eq!(lines.next().unwrap().unwrap().0, 1);
+        match lines.next() {
+            Some(Err(Error::Json(_))) => (),
+            other => panic!("expected a JSON error, got {:?}", other),
+        }
+        assert_eq!(lines.next().unwrap().unwrap().0, 3);
+        assert!(lines.next().is_none());
+        assert_eq!(lines.rollup(), None);
+    }
+}
@@ -0,0 +1,259 @@
+//! Hashing JSON documents as they're parsed
+//!
+//! Parsing a document into a `Value` before hashing it holds the whole tree
+//! in memory, which for documents of hundreds of megabytes takes far longer
+//! (and far more memory) than hashing them. `hash_reader` instead drives
+//! `serde_json`'s parser over an `io::Read` and hashes each value as soon as
+//! it's parsed, so it only keeps what's open at the current depth: the
+//! hashers of the enclosing lists and dicts, and the digests of the members
+//! of enclosing dicts, which are sorted once they're closed.
+//!
+//! The digests are the same as those of the parsed `Value`, with the same
+//! profile, down to a key which appears more than once in a dict keeping
+//! only its last value. Like `serde_json::from_reader`, it reads a byte at
+//! a time, so the reader should be buffered, and documents nested more than
+//! 128 deep are rejected.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+#[cfg(feature = "objecthash-ring")]
+use std::io::BufReader;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{self, Number};
+
+use {Digest, Error, ObjectHash, ObjectHasher, MAX_OUTPUT_LEN};
+#[cfg(feature = "objecthash-ring")]
+use {hasher, HashProfile};
+use super::{hash_number, NumberMode};
+use types::{dict_member, hash_dict_members, LIST_TAG};
+
+/// Parse a JSON document from the given reader and hash it into the given
+/// hasher, using the number mode of its profile. Only errors reading or
+/// parsing the document are returned: those raised while hashing it are
+/// returned by the hasher's `try_finish`
+pub fn hash_reader<R: io::Read, H: ObjectHasher>(reader: R, hasher: &mut H) -> Result<(), Error> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let mode = hasher.profile().json_numbers();
+    HashSeed { hasher, mode }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(())
+}
+
+/// Parse a JSON document from the given reader and compute its digest with
+/// the default hasher
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_reader<R: io::Read>(reader: R) -> Result<Digest, Error> {
+    digest_reader_with(reader, HashProfile::default())
+}
+
+/// Parse a JSON document from the given reader and compute its digest with
+/// the given profile. The reader is buffered
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_reader_with<R: io::Read>(reader: R, profile: HashProfile) -> Result<Digest, Error> {
+    let mut hasher = hasher::default().with_profile(profile);
+    hash_reader(BufReader::new(reader), &mut hasher)?;
+    hasher.try_finish()
+}
+
+// Hashes the next value parsed into the given hasher
+struct HashSeed<'h, H: 'h> {
+    hasher: &'h mut H,
+    mode: NumberMode,
+}
+
+impl<'de, 'h, H: ObjectHasher> DeserializeSeed<'de> for HashSeed<'h, H> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+// Hashes the next value parsed into a hasher nested within the given one,
+// returning its digest. The nested hasher is only created once there's a
+// value to hash, so it's counted against the profile's limits like the
+// hashers of a `Value`'s elements
+struct NestedSeed<'h, H: 'h> {
+    hasher: &'h H,
+    mode: NumberMode,
+}
+
+impl<'de, 'h, H: ObjectHasher> DeserializeSeed<'de> for NestedSeed<'h, H> {
+    type Value = Digest;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Digest, D::Error> {
+        let mut nested = self.hasher.nested();
+        HashSeed { hasher: &mut nested, mode: self.mode }.deserialize(deserializer)?;
+        Ok(nested.finish())
+    }
+}
+
+impl<'de, 'h, H: ObjectHasher> Visitor<'de> for HashSeed<'h, H> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        ().objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<(), E> {
+        b.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<(), E> {
+        hash_number(&Number::from(n), self.mode, self.hasher);
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<(), E> {
+        hash_number(&Number::from(n), self.mode, self.hasher);
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<(), E> {
+        // The parser never produces infinities or NaN
+        match Number::from_f64(n) {
+            Some(number) => hash_number(&number, self.mode, self.hasher),
+            None => n.objecthash(self.hasher),
+        }
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<(), E> {
+        s.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        self.hasher.update(LIST_TAG);
+
+        while let Some(digest) = seq.next_element_seed(NestedSeed { hasher: &*self.hasher, mode: self.mode })? {
+            self.hasher.update(digest.as_ref());
+        }
+
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut members: Vec<[u8; 2 * MAX_OUTPUT_LEN]> = Vec::new();
+
+        // Index of each key's member, so a repeated key replaces it
+        let mut indexes: HashMap<String, usize> = HashMap::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            // With arbitrary precision, numbers are parsed as a map holding
+            // their text under this key
+            #[cfg(feature = "arbitrary-precision")]
+            {
+                if members.is_empty() && key == "$serde_json::private::Number" {
+                    let text: String = map.next_value()?;
+                    let number: Number = text.parse().map_err(de::Error::custom)?;
+                    hash_number(&number, self.mode, self.hasher);
+                    return Ok(());
+                }
+            }
+
+            let mut key_hasher = self.hasher.nested();
+            key.objecthash(&mut key_hasher);
+            let key_digest = key_hasher.finish();
+
+            let value_digest = map.next_value_seed(NestedSeed { hasher: &*self.hasher, mode: self.mode })?;
+            let member = dict_member(&key_digest, &value_digest);
+
+            match indexes.get(&key) {
+                Some(&index) => members[index] = member,
+                None => {
+                    indexes.insert(key, members.len());
+                    members.push(member);
+                }
+            }
+        }
+
+        hash_dict_members(&mut members, self.hasher);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::{digest_reader, digest_reader_with, hash_reader};
+    use {digest, hasher, Error, HashProfile, Limits, NumberMode, ObjectHash, ObjectHasher};
+
+    const DOCUMENTS: &[&str] = &["null",
+                                 "[]",
+                                 "{}",
+                                 r#"["foo", {"bar": ["baz", null, 1, 1.5, 0.0001, 1000, 2, -23.1234, 2.0]}]"#,
+                                 r#"{"k3": "v3", "k1": {"a": [true, false]}, "k2": [[], {}, [18446744073709551615]]}"#,
+                                 "\"caf\\u00e9\"",
+                                 "-9223372036854775808"];
+
+    fn digest_value(json: &str, profile: HashProfile) -> ::Digest {
+        let value: Value = serde_json::from_str(json).unwrap();
+        let mut hasher = hasher::default().with_profile(profile);
+        value.objecthash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn hashing_readers() {
+        for json in DOCUMENTS {
+            assert_eq!(digest_reader(json.as_bytes()).unwrap(), digest_value(json, HashProfile::default()));
+
+            let common = HashProfile::common_json();
+            assert_eq!(digest_reader_with(json.as_bytes(), common).unwrap(), digest_value(json, common));
+        }
+
+        let mut hasher = hasher::default().with_profile(HashProfile::default().with_json_numbers(NumberMode::CommonJson));
+        hash_reader(&b" [1, 1000] \n"[..], &mut hasher).unwrap();
+        assert_eq!(hasher.finish(), digest(&vec![1.0, 1000.0]));
+
+        // Repeated keys keep their last value
+        let json = r#"{"foo": 1, "bar": 2, "foo": [3]}"#;
+        assert_eq!(digest_reader(json.as_bytes()).unwrap(), digest_value(json, HashProfile::default()));
+        assert_eq!(digest_reader(json.as_bytes()).unwrap(),
+                   digest_value(r#"{"bar": 2, "foo": [3]}"#, HashProfile::default()));
+    }
+
+    #[test]
+    fn hashing_invalid_readers() {
+        for json in &["", "{", "[1, 2", r#"{"foo": }"#, "[1] 2"] {
+            match digest_reader(json.as_bytes()) {
+                Err(Error::Json(_)) => (),
+                other => panic!("expected a JSON error for {:?}, got {:?}", json, other),
+            }
+        }
+
+        // Errors raised while hashing are reported like for a parsed value
+        let reject = HashProfile::default().with_reject_duplicate_keys(true);
+        match digest_reader_with(&br#"{"caf\u00e9": 1, "cafe\u0301": 2}"#[..], reject) {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, digest("caf\u{e9}")),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+
+        let limited = HashProfile::default().with_limits(Limits::default().with_max_elements(7));
+        match digest_reader_with(&br#"{"a": [1, [2, "foo"]], "b": null}"#[..], limited) {
+            Err(Error::LimitExceeded(_)) => (),
+            other => panic!("expected a limit error, got {:?}", other),
+        }
+        let limited = HashProfile::default().with_limits(Limits::default().with_max_elements(8));
+        assert!(digest_reader_with(&br#"{"a": [1, [2, "foo"]], "b": null}"#[..], limited).is_ok());
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn arbitrary_precision() {
+        for json in &["18446744073709551616", "[-9223372036854775809, 0.10000000000000000001]", r#"{"n": 1e400}"#] {
+            assert_eq!(digest_reader(json.as_bytes()).unwrap(), digest_value(json, HashProfile::default()));
+        }
+    }
+}
@@ -0,0 +1,673 @@
+//! Redaction and salted hashing of JSON documents
+//!
+//! `RedactedDocument` replaces chosen subtrees of a document with
+//! `"**REDACTED**:<hex digest>"` marker strings. `RedactedJson` hashes such a
+//! document with each marker standing in for the digest it carries, so the
+//! redacted document has the same digest as the original. It also accepts the
+//! markers of the reference implementations, which have no colon before the
+//! digest, so documents redacted by Python or Go services can be parsed and
+//! verified with `hash_redacted_str`.
+//!
+//! Salting a JSON document replaces every leaf value (null, boolean, number
+//! or string) with the two-element list of a random salt and the value, as
+//! the reference implementations' redactable JSON mode does. The salts are
+//! kept in a separate `Salts` map keyed by the JSON pointer (RFC 6901) of
+//! each leaf, which can be stored as JSON and later restored to verify the
+//! document.
+
+#[cfg(feature = "objecthash-ring")]
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "objecthash-ring")]
+use serde_json;
+use serde_json::{Map, Value};
+
+use super::{hash_scalar, hash_value_with, Leaves, NumberMode, Token};
+#[cfg(feature = "objecthash-ring")]
+use super::{select, select_mut};
+#[cfg(feature = "objecthash-ring")]
+use path::Path;
+#[cfg(feature = "objecthash-ring")]
+use {hasher, HashProfile};
+use {hex, Digest, Error, ObjectHash, ObjectHasher};
+use redaction::{hash_salted, Salt, SALT_LEN};
+#[cfg(feature = "objecthash-ring")]
+use redaction::random_salt;
+
+/// Prefix of the strings which stand in for redacted subtrees
+pub const REDACTED_PREFIX: &str = "**REDACTED**:";
+
+/// Prefix of the markers written by the reference implementations, which
+/// put the digest right after it
+pub const REFERENCE_REDACTED_PREFIX: &str = "**REDACTED**";
+
+/// A JSON document with some subtrees redacted, and the digest of the
+/// original document
+#[cfg(feature = "objecthash-ring")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RedactedDocument {
+    pub value: Value,
+    pub digest: Digest,
+
+    /// Profile the document was hashed with, which `verify` uses too
+    pub profile: HashProfile,
+}
+
+#[cfg(feature = "objecthash-ring")]
+impl RedactedDocument {
+    /// Redact the subtrees at the given JSON pointers (RFC 6901) of a document.
+    /// Pointers may be nested within one another
+    pub fn new<I, S>(value: &Value, pointers: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str>
+    {
+        Self::new_with(value, pointers, HashProfile::default())
+    }
+
+    /// Redact the subtrees at the given JSON pointers of a document, hashing
+    /// it with the given profile, e.g. `HashProfile::common_json()` for
+    /// documents shared with the reference implementations
+    pub fn new_with<I, S>(value: &Value, pointers: I, profile: HashProfile) -> Result<Self, Error>
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str>
+    {
+        let paths = pointers.into_iter()
+            .map(|pointer| Path::parse_pointer(pointer.as_ref()))
+            .collect::<Result<Vec<Path>, Error>>()?;
+
+        Self::from_paths_with(value, &paths, profile)
+    }
+
+    /// Redact the subtrees at the given paths of a document. Paths may be
+    /// nested within one another
+    pub fn from_paths(value: &Value, paths: &[Path]) -> Result<Self, Error> {
+        Self::from_paths_with(value, paths, HashProfile::default())
+    }
+
+    /// Redact the subtrees at the given paths of a document, hashing it with
+    /// the given profile
+    pub fn from_paths_with(value: &Value, paths: &[Path], profile: HashProfile) -> Result<Self, Error> {
+        let root = digest_redacted(value, profile)?;
+        let mut value = value.clone();
+
+        // Redacting the longest paths first leaves every path resolvable,
+        // since paths within a subtree are longer than the subtree's own
+        let mut paths: Vec<&Path> = paths.iter().collect();
+        paths.sort_by_key(|path| Reverse(path.segments().len()));
+
+        for path in paths {
+            redact_at_with(&mut value, path, profile)?;
+        }
+
+        Ok(RedactedDocument {
+            value,
+            digest: root,
+            profile,
+        })
+    }
+
+    /// Check the redacted document still has the digest of the original
+    pub fn verify(&self) -> bool {
+        verify_with(&self.value, &self.digest, self.profile)
+    }
+}
+
+/// Compute the digest of the subtree at the given path within a document,
+/// which may itself contain redaction markers
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_at(value: &Value, path: &Path) -> Result<Digest, Error> {
+    digest_at_with(value, path, HashProfile::default())
+}
+
+/// Compute the digest of the subtree at the given path within a document,
+/// hashing it with the given profile
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_at_with(value: &Value, path: &Path, profile: HashProfile) -> Result<Digest, Error> {
+    let subtree = select(value, path).ok_or_else(|| Error::InvalidPointer(path.to_string()))?;
+    digest_redacted(subtree, profile)
+}
+
+/// Replace the subtree at the given path within a document with a redaction
+/// marker, returning the subtree's digest. This leaves the digest of the
+/// document unchanged
+#[cfg(feature = "objecthash-ring")]
+pub fn redact_at(value: &mut Value, path: &Path) -> Result<Digest, Error> {
+    redact_at_with(value, path, HashProfile::default())
+}
+
+/// Replace the subtree at the given path within a document with a redaction
+/// marker, hashing it with the given profile
+#[cfg(feature = "objecthash-ring")]
+pub fn redact_at_with(value: &mut Value, path: &Path, profile: HashProfile) -> Result<Digest, Error> {
+    let subtree = select_mut(value, path).ok_or_else(|| Error::InvalidPointer(path.to_string()))?;
+    let digest = digest_redacted(subtree, profile)?;
+    *subtree = Value::String(redaction_marker(&digest));
+    Ok(digest)
+}
+
+/// Check a redacted document against the digest of the original
+#[cfg(feature = "objecthash-ring")]
+pub fn verify(redacted: &Value, root: &Digest) -> bool {
+    verify_with(redacted, root, HashProfile::default())
+}
+
+/// Check a redacted document against the digest of the original, which was
+/// hashed with the given profile
+#[cfg(feature = "objecthash-ring")]
+pub fn verify_with(redacted: &Value, root: &Digest, profile: HashProfile) -> bool {
+    digest_redacted(redacted, profile).ok() == Some(*root)
+}
+
+#[cfg(feature = "objecthash-ring")]
+fn digest_redacted(value: &Value, profile: HashProfile) -> Result<Digest, Error> {
+    let mut hasher = hasher::default().with_profile(profile);
+    RedactedJson::new(value).objecthash(&mut hasher);
+    hasher.try_finish()
+}
+
+/// Parse a JSON document which may contain redaction markers and compute the
+/// digest of the original, hashing integers with the integer tag
+#[cfg(feature = "objecthash-ring")]
+pub fn hash_redacted_str(json: &str) -> Result<Digest, Error> {
+    hash_redacted_str_with(json, NumberMode::Native)
+}
+
+/// Parse a JSON document which may contain redaction markers and compute the
+/// digest of the original using the given number mode. Documents redacted by
+/// the reference implementations' common JSON mode need `NumberMode::CommonJson`
+#[cfg(feature = "objecthash-ring")]
+pub fn hash_redacted_str_with(json: &str, mode: NumberMode) -> Result<Digest, Error> {
+    let value: Value = serde_json::from_str(json)?;
+    let mut hasher = hasher::default();
+    RedactedJson::new(&value).with_json_numbers(mode).objecthash(&mut hasher);
+    hasher.try_finish()
+}
+
+/// Marker string standing in for a redacted subtree with the given digest
+pub fn redaction_marker(digest: &Digest) -> String {
+    format!("{}{}", REDACTED_PREFIX, hex::encode(digest.as_ref()))
+}
+
+/// Hashes a JSON document with redaction markers standing in for the digests
+/// they carry. Strings which start with the marker prefix but don't carry a
+/// digest of the hasher's output length are hashed as ordinary strings
+pub struct RedactedJson<'a> {
+    pub value: &'a Value,
+
+    /// How numbers are hashed, if not the way the hasher's profile says
+    pub mode: Option<NumberMode>,
+}
+
+impl<'a> RedactedJson<'a> {
+    pub fn new(value: &'a Value) -> Self {
+        RedactedJson { value, mode: None }
+    }
+
+    /// Hash numbers with the given mode, whatever the hasher's profile says
+    pub fn with_json_numbers(mut self, mode: NumberMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+}
+
+impl<'a> ObjectHash for RedactedJson<'a> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let mode = self.mode.unwrap_or_else(|| hasher.profile().json_numbers());
+        hash_value_with(self.value, &mut Markers(mode), hasher);
+    }
+}
+
+// Hashes redaction markers as the digests they carry, and other leaves as
+// they are
+struct Markers(NumberMode);
+
+impl Leaves for Markers {
+    fn hash_leaf<H: ObjectHasher>(&mut self, value: &Value, hasher: &mut H) {
+        match value.as_str().and_then(|s| parse_marker(s, hasher.output_len())) {
+            Some(ref digest) => hasher.redact(digest),
+            None => hash_scalar(value, self.0, hasher),
+        }
+    }
+}
+
+fn parse_marker(s: &str, output_len: usize) -> Option<Digest> {
+    let digest_hex = s.strip_prefix(REDACTED_PREFIX).or_else(|| s.strip_prefix(REFERENCE_REDACTED_PREFIX))?;
+
+    hex::decode(digest_hex)
+        .filter(|bytes| bytes.len() == output_len)
+        .and_then(|bytes| Digest::new(&bytes).ok())
+}
+
+/// Salts for the leaves of a JSON document, keyed by JSON pointer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Salts {
+    salts: BTreeMap<String, Salt>,
+}
+
+impl Salts {
+    /// Create an empty set of salts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate a random salt for every leaf of the given document
+    #[cfg(feature = "objecthash-ring")]
+    pub fn generate(value: &Value) -> Result<Self, Error> {
+        let mut salts = Salts::new();
+        salts.generate_at(value, &mut String::new())?;
+        Ok(salts)
+    }
+
+    #[cfg(feature = "objecthash-ring")]
+    fn generate_at(&mut self, value: &Value, pointer: &mut String) -> Result<(), Error> {
+        let len = pointer.len();
+
+        match *value {
+            Value::Array(ref a) => {
+                for (i, v) in a.iter().enumerate() {
+                    push_token(pointer, &i.to_string());
+                    self.generate_at(v, pointer)?;
+                    pointer.truncate(len);
+                }
+            }
+            Value::Object(ref o) => {
+                for (k, v) in o {
+                    push_token(pointer, k);
+                    self.generate_at(v, pointer)?;
+                    pointer.truncate(len);
+                }
+            }
+            _ => {
+                self.salts.insert(pointer.clone(), random_salt()?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the salt for the leaf at the given JSON pointer
+    pub fn get(&self, pointer: &str) -> Option<&Salt> {
+        self.salts.get(pointer)
+    }
+
+    /// Set the salt for the leaf at the given JSON pointer
+    pub fn insert(&mut self, pointer: String, salt: Salt) {
+        self.salts.insert(pointer, salt);
+    }
+
+    pub fn len(&self) -> usize {
+        self.salts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.salts.is_empty()
+    }
+
+    /// Serialize the salts as a JSON object mapping pointers to hex salts
+    pub fn to_value(&self) -> Value {
+        let map: Map<String, Value> = self.salts
+            .iter()
+            .map(|(pointer, salt)| (pointer.clone(), Value::String(hex::encode(salt))))
+            .collect();
+
+        Value::Object(map)
+    }
+
+    /// Restore salts serialized with `to_value`
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let object = match *value {
+            Value::Object(ref o) => o,
+            _ => return Err(Error::InvalidSalts(String::from("expected an object"))),
+        };
+
+        let mut salts = Salts::new();
+
+        for (pointer, salt) in object {
+            let bytes = salt.as_str()
+                .and_then(hex::decode)
+                .filter(|bytes| bytes.len() == SALT_LEN)
+                .ok_or_else(|| Error::InvalidSalts(format!("bad salt for {:?}", pointer)))?;
+
+            let mut salt = [0u8; SALT_LEN];
+            salt.copy_from_slice(&bytes);
+            salts.insert(pointer.clone(), salt);
+        }
+
+        Ok(salts)
+    }
+}
+
+/// Hashes a JSON document with its leaves salted. Leaves without a salt are
+/// hashed as they are, unless the hasher's profile requires salts
+pub struct SaltedJson<'a> {
+    pub value: &'a Value,
+    pub salts: &'a Salts,
+
+    /// How numbers are hashed, if not the way the hasher's profile says
+    pub mode: Option<NumberMode>,
+}
+
+impl<'a> SaltedJson<'a> {
+    pub fn new(value: &'a Value, salts: &'a Salts) -> Self {
+        SaltedJson {
+            value,
+            salts,
+            mode: None,
+        }
+    }
+
+    /// Hash numbers with the given mode, whatever the hasher's profile says
+    pub fn with_json_numbers(mut self, mode: NumberMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+}
+
+impl<'a> ObjectHash for SaltedJson<'a> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let mut leaves = SaltedLeaves {
+            salts: self.salts,
+            mode: self.mode.unwrap_or_else(|| hasher.profile().json_numbers()),
+            pointer: String::new(),
+            lens: Vec::new(),
+        };
+
+        hash_value_with(self.value, &mut leaves, hasher);
+    }
+}
+
+// Hashes leaves with the salts for their pointers, keeping track of the
+// pointer of the value being hashed
+struct SaltedLeaves<'a> {
+    salts: &'a Salts,
+    mode: NumberMode,
+    pointer: String,
+
+    // Lengths of the pointers of the values the current one is nested in
+    lens: Vec<usize>,
+}
+
+impl<'a> Leaves for SaltedLeaves<'a> {
+    fn hash_leaf<H: ObjectHasher>(&mut self, value: &Value, hasher: &mut H) {
+        let mode = self.mode;
+
+        match self.salts.get(&self.pointer) {
+            Some(salt) => hash_salted(salt, hasher, |h| hash_scalar(value, mode, h)),
+            None => {
+                if hasher.profile().require_salts() {
+                    hasher.fail(Error::InvalidSalts(format!("no salt for {:?}", self.pointer)));
+                }
+
+                hash_scalar(value, mode, hasher)
+            }
+        }
+    }
+
+    fn enter(&mut self, token: Token) {
+        self.lens.push(self.pointer.len());
+
+        match token {
+            Token::Index(i) => push_token(&mut self.pointer, &i.to_string()),
+            Token::Key(k) => push_token(&mut self.pointer, k),
+        }
+    }
+
+    fn leave(&mut self) {
+        let len = self.lens.pop().expect("left a value which wasn't entered");
+        self.pointer.truncate(len);
+    }
+}
+
+/// Append a reference token to a JSON pointer, escaping it per RFC 6901
+pub(crate) fn push_token(pointer: &mut String, token: &str) {
+    pointer.push('/');
+
+    for c in token.chars() {
+        match c {
+            '~' => pointer.push_str("~0"),
+            '/' => pointer.push_str("~1"),
+            c => pointer.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use serde_json::{self, Value};
+    use rustc_serialize::hex::ToHex;
+
+    use super::{digest_at, digest_at_with, redact_at, redact_at_with, redaction_marker, verify, verify_with, RedactedDocument, RedactedJson, SaltedJson,
+                Salts};
+    use json::{hash_str_with, NumberMode};
+    use path::Path;
+    use {digest, hasher, Error, HashProfile, ObjectHash, ObjectHasher};
+
+    #[test]
+    fn paths() {
+        let mut value: Value = serde_json::from_str(r#"{"users": [{"name": "Alice"}, {"name": "Bob", "0": 1}]}"#)
+            .unwrap();
+        let root = digest(&value);
+        let bob = Path::root().key("users").index(1);
+
+        assert_eq!(digest_at(&value, &bob.clone().key("name")).unwrap(), digest("Bob"));
+        assert_eq!(digest_at(&value, &bob.clone().index(0)).unwrap(), digest(&1));
+        assert_eq!(digest_at(&value, &Path::root()).unwrap(), root);
+
+        // With a profile, the subtree is hashed as the whole document would be
+        let common = HashProfile::common_json();
+        assert_eq!(digest_at_with(&value, &bob.clone().index(0), common).unwrap(), digest(&1.0));
+
+        let mut redacted = value.clone();
+        let common_root = digest_at_with(&value, &Path::root(), common).unwrap();
+        assert_eq!(redact_at_with(&mut redacted, &bob, common).unwrap(),
+                   digest_at_with(&value, &bob, common).unwrap());
+        assert!(verify_with(&redacted, &common_root, common));
+
+        let name = redact_at(&mut value, &"/users/0/name".parse().unwrap()).unwrap();
+        assert_eq!(name, digest("Alice"));
+        assert_eq!(redact_at(&mut value, &bob).unwrap(), digest_at(&value, &bob).unwrap());
+        assert!(verify(&value, &root));
+
+        match digest_at(&value, &Path::root().key("users").key("x")) {
+            Err(Error::InvalidPointer(ref pointer)) if pointer == "/users/x" => (),
+            other => panic!("expected an invalid pointer error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn redacted_documents() {
+        let value: Value = serde_json::from_str(r#"{"name": "Alice", "address": {"city": "Paris", "zip": 75001}}"#)
+            .unwrap();
+        let root = digest(&value);
+
+        let redacted = RedactedDocument::new(&value, ["/address/zip", "/name"]).unwrap();
+        assert_eq!(redacted.digest, root);
+        assert!(redacted.verify());
+
+        assert_eq!(redacted.value["name"],
+                   Value::String(redaction_marker(&digest(&"Alice".to_owned()))));
+        assert_eq!(redacted.value["address"]["city"], "Paris");
+        assert!(redacted.value["address"]["zip"].as_str().unwrap().starts_with("**REDACTED**:"));
+
+        // Nested pointers
+        let redacted = RedactedDocument::new(&value, ["/address", "/address/city"]).unwrap();
+        assert_eq!(redacted.value["address"],
+                   Value::String(redaction_marker(&digest(&value["address"]))));
+        assert!(verify(&redacted.value, &root));
+
+        let mut tampered = redacted.value.clone();
+        tampered["name"] = "Bob".into();
+        assert!(!verify(&tampered, &root));
+
+        match RedactedDocument::new(&value, ["/phone"]) {
+            Err(Error::InvalidPointer(ref pointer)) if pointer == "/phone" => (),
+            other => panic!("expected an invalid pointer error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn common_json_documents() {
+        let json = r#"{"name": "Alice", "address": {"city": "Paris", "zip": 75001}, "tags": [1, 2.5]}"#;
+        let value: Value = serde_json::from_str(json).unwrap();
+        let root = hash_str_with(json, NumberMode::CommonJson).unwrap();
+
+        let redacted = RedactedDocument::new_with(&value, ["/address/zip", "/name"], HashProfile::common_json())
+            .unwrap();
+        assert_eq!(redacted.digest, root);
+        assert!(redacted.verify());
+        assert!(verify_with(&redacted.value, &root, HashProfile::common_json()));
+        assert!(!verify(&redacted.value, &root));
+
+        // Markers carry the digests of common-JSON subtrees
+        assert_eq!(redacted.value["address"]["zip"],
+                   Value::String(redaction_marker(&digest(&75001.0))));
+
+        let mut hasher = hasher::default().with_profile(HashProfile::common_json());
+        RedactedJson::new(&redacted.value).objecthash(&mut hasher);
+        assert_eq!(hasher.finish(), root);
+        assert_eq!(digest(&RedactedJson::new(&redacted.value).with_json_numbers(NumberMode::CommonJson)), root);
+    }
+
+    #[test]
+    fn redaction_markers() {
+        let marker = "**REDACTED**:32ae896c413cfdc79eec68be9139c86ded8b279238467c216cf2bec4d5f1e4a2";
+        let value = Value::Array(vec![marker.into()]);
+        assert_eq!(digest(&RedactedJson::new(&value)),
+                   digest(&vec![vec!["foo".to_owned(), "bar".to_owned()]]));
+
+        let reference_marker = marker.replacen(':', "", 1);
+        assert_eq!(digest(&RedactedJson::new(&Value::String(reference_marker))),
+                   digest(&vec!["foo".to_owned(), "bar".to_owned()]));
+
+        // Strings which only look like markers are hashed as they are
+        for s in &["**REDACTED**:32ae", "**REDACTED**:not hex", "**REDACTED**"] {
+            let value = Value::String((*s).to_owned());
+            assert_eq!(digest(&RedactedJson::new(&value)), digest(*s));
+        }
+    }
+
+    #[test]
+    fn reference_redactions() {
+        use super::{hash_redacted_str, hash_redacted_str_with};
+        use json::{hash_str, hash_str_with};
+
+        // Redacted by the Python reference implementation
+        let original = r#"{"name": "Alice", "ssn": "123-45-6789", "tags": [1, 2]}"#;
+        let redacted = r#"{"name": "Alice", "tags": [1, 2],
+                           "ssn": "**REDACTED**b0d4249048d94ace285b67d063e22c3604f29867254c527eee97a2318d1169b6"}"#;
+
+        let root = hash_redacted_str(redacted).unwrap();
+        assert_eq!(root.as_ref().to_hex(), "a13f0814cdb434792262cb046c333ef361bf14049456f48cb179168c106d48ff");
+        assert_eq!(root, hash_str(original).unwrap());
+
+        let common = r#"{"name": "Alice",
+                         "ssn": "**REDACTED**b0d4249048d94ace285b67d063e22c3604f29867254c527eee97a2318d1169b6",
+                         "tags": "**REDACTED**8edec191ee4cbe23fc661b0a6a7a35c483e89e0a484ffc154dc75b7b9d680f30"}"#;
+
+        let root = hash_redacted_str_with(common, NumberMode::CommonJson).unwrap();
+        assert_eq!(root.as_ref().to_hex(), "9b84f960a4f27b4e49331b1ac9df041321cda5f114eba3ccbf3acd44b4d5520c");
+        assert_eq!(root, hash_str_with(original, NumberMode::CommonJson).unwrap());
+
+        match hash_redacted_str("{\"ssn\": ") {
+            Err(Error::Json(_)) => (),
+            other => panic!("expected a JSON error, got {:?}", other),
+        }
+    }
+
+    fn document() -> Value {
+        serde_json::from_str(r#"{"name": "Alice", "tags": [1], "n": [], "a/b~": null}"#).unwrap()
+    }
+
+    #[test]
+    fn salted_documents() {
+        let mut salts = Salts::new();
+        salts.insert("/name".to_owned(), [0x11; 32]);
+        salts.insert("/tags/0".to_owned(), [0x22; 32]);
+
+        // {"name": ["11...", "Alice"], "tags": [["22...", 1]], "n": [], "a/b~": null}
+        let value = document();
+        let salted = SaltedJson::new(&value, &salts).with_json_numbers(NumberMode::CommonJson);
+        assert_eq!(digest(&salted).as_ref().to_hex(),
+                   "47a3b8ae2db81326a550b9f00bd370fe396502a56b8a11c6649ab39fe0baf7ec");
+
+        // Without a mode of its own, it hashes numbers as the profile says
+        let mut hasher = hasher::default().with_profile(HashProfile::common_json());
+        SaltedJson::new(&value, &salts).objecthash(&mut hasher);
+        assert_eq!(hasher.finish(), digest(&salted));
+
+        let mut expected = value.clone();
+        expected["name"] = Value::Array(vec![Value::String("11".repeat(32)), "Alice".into()]);
+        expected["tags"][0] = Value::Array(vec![Value::String("22".repeat(32)), 1.into()]);
+        let salted = SaltedJson::new(&value, &salts);
+        assert_eq!(digest(&salted), digest(&expected));
+
+        let strict = || hasher::default().with_profile(HashProfile::default().with_require_salts(true));
+        let mut hasher = strict();
+        salted.objecthash(&mut hasher);
+        match hasher.try_finish() {
+            Err(Error::InvalidSalts(_)) => (),
+            other => panic!("expected an invalid salts error, got {:?}", other),
+        }
+
+        let salts = Salts::generate(&value).unwrap();
+        let mut hasher = strict();
+        SaltedJson::new(&value, &salts).objecthash(&mut hasher);
+        assert!(hasher.try_finish().is_ok());
+    }
+
+    #[test]
+    fn generating_salts() {
+        let value = document();
+        let salts = Salts::generate(&value).unwrap();
+        assert_eq!(salts.len(), 3);
+        assert!(salts.get("/a~1b~0").is_some());
+        assert!(digest(&SaltedJson::new(&value, &salts)) != digest(&value));
+
+        let restored = Salts::from_value(&salts.to_value()).unwrap();
+        assert_eq!(restored, salts);
+        assert_eq!(digest(&SaltedJson::new(&value, &restored)),
+                   digest(&SaltedJson::new(&value, &salts)));
+
+        match Salts::from_value(&serde_json::from_str(r#"{"/name": "abcd"}"#).unwrap()) {
+            Err(Error::InvalidSalts(_)) => (),
+            other => panic!("expected invalid salts, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 100_000;
+
+        let mut value = Value::from(1);
+        for _ in 0..DEPTH {
+            value = Value::Array(vec![value]);
+        }
+        let root = digest(&value);
+
+        // The leaf's pointer is "/0" for each level it's nested in
+        let mut salts = Salts::new();
+        salts.insert("/0".repeat(DEPTH), [0x11; 32]);
+        let mut salted = Value::Array(vec![Value::String("11".repeat(32)), Value::from(1)]);
+        for _ in 0..DEPTH {
+            salted = Value::Array(vec![salted]);
+        }
+        assert_eq!(digest(&SaltedJson::new(&value, &salts)), digest(&salted));
+        take_apart(salted);
+
+        let leaf = Path::parse_pointer(&"/0".repeat(DEPTH)).unwrap();
+        redact_at(&mut value, &leaf).unwrap();
+        assert!(verify(&value, &root));
+        take_apart(value);
+    }
+
+    // Dropping a Value recurses, so take it apart one level at a time
+    fn take_apart(mut value: Value) {
+        while let Value::Array(mut a) = value {
+            value = a.pop().unwrap();
+        }
+    }
+}
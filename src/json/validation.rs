@@ -0,0 +1,143 @@
+//! Hashing JSON documents which match a JSON Schema
+//!
+//! A digest received at a trust boundary only says which document it refers
+//! to, not that the document has the shape the two sides agreed on. A
+//! `SchemaValidator` checks a document against a JSON Schema first, and only
+//! hashes it if it's valid, so a digest it returns always refers to a
+//! document of the agreed shape. Documents which don't match are reported
+//! with every `Violation` of the schema, by where they are in the document
+//! and in the schema.
+
+use serde_json::Value;
+
+use jsonschema::{self, Validator};
+
+#[cfg(feature = "objecthash-ring")]
+use hasher;
+use {Digest, Error, ObjectHash, ObjectHasher};
+
+/// Where a document doesn't match its schema, and why
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// JSON pointer to the value which doesn't match
+    pub instance_path: String,
+
+    /// JSON pointer to the keyword of the schema it doesn't match
+    pub schema_path: String,
+
+    pub message: String,
+}
+
+/// A compiled JSON Schema, which documents are validated against before
+/// they're hashed
+#[derive(Clone, Debug)]
+pub struct SchemaValidator {
+    validator: Validator,
+}
+
+impl SchemaValidator {
+    /// Compile a JSON Schema, whose draft is taken from its `$schema`
+    pub fn new(schema: &Value) -> Result<Self, Error> {
+        let validator = jsonschema::validator_for(schema).map_err(|e| Error::InvalidSchema(e.to_string()))?;
+        Ok(SchemaValidator { validator })
+    }
+
+    /// Check the document matches the schema, returning all the places it
+    /// doesn't otherwise
+    pub fn validate(&self, value: &Value) -> Result<(), Error> {
+        let violations: Vec<Violation> = self.validator
+            .iter_errors(value)
+            .map(|e| {
+                Violation {
+                    instance_path: e.instance_path().as_str().to_owned(),
+                    schema_path: e.schema_path().as_str().to_owned(),
+                    message: e.to_string(),
+                }
+            })
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::SchemaViolations(violations))
+        }
+    }
+
+    /// Hash the document into the given hasher if it matches the schema,
+    /// returning its digest
+    pub fn hash<H: ObjectHasher>(&self, value: &Value, mut hasher: H) -> Result<Digest, Error> {
+        self.validate(value)?;
+        value.objecthash(&mut hasher);
+        hasher.try_finish()
+    }
+
+    /// Compute the digest of the document with the default hasher, if it
+    /// matches the schema
+    #[cfg(feature = "objecthash-ring")]
+    pub fn digest(&self, value: &Value) -> Result<Digest, Error> {
+        self.hash(value, hasher::default())
+    }
+}
+
+/// Validate a document against a JSON Schema and compute its digest with
+/// the default hasher if it matches. Compile the schema once with
+/// `SchemaValidator` to check many documents against it
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_validated(schema: &Value, value: &Value) -> Result<Digest, Error> {
+    SchemaValidator::new(schema)?.digest(value)
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use serde_json::{self, Value};
+
+    use super::{digest_validated, SchemaValidator};
+    use {digest, hasher, Error, HashProfile, ObjectHash, ObjectHasher};
+
+    fn schema() -> Value {
+        serde_json::from_str(r#"{
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer"},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["id"]
+        }"#)
+            .unwrap()
+    }
+
+    #[test]
+    fn valid_documents() {
+        let value: Value = serde_json::from_str(r#"{"id": 1, "tags": ["foo"]}"#).unwrap();
+        assert_eq!(digest_validated(&schema(), &value).unwrap(), digest(&value));
+
+        let validator = SchemaValidator::new(&schema()).unwrap();
+        let profile = HashProfile::common_json();
+        let mut expected = hasher::default().with_profile(profile);
+        value.objecthash(&mut expected);
+        assert_eq!(validator.hash(&value, hasher::default().with_profile(profile)).unwrap(), expected.finish());
+    }
+
+    #[test]
+    fn invalid_documents() {
+        let value: Value = serde_json::from_str(r#"{"tags": ["foo", 2]}"#).unwrap();
+
+        match SchemaValidator::new(&schema()).unwrap().digest(&value) {
+            Err(Error::SchemaViolations(violations)) => {
+                let mut paths: Vec<(&str, &str)> = violations.iter()
+                    .map(|v| (v.instance_path.as_str(), v.schema_path.as_str()))
+                    .collect();
+                paths.sort();
+                assert_eq!(paths, vec![("", "/required"), ("/tags/1", "/properties/tags/items/type")]);
+            }
+            other => panic!("expected schema violations, got {:?}", other),
+        }
+
+        let invalid_schema: Value = serde_json::from_str(r#"{"type": 12}"#).unwrap();
+        match SchemaValidator::new(&invalid_schema) {
+            Err(Error::InvalidSchema(_)) => (),
+            other => panic!("expected an invalid schema, got {:?}", other),
+        }
+    }
+}
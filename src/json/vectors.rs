@@ -0,0 +1,164 @@
+//! Loader and verifier for the reference implementations' test vectors
+//!
+//! The format used by `common_json.test` is line-based: each vector is a
+//! line of JSON followed by a line with the expected digest in hex. Blank
+//! lines and lines starting with `#` are ignored.
+//!
+//! `verify_vectors` checks a vector file against this crate's common-JSON
+//! hashing, while `verify_vectors_with` accepts any function from JSON text
+//! to digest, so applications can check their own type mappings against
+//! the same vectors.
+
+use std::io::BufRead;
+
+#[cfg(feature = "objecthash-ring")]
+use super::{hash_str_with, NumberMode};
+use {hex, Digest, Error};
+
+/// A JSON document and its expected digest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vector {
+    /// Line number of the JSON document, starting from 1
+    pub line: usize,
+    pub json: String,
+    pub digest: Vec<u8>,
+}
+
+/// A vector whose computed digest didn't match
+#[derive(Debug)]
+pub struct Failure {
+    pub vector: Vector,
+
+    /// Digest which was computed instead, or the error raised computing it
+    pub actual: Result<Vec<u8>, Error>,
+}
+
+/// Outcome of verifying a set of vectors
+#[derive(Debug, Default)]
+pub struct Report {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Did every vector verify?
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Parse all the vectors in the given reader
+pub fn parse_vectors<R: BufRead>(reader: R) -> Result<Vec<Vector>, Error> {
+    let mut vectors = Vec::new();
+    let mut json: Option<(usize, String)> = None;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        match json.take() {
+            None => json = Some((i + 1, trimmed.to_owned())),
+            Some((line, json)) => {
+                let digest = hex::decode(trimmed).ok_or(Error::MalformedVector(i + 1))?;
+                vectors.push(Vector {
+                    line,
+                    json,
+                    digest,
+                });
+            }
+        }
+    }
+
+    match json {
+        Some((line, _)) => Err(Error::MalformedVector(line)),
+        None => Ok(vectors),
+    }
+}
+
+/// Verify the vectors in the given reader against common-JSON hashing, i.e.
+/// with all numbers hashed as floats
+#[cfg(feature = "objecthash-ring")]
+pub fn verify_vectors<R: BufRead>(reader: R) -> Result<Report, Error> {
+    verify_vectors_with(reader, |json| hash_str_with(json, NumberMode::CommonJson))
+}
+
+/// Verify the vectors in the given reader using a custom function to compute
+/// the digest of each JSON document
+pub fn verify_vectors_with<R, F>(reader: R, mut hash: F) -> Result<Report, Error>
+    where R: BufRead,
+          F: FnMut(&str) -> Result<Digest, Error>
+{
+    let mut report = Report::default();
+
+    for vector in parse_vectors(reader)? {
+        match hash(&vector.json) {
+            Ok(ref digest) if digest.as_ref() == &vector.digest[..] => report.passed += 1,
+            actual => {
+                report.failures.push(Failure {
+                    vector,
+                    actual: actual.map(|digest| digest.as_ref().to_vec()),
+                })
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use super::{parse_vectors, verify_vectors, verify_vectors_with};
+    use json::{hash_str_with, NumberMode};
+    use Error;
+
+    const COMMON_JSON: &str = include_str!("../../vectors/common_json.test");
+
+    #[test]
+    fn common_json() {
+        let report = verify_vectors(COMMON_JSON.as_bytes()).unwrap();
+        assert!(report.is_success(), "{:?}", report.failures);
+        assert_eq!(report.passed, 20);
+    }
+
+    #[test]
+    fn failures() {
+        // Integers are hashed differently outside of common-JSON mode
+        let report = verify_vectors_with(COMMON_JSON.as_bytes(),
+                                         |json| hash_str_with(json, NumberMode::Native))
+            .unwrap();
+
+        // With arbitrary precision, so are integers too big for 64 bits
+        let expected = if cfg!(feature = "arbitrary-precision") {
+            vec![22, 24, 26, 28, 40]
+        } else {
+            vec![22, 24, 26, 40]
+        };
+
+        let lines: Vec<usize> = report.failures.iter().map(|f| f.vector.line).collect();
+        assert_eq!(lines, expected);
+        assert_eq!(report.passed, 20 - expected.len());
+    }
+
+    #[test]
+    fn malformed() {
+        let vectors = parse_vectors(&b"# comment\n\n  [1]  \nABCDEF\n"[..]).unwrap();
+        assert_eq!(vectors[0].line, 3);
+        assert_eq!(vectors[0].json, "[1]");
+        assert_eq!(vectors[0].digest, vec![0xab, 0xcd, 0xef]);
+
+        match parse_vectors(&b"[1]\nabc\n"[..]) {
+            Err(Error::MalformedVector(2)) => (),
+            other => panic!("expected a malformed vector error, got {:?}", other),
+        }
+
+        match parse_vectors(&b"[1]\n\n# digest missing\n"[..]) {
+            Err(Error::MalformedVector(1)) => (),
+            other => panic!("expected a malformed vector error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Hashing maps with non-string keys
+//!
+//! `HashMap<K, V>` hashes its keys as whatever type they are, so a map with
+//! integer keys hashes differently from the JSON object (or the dict in
+//! another language) that has the same keys as strings. `StringKeys<I>`
+//! hashes any map, or sequence of pairs, with its keys converted to strings
+//! through their `Display` impls, which is what JSON does with integer keys.
+//!
+//! Profiles with `KeyPolicy::Strings` reject other keys, so maps like these
+//! have to be hashed this way.
+
+use std::fmt::Display;
+
+use {ObjectHash, ObjectHasher};
+use types::{hash_dict_members, hash_member};
+
+/// Hashes the key-value pairs produced by the wrapped iterable as a dict,
+/// with each key hashed as its `Display` string.
+///
+/// Wrap a reference to a map (`StringKeys(&map)`) rather than an owned one,
+/// for the reasons given on [`Set`](crate::set::Set).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StringKeys<I>(pub I);
+
+impl<I, K, V> ObjectHash for StringKeys<I>
+    where I: IntoIterator<Item = (K, V)> + Clone,
+          K: Display,
+          V: ObjectHash
+{
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let mut members: Vec<_> = self.0
+            .clone()
+            .into_iter()
+            .map(|(k, v)| hash_member(&k.to_string(), &v, hasher))
+            .collect();
+
+        hash_dict_members(&mut members, hasher);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::StringKeys;
+    use {digest, hasher, Error, HashProfile, KeyPolicy, ObjectHash, ObjectHasher};
+
+    #[test]
+    fn converting_keys() {
+        let mut ports = HashMap::new();
+        ports.insert(80, "http");
+        ports.insert(443, "https");
+
+        let mut expected = HashMap::new();
+        expected.insert("80".to_owned(), "http");
+        expected.insert("443".to_owned(), "https");
+
+        assert_eq!(digest(&StringKeys(&ports)), digest(&expected));
+        assert!(digest(&ports) != digest(&expected));
+
+        let sorted: BTreeMap<_, _> = ports.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(digest(&StringKeys(&sorted)), digest(&expected));
+    }
+
+    #[test]
+    fn string_keys_policy() {
+        let strings = HashProfile::default().with_key_policy(KeyPolicy::Strings);
+
+        let mut ports = HashMap::new();
+        ports.insert(80, "http");
+
+        let mut hasher = hasher::default().with_profile(strings);
+        ports.objecthash(&mut hasher);
+        match hasher.try_finish() {
+            Err(Error::NonStringKey(b'i')) => (),
+            other => panic!("expected a non-string key error, got {:?}", other),
+        }
+
+        let mut hasher = hasher::default().with_profile(strings);
+        StringKeys(&ports).objecthash(&mut hasher);
+        assert_eq!(hasher.try_finish().unwrap(), digest(&StringKeys(&ports)));
+
+        let mut nested = HashMap::new();
+        nested.insert("ports".to_owned(), StringKeys(&ports));
+        let mut hasher = hasher::default().with_profile(strings);
+        nested.objecthash(&mut hasher);
+        assert!(hasher.try_finish().is_ok());
+    }
+}
@@ -1,35 +1,149 @@
 extern crate unicode_normalization;
 
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[cfg(feature = "avro")]
+extern crate apache_avro;
+
+#[cfg(feature = "cbor")]
+extern crate ciborium;
+
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+#[cfg(feature = "schema")]
+extern crate jsonschema;
+
+#[cfg(feature = "toml")]
+extern crate toml;
+
+#[cfg(feature = "prost")]
+extern crate prost_reflect;
+
+#[cfg(feature = "parallel")]
+extern crate rayon;
+
+#[cfg(feature = "ordered-float")]
+extern crate ordered_float;
+
+#[cfg(feature = "half")]
+extern crate half;
+
+#[cfg(feature = "semver")]
+extern crate semver;
+
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+
+#[cfg(feature = "futures")]
+extern crate futures;
+
+#[cfg(feature = "sled")]
+extern crate sled;
+
+#[cfg(feature = "signatures")]
+extern crate untrusted;
+
+#[cfg(feature = "derive")]
+extern crate objecthash_derive;
+
 #[cfg(test)]
 extern crate rustc_serialize;
 
+// The derived impls in tests refer to this crate by name
+#[cfg(all(test, feature = "derive"))]
+extern crate self as objecthash;
+
 #[macro_use]
 pub mod macros;
 
+#[cfg(feature = "futures")]
+pub mod asynchronous;
+#[cfg(feature = "objecthash-ring")]
+pub mod batch;
+#[cfg(feature = "objecthash-ring")]
+pub mod cache;
+#[cfg(feature = "cid")]
+pub mod cid;
+pub mod disclosure;
+mod error;
 pub mod hasher;
+mod hex;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod keys;
+#[cfg(feature = "objecthash-ring")]
+pub mod log;
+#[cfg(feature = "objecthash-ring")]
+pub mod map;
+pub mod memo;
+pub mod multimap;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod path;
+pub mod profile;
+pub mod proof;
+#[cfg(feature = "prost")]
+pub mod proto;
+pub mod redaction;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod serializer;
+pub mod set;
+#[cfg(feature = "signatures")]
+pub mod signature;
+#[cfg(feature = "objecthash-ring")]
+pub mod store;
+pub mod stream;
+#[cfg(feature = "objecthash-ring")]
+pub mod testing;
 pub mod types;
+pub mod versioned;
+
+pub use error::Error;
+pub use memo::Memoized;
+pub use profile::{Canonicalizer, FloatFormat, HashProfile, KeyPolicy, Limit, Limits, NumberMode};
+pub use redaction::Redactable;
+pub use set::{as_set, Set};
+pub use types::{ExtensionTags, Normalization};
+
+#[cfg(feature = "derive")]
+pub use objecthash_derive::ObjectHash;
 
 const MAX_OUTPUT_LEN: usize = 32;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Digest {
     output_len: usize,
     value: [u8; MAX_OUTPUT_LEN],
 }
 
 impl Digest {
-    pub fn new(bytes: &[u8]) -> Result<Digest, ()> {
+    pub fn new(bytes: &[u8]) -> Result<Digest, Error> {
         if bytes.len() > MAX_OUTPUT_LEN {
-            return Err(());
+            return Err(Error::DigestLength(bytes.len()));
         }
 
         let mut digest_bytes = [0u8; MAX_OUTPUT_LEN];
-        digest_bytes.copy_from_slice(bytes);
+        digest_bytes[..bytes.len()].copy_from_slice(bytes);
 
         Ok(Digest {
             output_len: bytes.len(),
             value: digest_bytes,
         })
     }
+
+    /// Compare with another digest in constant time, so how long the
+    /// comparison takes doesn't reveal how much of the digests match
+    pub fn ct_eq(&self, other: &Digest) -> bool {
+        hex::ct_eq(self.as_ref(), other.as_ref())
+    }
+
+    /// Compare with a digest in hex (either case) in constant time
+    pub fn ct_eq_hex(&self, hex: &str) -> bool {
+        hex::ct_eq_hex(self.as_ref(), hex)
+    }
 }
 
 impl AsRef<[u8]> for Digest {
@@ -46,11 +160,76 @@ pub fn digest<T: ObjectHash + ?Sized>(msg: &T) -> Digest {
     hasher.finish()
 }
 
+/// Check that a value has the expected digest, comparing them in constant
+/// time
+#[cfg(feature = "objecthash-ring")]
+pub fn verify<T: ObjectHash + ?Sized>(msg: &T, expected: &Digest) -> bool {
+    digest(msg).ct_eq(expected)
+}
+
+/// Check that a value has the expected digest, given in hex, comparing them
+/// in constant time
+#[cfg(feature = "objecthash-ring")]
+pub fn verify_hex<T: ObjectHash + ?Sized>(msg: &T, expected: &str) -> bool {
+    digest(msg).ct_eq_hex(expected)
+}
+
 pub trait ObjectHasher {
     fn output_len(&self) -> usize;
+
+    /// Create a fresh hasher using the same algorithm, for hashing a nested
+    /// value whose digest is needed before it can be fed to this one
+    fn nested(&self) -> Self;
+
     fn update(&mut self, bytes: &[u8]);
     fn update_nested<F>(&mut self, nested: F) where F: Fn(&mut Self);
+
+    /// Settings for how values are hashed, which nested hashers share
+    #[inline]
+    fn profile(&self) -> HashProfile {
+        HashProfile::default()
+    }
+
+    /// The first byte hashed into this hasher, i.e. the tag of the value
+    /// being hashed, if the hasher keeps track of it
+    #[inline]
+    fn tag(&self) -> Option<u8> {
+        None
+    }
+
+    /// Record an error in the value being hashed, to be returned from
+    /// `try_finish`. Hashers which never reject values can ignore it
+    #[inline]
+    fn fail(&mut self, _error: Error) {}
+
+    /// Start hashing the shared allocation at the given address, returning
+    /// false if it's already being hashed further up, i.e. the value is
+    /// part of a cycle and mustn't be hashed again. Hashers which don't
+    /// detect cycles always return true
+    #[inline]
+    fn enter_shared(&mut self, _address: usize) -> bool {
+        true
+    }
+
+    /// Finish hashing the shared allocation most recently entered
+    #[inline]
+    fn leave_shared(&mut self) {}
+
+    /// Finish with the given digest in place of whatever is hashed into this
+    /// hasher, so a redacted value contributes its original digest to the
+    /// structure containing it
+    fn redact(&mut self, digest: &Digest);
+
     fn finish(self) -> Digest;
+
+    /// Finish, returning the first error recorded with `fail` by this hasher
+    /// or any of the hashers nested within it, if there was one
+    #[inline]
+    fn try_finish(self) -> Result<Digest, Error>
+        where Self: Sized
+    {
+        Ok(self.finish())
+    }
 }
 
 pub trait ObjectHash {
@@ -69,4 +248,20 @@ mod tests {
         assert_eq!(result.as_ref().to_hex(),
                    "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b");
     }
+
+    #[test]
+    fn verify_test() {
+        use {verify, verify_hex};
+
+        assert!(verify(&1000, &digest(&1000)));
+        assert!(!verify(&1001, &digest(&1000)));
+        assert!(!verify(&1000, &::Digest::new(&digest(&1000).as_ref()[..16]).unwrap()));
+
+        assert!(verify_hex(&1000, "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b"));
+        assert!(verify_hex(&1000, "A3346D18105EF801C3598FEC426DCC5D4BE9D0374DA5343F6C8DCBDF24CB8E0B"));
+        assert!(!verify_hex(&1000, "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0c"));
+        assert!(!verify_hex(&1000, "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0"));
+        assert!(!verify_hex(&1000, "z3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b"));
+        assert!(!verify_hex(&1000, ""));
+    }
 }
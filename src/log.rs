@@ -0,0 +1,423 @@
+//! Verifiable append-only logs
+//!
+//! A `Log` is a sequence of records' digests summarized by a Merkle tree, as
+//! in Certificate Transparency (RFC 6962): leaves hash as `H(0x00 || digest)`
+//! and interior nodes as `H(0x01 || left || right)`, where `digest` is the
+//! record's ObjectHash digest. The root is maintained incrementally as
+//! records are appended.
+//!
+//! `InclusionProof` shows that a record is in the log at a given index, and
+//! `ConsistencyProof` that a log with a given root is an extension of an
+//! earlier one, i.e. that records were only ever appended to it.
+
+use {digest, hasher, Digest, ObjectHash, ObjectHasher};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Append-only log of records' digests
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Log {
+    // Leaf hashes, in order
+    leaves: Vec<Digest>,
+
+    // Roots of the perfect subtrees covering the leaves, with their heights,
+    // from left to right
+    frontier: Vec<(usize, Digest)>,
+}
+
+impl Log {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Log::default()
+    }
+
+    /// Append a record, returning its index
+    pub fn append<T: ObjectHash + ?Sized>(&mut self, record: &T) -> usize {
+        self.append_digest(&digest(record))
+    }
+
+    /// Append a record given its digest, returning its index
+    pub fn append_digest(&mut self, record: &Digest) -> usize {
+        let leaf = leaf_hash(record);
+        self.leaves.push(leaf);
+
+        let mut subtree = (0, leaf);
+
+        while let Some(&(height, left)) = self.frontier.last() {
+            if height != subtree.0 {
+                break;
+            }
+
+            self.frontier.pop();
+            subtree = (height + 1, node_hash(&left, &subtree.1));
+        }
+
+        self.frontier.push(subtree);
+        self.leaves.len() - 1
+    }
+
+    /// Number of records in the log
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Root of the Merkle tree over the whole log
+    pub fn root(&self) -> Digest {
+        let mut subtrees = self.frontier.iter().rev();
+
+        match subtrees.next() {
+            Some(&(_, last)) => subtrees.fold(last, |right, &(_, left)| node_hash(&left, &right)),
+            None => hasher::default().finish(),
+        }
+    }
+
+    /// Root of the Merkle tree over the first `size` records, or `None` if
+    /// the log is shorter than that
+    pub fn root_at(&self, size: usize) -> Option<Digest> {
+        if size > self.len() {
+            return None;
+        }
+
+        Some(tree_hash(&self.leaves[..size]))
+    }
+
+    /// Prove the record at the given index is in the log as of the given
+    /// size, or return `None` if the index isn't within that size or the log
+    /// is shorter than it
+    pub fn inclusion_proof(&self, index: usize, size: usize) -> Option<InclusionProof> {
+        if index >= size || size > self.len() {
+            return None;
+        }
+
+        let mut path = Vec::new();
+        inclusion_path(index, &self.leaves[..size], &mut path);
+
+        Some(InclusionProof { index, size, path })
+    }
+
+    /// Prove the log as of `new_size` extends the log as of `old_size`, or
+    /// return `None` if `old_size` is larger than `new_size` or the log is
+    /// shorter than `new_size`
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Option<ConsistencyProof> {
+        if old_size > new_size || new_size > self.len() {
+            return None;
+        }
+
+        let mut path = Vec::new();
+
+        if old_size > 0 && old_size < new_size {
+            consistency_path(old_size, &self.leaves[..new_size], true, &mut path);
+        }
+
+        Some(ConsistencyProof {
+            old_size,
+            new_size,
+            path,
+        })
+    }
+}
+
+/// Proof that a record is at a given index of a log of a given size
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InclusionProof {
+    /// Index of the record within the log
+    pub index: usize,
+
+    /// Size of the log the proof is for
+    pub size: usize,
+
+    /// Hashes of the sibling subtrees from the leaf up to the root
+    pub path: Vec<Digest>,
+}
+
+impl InclusionProof {
+    /// Verify that the given record is at the proof's index of the log with
+    /// the given root
+    pub fn verify<T: ObjectHash + ?Sized>(&self, record: &T, root: &Digest) -> bool {
+        self.verify_digest(&digest(record), root)
+    }
+
+    /// Verify that the record with the given digest is at the proof's index
+    /// of the log with the given root
+    pub fn verify_digest(&self, record: &Digest, root: &Digest) -> bool {
+        if self.index >= self.size {
+            return false;
+        }
+
+        let mut index = self.index;
+        let mut last = self.size - 1;
+        let mut hash = leaf_hash(record);
+
+        for sibling in &self.path {
+            if last == 0 {
+                return false;
+            }
+
+            if index & 1 == 1 || index == last {
+                hash = node_hash(sibling, &hash);
+
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, sibling);
+            }
+
+            index >>= 1;
+            last >>= 1;
+        }
+
+        last == 0 && hash == *root
+    }
+}
+
+/// Proof that a log of one size extends the same log of a smaller size
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsistencyProof {
+    /// Size of the earlier log
+    pub old_size: usize,
+
+    /// Size of the later log
+    pub new_size: usize,
+
+    /// Hashes of the subtrees needed to compute both roots
+    pub path: Vec<Digest>,
+}
+
+impl ConsistencyProof {
+    /// Verify that the log with root `new_root` extends the log with root
+    /// `old_root`
+    pub fn verify(&self, old_root: &Digest, new_root: &Digest) -> bool {
+        if self.old_size > self.new_size {
+            return false;
+        }
+
+        if self.old_size == self.new_size {
+            return self.path.is_empty() && old_root == new_root;
+        }
+
+        if self.old_size == 0 {
+            return self.path.is_empty();
+        }
+
+        let mut path = self.path.iter();
+
+        // When the old tree is a perfect subtree of the new one, its root is
+        // the first node of the proof, and is left out of it
+        let first = if self.old_size.is_power_of_two() {
+            *old_root
+        } else {
+            match path.next() {
+                Some(digest) => *digest,
+                None => return false,
+            }
+        };
+
+        let mut index = self.old_size - 1;
+        let mut last = self.new_size - 1;
+
+        while index & 1 == 1 {
+            index >>= 1;
+            last >>= 1;
+        }
+
+        let mut old_hash = first;
+        let mut new_hash = first;
+
+        for digest in path {
+            if last == 0 {
+                return false;
+            }
+
+            if index & 1 == 1 || index == last {
+                old_hash = node_hash(digest, &old_hash);
+                new_hash = node_hash(digest, &new_hash);
+
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                new_hash = node_hash(&new_hash, digest);
+            }
+
+            index >>= 1;
+            last >>= 1;
+        }
+
+        last == 0 && old_hash == *old_root && new_hash == *new_root
+    }
+}
+
+fn leaf_hash(record: &Digest) -> Digest {
+    let mut hasher = hasher::default();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(record.as_ref());
+    hasher.finish()
+}
+
+fn node_hash(left: &Digest, right: &Digest) -> Digest {
+    let mut hasher = hasher::default();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left.as_ref());
+    hasher.update(right.as_ref());
+    hasher.finish()
+}
+
+// The largest power of two smaller than n, where n > 1
+fn split(n: usize) -> usize {
+    let mut k = 1;
+
+    while k * 2 < n {
+        k *= 2;
+    }
+
+    k
+}
+
+fn tree_hash(leaves: &[Digest]) -> Digest {
+    match leaves.len() {
+        0 => hasher::default().finish(),
+        1 => leaves[0],
+        n => {
+            let k = split(n);
+            node_hash(&tree_hash(&leaves[..k]), &tree_hash(&leaves[k..]))
+        }
+    }
+}
+
+fn inclusion_path(index: usize, leaves: &[Digest], path: &mut Vec<Digest>) {
+    let n = leaves.len();
+
+    if n <= 1 {
+        return;
+    }
+
+    let k = split(n);
+
+    if index < k {
+        inclusion_path(index, &leaves[..k], path);
+        path.push(tree_hash(&leaves[k..]));
+    } else {
+        inclusion_path(index - k, &leaves[k..], path);
+        path.push(tree_hash(&leaves[..k]));
+    }
+}
+
+fn consistency_path(old_size: usize, leaves: &[Digest], complete: bool, path: &mut Vec<Digest>) {
+    let n = leaves.len();
+
+    if old_size == n {
+        if !complete {
+            path.push(tree_hash(leaves));
+        }
+
+        return;
+    }
+
+    let k = split(n);
+
+    if old_size <= k {
+        consistency_path(old_size, &leaves[..k], complete, path);
+        path.push(tree_hash(&leaves[k..]));
+    } else {
+        consistency_path(old_size - k, &leaves[k..], false, path);
+        path.push(tree_hash(&leaves[..k]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{leaf_hash, node_hash, split, Log};
+    use {digest, hasher, ObjectHasher};
+
+    fn log(size: usize) -> Log {
+        let mut log = Log::new();
+
+        for i in 0..size {
+            assert_eq!(log.append(&format!("record {}", i)), i);
+        }
+
+        log
+    }
+
+    #[test]
+    fn splitting() {
+        let splits: Vec<usize> = (2..10).map(split).collect();
+        assert_eq!(splits, vec![1, 2, 2, 4, 4, 4, 4, 8]);
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(Log::new().root(), hasher::default().finish());
+
+        let log = log(3);
+        let leaves: Vec<_> = (0..3).map(|i| leaf_hash(&digest(&format!("record {}", i)))).collect();
+        assert_eq!(log.root(),
+                   node_hash(&node_hash(&leaves[0], &leaves[1]), &leaves[2]));
+
+        let log = self::log(20);
+        for size in 0..21 {
+            assert_eq!(log.root_at(size), Some(self::log(size).root()));
+        }
+        assert_eq!(log.root_at(21), None);
+    }
+
+    #[test]
+    fn inclusion() {
+        let log = log(13);
+
+        for size in 1..14 {
+            let root = log.root_at(size).unwrap();
+
+            for index in 0..size {
+                let record = format!("record {}", index);
+                let proof = log.inclusion_proof(index, size).unwrap();
+                assert!(proof.verify(&record, &root));
+                assert!(!proof.verify("another record", &root));
+
+                let mut moved = proof.clone();
+                moved.index = (index + 1) % size;
+                assert!(size == 1 || !moved.verify(&record, &root));
+            }
+        }
+
+        assert_eq!(log.inclusion_proof(3, 3), None);
+        assert_eq!(log.inclusion_proof(0, 14), None);
+    }
+
+    #[test]
+    fn consistency() {
+        let log = log(13);
+
+        for new_size in 0..14 {
+            let new_root = log.root_at(new_size).unwrap();
+
+            for old_size in 0..new_size + 1 {
+                let old_root = log.root_at(old_size).unwrap();
+                let proof = log.consistency_proof(old_size, new_size).unwrap();
+                assert!(proof.verify(&old_root, &new_root));
+
+                if old_size > 0 && old_size < new_size {
+                    let other_root = Log::new().root_at(0).unwrap();
+                    assert!(!proof.verify(&other_root, &new_root));
+                    assert!(!proof.verify(&old_root, &other_root));
+                }
+            }
+        }
+
+        let mut forked = self::log(5);
+        forked.append("forked record");
+        let proof = log.consistency_proof(5, 6).unwrap();
+        assert!(!proof.verify(&log.root_at(5).unwrap(), &forked.root()));
+
+        assert_eq!(log.consistency_proof(4, 3), None);
+        assert_eq!(log.consistency_proof(3, 14), None);
+    }
+}
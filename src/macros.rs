@@ -1,5 +1,22 @@
 #[macro_export]
 macro_rules! objecthash_member {
+    ($hasher:expr, $key:expr => $value:expr) => {
+        {
+            let mut key_hasher = $hasher.nested();
+            $crate::ObjectHash::objecthash(&$key, &mut key_hasher);
+            let key_digest = key_hasher.finish();
+
+            let mut value_hasher = $hasher.nested();
+            $crate::ObjectHash::objecthash(&$value, &mut value_hasher);
+            let value_digest = value_hasher.finish();
+
+            let mut result = Vec::with_capacity(key_digest.as_ref().len() + value_digest.as_ref().len());
+
+            result.extend_from_slice(key_digest.as_ref());
+            result.extend_from_slice(value_digest.as_ref());
+            result
+        }
+    };
     ($key:expr => $value:expr) => {
         {
             let key_digest = $crate::digest($key);
@@ -15,28 +32,56 @@ macro_rules! objecthash_member {
 
 #[macro_export]
 macro_rules! objecthash_struct(
+    { $hasher:expr, schema = $schema:expr, $($key:expr => $value:expr),+ } => {
+        $crate::versioned::hash_versioned($schema, $hasher, |h| objecthash_struct!(h, $($key => $value),+))
+    };
     { $hasher:expr, $($key:expr => $value:expr),+ } => {
         {
+            use $crate::types::DictMembers;
+
             let mut digests: Vec<Vec<u8>> = Vec::new();
 
             $(
-                digests.push(objecthash_member!($key => $value));
+                digests.push(objecthash_member!($hasher, $key => $value));
             )+
 
-            digests.sort();
+            $hasher.hash_dict_members(&mut digests);
+        }
+     };
+);
 
-            $hasher.update($crate::types::DICT_TAG);
-            for value in &digests {
-                $hasher.update(&value);
+#[macro_export]
+macro_rules! objecthash_schema(
+    { $name:ident, $($key:expr => $type:ty),+ } => {
+        impl $crate::schema::Schema for $name {
+            fn schema() -> $crate::schema::TypeSchema {
+                $crate::schema::TypeSchema::Struct {
+                    name: stringify!($name).to_owned(),
+                    fields: vec![$(($key.to_owned(), <$type as $crate::schema::Schema>::schema())),+],
+                }
             }
         }
-     };
+    };
+);
+
+#[macro_export]
+macro_rules! assert_objecthash_eq(
+    ($left:expr, $right:expr) => {
+        if let Some(difference) = $crate::testing::first_difference(&$left, &$right) {
+            panic!("{}", difference);
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        if let Some(difference) = $crate::testing::first_difference(&$left, &$right) {
+            panic!("{}: {}", format_args!($($arg)+), difference);
+        }
+    };
 );
 
 #[cfg(test)]
 #[cfg(feature = "objecthash-ring")]
 mod tests {
-    use {hasher, ObjectHasher};
+    use {hasher, Error, HashProfile, ObjectHasher};
     use rustc_serialize::hex::ToHex;
 
     #[test]
@@ -49,5 +94,25 @@ mod tests {
             h.finish().as_ref().to_hex(),
             "bf4c58f5e308e31e2cd64bdbf7a01b9b595a13602438be5e912c7d94f6d8177a"
         );
+
+        let mut h = hasher::default().with_profile(HashProfile::default().with_reject_duplicate_keys(true));
+        objecthash_struct!(h, "foo" => &1, "bar" => &2, "foo" => &3);
+
+        match h.try_finish() {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, ::digest("foo")),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn objecthash_member_test() {
+        use digest;
+
+        let h = hasher::default();
+        assert_eq!(objecthash_member!(h, "foo" => 1), objecthash_member!("foo" => &1));
+
+        let mut expected = digest("foo").as_ref().to_vec();
+        expected.extend_from_slice(digest(&1).as_ref());
+        assert_eq!(objecthash_member!(h, "foo" => 1), expected);
     }
 }
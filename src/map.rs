@@ -0,0 +1,465 @@
+//! Verifiable maps
+//!
+//! A `VerifiableMap` maps digests to records' digests, and commits to all of
+//! its entries with a single root: that of a sparse Merkle tree with a leaf
+//! for each key, at the position given by the bits of the key. Subtrees with
+//! no entries hash as the digest of the empty string, and those with a single
+//! entry as that entry's leaf, `H(0x00 || key || value)`. Other subtrees hash
+//! as `H(0x01 || left || right)`.
+//!
+//! A `MapProof` shows either that a key maps to a given record, or that it
+//! isn't in the map at all.
+//!
+//! Keys have to be as long as the digests the map is hashed with, since
+//! their bits give the path to their leaves: shorter or longer keys are
+//! rejected.
+
+use std::mem;
+
+use {digest, hasher, Digest, Error, ObjectHash, ObjectHasher};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Map from digests to records' digests, with a Merkle root
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiableMap {
+    root: Node,
+    len: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Empty,
+    Leaf(Leaf),
+    Branch(Box<Branch>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Leaf {
+    key: Digest,
+    value: Digest,
+    hash: Digest,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Branch {
+    left: Node,
+    right: Node,
+    hash: Digest,
+}
+
+impl VerifiableMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        VerifiableMap {
+            root: Node::Empty,
+            len: 0,
+        }
+    }
+
+    /// Map the key to the given record, returning the digest of the record
+    /// it previously mapped to
+    pub fn insert<T: ObjectHash + ?Sized>(&mut self, key: &Digest, record: &T) -> Result<Option<Digest>, Error> {
+        self.insert_digest(key, &digest(record))
+    }
+
+    /// Map the key to the record with the given digest, returning the digest
+    /// of the record it previously mapped to
+    pub fn insert_digest(&mut self, key: &Digest, value: &Digest) -> Result<Option<Digest>, Error> {
+        check_key(key)?;
+        let root = mem::replace(&mut self.root, Node::Empty);
+        let (root, previous) = insert(root, Leaf::new(key, value), 0);
+        self.root = root;
+
+        if previous.is_none() {
+            self.len += 1;
+        }
+
+        Ok(previous)
+    }
+
+    /// Remove the key from the map, returning the digest of the record it
+    /// mapped to
+    pub fn remove(&mut self, key: &Digest) -> Result<Option<Digest>, Error> {
+        check_key(key)?;
+        let root = mem::replace(&mut self.root, Node::Empty);
+        let (root, removed) = remove(root, key, 0);
+        self.root = root;
+
+        if removed.is_some() {
+            self.len -= 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Digest of the record the key maps to
+    pub fn get(&self, key: &Digest) -> Result<Option<&Digest>, Error> {
+        check_key(key)?;
+        let mut node = &self.root;
+        let mut depth = 0;
+
+        loop {
+            match *node {
+                Node::Empty => return Ok(None),
+                Node::Leaf(ref leaf) => return Ok(if leaf.key == *key { Some(&leaf.value) } else { None }),
+                Node::Branch(ref branch) => node = branch.child(key, depth),
+            }
+
+            depth += 1;
+        }
+    }
+
+    /// Number of entries in the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Root of the Merkle tree over the map's entries
+    pub fn root(&self) -> Digest {
+        self.root.hash()
+    }
+
+    /// Prove whether or not the key is in the map, and if so what it maps to
+    pub fn prove(&self, key: &Digest) -> Result<MapProof, Error> {
+        check_key(key)?;
+        let mut siblings = Vec::new();
+        let mut node = &self.root;
+
+        loop {
+            match *node {
+                Node::Empty => break,
+                Node::Leaf(ref leaf) => {
+                    return Ok(MapProof {
+                        siblings,
+                        leaf: Some((leaf.key, leaf.value)),
+                    })
+                }
+                Node::Branch(ref branch) => {
+                    let depth = siblings.len();
+                    let (child, sibling) = if bit(key, depth) {
+                        (&branch.right, &branch.left)
+                    } else {
+                        (&branch.left, &branch.right)
+                    };
+
+                    siblings.push(sibling.hash());
+                    node = child;
+                }
+            }
+        }
+
+        Ok(MapProof {
+            siblings,
+            leaf: None,
+        })
+    }
+}
+
+impl Default for VerifiableMap {
+    fn default() -> Self {
+        VerifiableMap::new()
+    }
+}
+
+/// Proof of the entry for a key in a map, or that there's no such entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapProof {
+    /// Hashes of the sibling subtrees on the path to the key, from the root
+    pub siblings: Vec<Digest>,
+
+    /// Key and value digests of the entry at the end of the path, if any.
+    /// For keys which aren't in the map this is another entry, or `None` if
+    /// the path ends in an empty subtree
+    pub leaf: Option<(Digest, Digest)>,
+}
+
+impl MapProof {
+    /// Verify that the key maps to the given record in the map with the
+    /// given root, or if `record` is `None`, that the key isn't in the map
+    pub fn verify<T: ObjectHash + ?Sized>(&self, key: &Digest, record: Option<&T>, root: &Digest) -> bool {
+        self.verify_digest(key, record.map(digest).as_ref(), root)
+    }
+
+    /// Verify that the key maps to the record with the given digest in the
+    /// map with the given root, or if `value` is `None`, that the key isn't
+    /// in the map. Keys of the wrong length are never in a map
+    pub fn verify_digest(&self, key: &Digest, value: Option<&Digest>, root: &Digest) -> bool {
+        let depth = self.siblings.len();
+
+        if check_key(key).is_err() || depth > key.as_ref().len() * 8 {
+            return false;
+        }
+
+        // The proof's leaf key is compared with the key bit by bit, so it has
+        // to be checked too
+        if let Some((ref k, _)) = self.leaf {
+            if check_key(k).is_err() {
+                return false;
+            }
+        }
+
+        let mut hash = match (&self.leaf, value) {
+            (Some((k, v)), Some(value)) if k == key && v == value => Leaf::new(k, v).hash,
+            (Some((k, v)), None) if k != key && (0..depth).all(|i| bit(k, i) == bit(key, i)) => {
+                Leaf::new(k, v).hash
+            }
+            (None, None) => empty_hash(),
+            _ => return false,
+        };
+
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            hash = if bit(key, depth) {
+                node_hash(sibling, &hash)
+            } else {
+                node_hash(&hash, sibling)
+            };
+        }
+
+        hash == *root
+    }
+}
+
+impl Node {
+    fn hash(&self) -> Digest {
+        match *self {
+            Node::Empty => empty_hash(),
+            Node::Leaf(ref leaf) => leaf.hash,
+            Node::Branch(ref branch) => branch.hash,
+        }
+    }
+}
+
+impl Leaf {
+    fn new(key: &Digest, value: &Digest) -> Self {
+        let mut hasher = hasher::default();
+        hasher.update(&[LEAF_PREFIX]);
+        hasher.update(key.as_ref());
+        hasher.update(value.as_ref());
+
+        Leaf {
+            key: *key,
+            value: *value,
+            hash: hasher.finish(),
+        }
+    }
+}
+
+impl Branch {
+    fn child(&self, key: &Digest, depth: usize) -> &Node {
+        if bit(key, depth) { &self.right } else { &self.left }
+    }
+}
+
+// The subtree with the given children
+fn join(left: Node, right: Node) -> Node {
+    let hash = node_hash(&left.hash(), &right.hash());
+    Node::Branch(Box::new(Branch { left, right, hash }))
+}
+
+fn insert(node: Node, leaf: Leaf, depth: usize) -> (Node, Option<Digest>) {
+    match node {
+        Node::Empty => (Node::Leaf(leaf), None),
+        Node::Leaf(existing) => {
+            if existing.key == leaf.key {
+                (Node::Leaf(leaf), Some(existing.value))
+            } else {
+                (split(existing, leaf, depth), None)
+            }
+        }
+        Node::Branch(branch) => {
+            let Branch { left, right, .. } = *branch;
+
+            if bit(&leaf.key, depth) {
+                let (right, previous) = insert(right, leaf, depth + 1);
+                (join(left, right), previous)
+            } else {
+                let (left, previous) = insert(left, leaf, depth + 1);
+                (join(left, right), previous)
+            }
+        }
+    }
+}
+
+// The subtree at the given depth containing just the two leaves
+fn split(a: Leaf, b: Leaf, depth: usize) -> Node {
+    match (bit(&a.key, depth), bit(&b.key, depth)) {
+        (false, true) => join(Node::Leaf(a), Node::Leaf(b)),
+        (true, false) => join(Node::Leaf(b), Node::Leaf(a)),
+        (false, false) => join(split(a, b, depth + 1), Node::Empty),
+        (true, true) => join(Node::Empty, split(a, b, depth + 1)),
+    }
+}
+
+fn remove(node: Node, key: &Digest, depth: usize) -> (Node, Option<Digest>) {
+    match node {
+        Node::Leaf(leaf) => {
+            if leaf.key == *key {
+                (Node::Empty, Some(leaf.value))
+            } else {
+                (Node::Leaf(leaf), None)
+            }
+        }
+        Node::Branch(branch) => {
+            let Branch { left, right, .. } = *branch;
+
+            let (left, right, removed) = if bit(key, depth) {
+                let (right, removed) = remove(right, key, depth + 1);
+                (left, right, removed)
+            } else {
+                let (left, removed) = remove(left, key, depth + 1);
+                (left, right, removed)
+            };
+
+            // A subtree left with a single entry is replaced by its leaf
+            let node = match (left, right) {
+                (Node::Empty, Node::Leaf(leaf)) |
+                (Node::Leaf(leaf), Node::Empty) => Node::Leaf(leaf),
+                (left, right) => join(left, right),
+            };
+
+            (node, removed)
+        }
+        Node::Empty => (Node::Empty, None),
+    }
+}
+
+// Keys have to be the length of the digests they're hashed with, so that
+// every key has a bit at each depth of the tree
+fn check_key(key: &Digest) -> Result<(), Error> {
+    let len = key.as_ref().len();
+
+    if len == hasher::default().output_len() {
+        Ok(())
+    } else {
+        Err(Error::KeyLength(len))
+    }
+}
+
+fn bit(key: &Digest, depth: usize) -> bool {
+    key.as_ref()[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+fn empty_hash() -> Digest {
+    hasher::default().finish()
+}
+
+fn node_hash(left: &Digest, right: &Digest) -> Digest {
+    let mut hasher = hasher::default();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left.as_ref());
+    hasher.update(right.as_ref());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{empty_hash, MapProof, VerifiableMap};
+    use {digest, Digest, Error};
+
+    fn key(i: usize) -> Digest {
+        digest(&format!("key {}", i))
+    }
+
+    fn map(keys: &[usize]) -> VerifiableMap {
+        let mut map = VerifiableMap::new();
+
+        for &i in keys {
+            assert_eq!(map.insert(&key(i), &format!("record {}", i)).unwrap(), None);
+        }
+
+        map
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(VerifiableMap::new().root(), empty_hash());
+
+        let forwards: Vec<usize> = (0..50).collect();
+        let backwards: Vec<usize> = (0..50).rev().collect();
+        let map = self::map(&forwards);
+        assert_eq!(map.len(), 50);
+        assert_eq!(map.root(), self::map(&backwards).root());
+        assert_eq!(map.get(&key(7)).unwrap(), Some(&digest("record 7")));
+        assert_eq!(map.get(&key(50)).unwrap(), None);
+
+        let mut updated = map.clone();
+        assert_eq!(updated.insert(&key(7), "updated").unwrap(), Some(digest("record 7")));
+        assert_eq!(updated.len(), 50);
+        assert!(updated.root() != map.root());
+
+        let mut removed = map.clone();
+        for i in 25..50 {
+            assert_eq!(removed.remove(&key(i)).unwrap(), Some(digest(&format!("record {}", i))));
+        }
+        assert_eq!(removed.remove(&key(25)).unwrap(), None);
+        assert_eq!(removed, self::map(&forwards[..25]));
+
+        for i in 0..25 {
+            removed.remove(&key(i)).unwrap();
+        }
+        assert_eq!(removed, VerifiableMap::new());
+    }
+
+    #[test]
+    fn proofs() {
+        let keys: Vec<usize> = (0..50).collect();
+        let map = map(&keys);
+        let root = map.root();
+
+        for i in 0..50 {
+            let record = format!("record {}", i);
+            let proof = map.prove(&key(i)).unwrap();
+            assert!(proof.verify(&key(i), Some(&record), &root));
+            assert!(!proof.verify(&key(i), Some("another record"), &root));
+            assert!(!proof.verify::<str>(&key(i), None, &root));
+            assert!(!proof.verify(&key(i + 1), Some(&record), &root));
+        }
+
+        for i in 50..100 {
+            let proof = map.prove(&key(i)).unwrap();
+            assert!(proof.verify::<str>(&key(i), None, &root));
+            assert!(!proof.verify(&key(i), Some("record"), &root));
+
+            if let Some((k, v)) = proof.leaf {
+                assert!(map.prove(&k).unwrap().verify_digest(&k, Some(&v), &root));
+            }
+        }
+
+        let empty = VerifiableMap::new();
+        assert!(empty.prove(&key(0)).unwrap().verify::<str>(&key(0), None, &empty.root()));
+    }
+
+    #[test]
+    fn key_lengths() {
+        let short = Digest::new(&[0x80]).unwrap();
+        let longer = Digest::new(&[0x80, 0]).unwrap();
+        let mut map = map(&[0, 1, 2]);
+
+        for key in &[short, longer] {
+            match map.insert_digest(key, &digest("record")) {
+                Err(Error::KeyLength(len)) => assert_eq!(len, key.as_ref().len()),
+                other => panic!("expected a key length error, got {:?}", other),
+            }
+
+            assert!(map.get(key).is_err());
+            assert!(map.prove(key).is_err());
+            assert!(map.remove(key).is_err());
+        }
+        assert_eq!(map, self::map(&[0, 1, 2]));
+
+        // A forged proof with a short leaf key for some other key
+        let root = map.root();
+        let proof = MapProof {
+            siblings: vec![empty_hash(); 9],
+            leaf: Some((short, digest("record"))),
+        };
+        assert!(!proof.verify::<str>(&key(3), None, &root));
+        assert!(!MapProof { siblings: vec![], leaf: None }.verify::<str>(&short, None, &empty_hash()));
+    }
+}
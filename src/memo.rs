@@ -0,0 +1,197 @@
+//! Caching the digests of immutable values
+//!
+//! A large value which is shared between many structures (a schema, a
+//! catalog entry) gets rehashed every time one of them is hashed. Wrapping it
+//! in `Memoized<T>` hashes it once, and contributes the cached digest to the
+//! structures containing it from then on.
+
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use {Digest, HashProfile, ObjectHash, ObjectHasher};
+
+/// An immutable value whose digest is computed the first time it's hashed,
+/// and reused afterwards.
+///
+/// The cached digest is only reused by hashers with the same profile and
+/// output length as the one which computed it: others hash the value
+/// afresh. Hashers whose profile can reject values (with limits, say)
+/// always hash it afresh, and cache nothing, so it counts against their
+/// limits and any error it raises is reported as it would be without the
+/// wrapper. Hashing the same value with two different algorithms of the
+/// same output length would reuse the wrong digest, so don't.
+#[derive(Clone, Debug)]
+pub struct Memoized<T> {
+    value: T,
+    digest: OnceLock<(HashProfile, Digest)>,
+}
+
+impl<T> Memoized<T> {
+    /// Wrap a value, which will be hashed when first needed
+    pub fn new(value: T) -> Self {
+        Memoized {
+            value,
+            digest: OnceLock::new(),
+        }
+    }
+
+    /// The cached digest, if the value has been hashed
+    pub fn cached_digest(&self) -> Option<&Digest> {
+        self.digest.get().map(|cached| &cached.1)
+    }
+
+    /// Unwrap the value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Memoized<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> From<T> for Memoized<T> {
+    fn from(value: T) -> Self {
+        Memoized::new(value)
+    }
+}
+
+impl<T: ObjectHash> ObjectHash for Memoized<T> {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let profile = hasher.profile();
+
+        // Hashing the value into a hasher of its own would count it as one
+        // level deeper than it is, and its error, if any, would be mixed up
+        // with those of the values hashed before it
+        if profile.rejects_values() {
+            self.value.objecthash(hasher);
+            return;
+        }
+
+        if let Some(&(cached_profile, ref digest)) = self.digest.get() {
+            if cached_profile == profile && digest.as_ref().len() == hasher.output_len() {
+                hasher.redact(digest);
+            } else {
+                self.value.objecthash(hasher);
+            }
+
+            return;
+        }
+
+        let mut nested_hasher = hasher.nested();
+        self.value.objecthash(&mut nested_hasher);
+        let digest = nested_hasher.finish();
+
+        // Another thread may have cached the same digest meanwhile
+        let _ = self.digest.set((profile, digest));
+        hasher.redact(&digest);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::cell::Cell;
+
+    use super::Memoized;
+    use {digest, hasher, Error, HashProfile, Limit, Limits, Normalization, ObjectHash, ObjectHasher};
+    use types::LIST_TAG;
+
+    // Counts how many times it's been hashed
+    struct Counted<'a>(&'a Cell<usize>, Vec<String>);
+
+    impl<'a> ObjectHash for Counted<'a> {
+        fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+            self.0.set(self.0.get() + 1);
+            self.1.objecthash(hasher);
+        }
+    }
+
+    #[test]
+    fn memoizing() {
+        let count = Cell::new(0);
+        let schema = vec!["id".to_owned(), "name".to_owned()];
+        let memoized = Memoized::new(Counted(&count, schema.clone()));
+        assert_eq!(memoized.cached_digest(), None);
+
+        for _ in 0..3 {
+            assert_eq!(digest(&vec![&memoized, &memoized]), digest(&vec![&schema, &schema]));
+        }
+
+        assert_eq!(count.get(), 1);
+        assert_eq!(memoized.cached_digest(), Some(&digest(&schema)));
+        assert_eq!(digest(&memoized), digest(&schema));
+        assert_eq!(memoized.1, schema);
+    }
+
+    #[test]
+    fn memoizing_with_profiles() {
+        let count = Cell::new(0);
+        let memoized = Memoized::new(Counted(&count, vec!["caf\u{e9}".to_owned()]));
+        assert_eq!(digest(&memoized), digest(&memoized.1));
+
+        let nfd = HashProfile::default().with_normalization(Normalization::Nfd);
+        let mut hasher = hasher::default().with_profile(nfd);
+        memoized.objecthash(&mut hasher);
+        assert_eq!(count.get(), 2);
+
+        let mut expected = hasher::default().with_profile(nfd);
+        memoized.1.objecthash(&mut expected);
+        assert_eq!(hasher.finish(), expected.finish());
+        assert_eq!(memoized.cached_digest(), Some(&digest(&memoized.1)));
+    }
+
+    #[test]
+    fn memoizing_errors() {
+        let count = Cell::new(0);
+        let memoized = Memoized::new(Counted(&count, vec!["foo".to_owned()]));
+        let reject = HashProfile::default().with_limits(Limits::default().with_max_depth(0));
+
+        let mut hasher = hasher::default().with_profile(reject);
+        memoized.objecthash(&mut hasher);
+        assert!(hasher.try_finish().is_err());
+        assert_eq!(memoized.cached_digest(), None);
+
+        assert_eq!(digest(&memoized), digest(&memoized.1));
+        assert_eq!(memoized.cached_digest(), Some(&digest(&memoized.1)));
+    }
+
+    #[test]
+    fn memoizing_with_limits() {
+        let memoized = vec![Memoized::new(vec!["foo".to_owned()])];
+        let expected = digest(&vec![vec!["foo"]]);
+
+        // The wrapped value counts against the limits as if it were unwrapped
+        let limits = Limits::default().with_max_depth(2).with_max_elements(2);
+        let mut hasher = hasher::default().with_profile(HashProfile::default().with_limits(limits));
+        memoized.objecthash(&mut hasher);
+        assert_eq!(hasher.try_finish().unwrap(), expected);
+
+        let limits = Limits::default().with_max_depth(1);
+        let mut hasher = hasher::default().with_profile(HashProfile::default().with_limits(limits));
+        memoized.objecthash(&mut hasher);
+        match hasher.try_finish() {
+            Err(Error::LimitExceeded(Limit::Depth)) => (),
+            other => panic!("expected a depth limit error, got {:?}", other),
+        }
+
+        // A value before it which raises an error leaves it hashed as usual
+        let strict = HashProfile::default().with_strict_floats(true);
+        let mut hasher = hasher::default().with_profile(strict);
+        hasher.update(LIST_TAG);
+        hasher.update_nested(|h| f64::NAN.objecthash(h));
+        hasher.update_nested(|h| memoized[0].objecthash(h));
+        match hasher.try_finish() {
+            Err(Error::NonFiniteFloat(_)) => (),
+            other => panic!("expected a non-finite float error, got {:?}", other),
+        }
+        assert_eq!(memoized[0].cached_digest(), None);
+
+        assert_eq!(digest(&memoized), expected);
+        assert_eq!(memoized[0].cached_digest(), Some(&digest(&vec!["foo"])));
+    }
+}
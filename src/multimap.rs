@@ -0,0 +1,89 @@
+//! Hashing multimaps, whose keys can appear more than once
+//!
+//! Collecting HTTP headers or query parameters into a `HashMap` keeps one
+//! value per key, silently dropping the rest before they're hashed.
+//! `MultiMap<I>` hashes a sequence of key-value pairs as a dict from each key
+//! to the list of its values, in the order they appear:
+//!
+//! ```text
+//! [("accept", "text/html"), ("host", "example.com"), ("accept", "*/*")]
+//!     => {"accept": ["text/html", "*/*"], "host": ["example.com"]}
+//! ```
+//!
+//! The order of the keys doesn't affect the digest, but the order of each
+//! key's values does, since repeated headers and parameters are usually
+//! meaningful in order. Every value is in a list, even a key's only one, so
+//! the digest doesn't depend on how many times a key appears except through
+//! the values themselves.
+
+use std::collections::HashMap;
+
+use {Digest, ObjectHash, ObjectHasher};
+use types::{dict_member, hash_dict_members, key_digest_with, LIST_TAG};
+
+/// Hashes the key-value pairs produced by the wrapped iterable as a dict
+/// from each distinct key to the list of its values.
+///
+/// Wrap borrowed pairs rather than an owned collection, for the reasons
+/// given on [`Set`](crate::set::Set).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MultiMap<I>(pub I);
+
+impl<I, K, V> ObjectHash for MultiMap<I>
+    where I: IntoIterator<Item = (K, V)> + Clone,
+          K: ObjectHash,
+          V: ObjectHash
+{
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        // Each distinct key's digest, and the hasher of the list of its values
+        let mut keys: Vec<(Digest, H)> = Vec::new();
+        let mut indexes: HashMap<Digest, usize> = HashMap::new();
+
+        for (k, v) in self.0.clone() {
+            let key_digest = key_digest_with(&k, hasher);
+
+            let index = *indexes.entry(key_digest).or_insert_with(|| {
+                let mut values_hasher = hasher.nested();
+                values_hasher.update(LIST_TAG);
+                keys.push((key_digest, values_hasher));
+                keys.len() - 1
+            });
+
+            keys[index].1.update_nested(|h| v.objecthash(h));
+        }
+
+        let mut members: Vec<_> = keys.into_iter()
+            .map(|(key_digest, values_hasher)| dict_member(&key_digest, &values_hasher.finish()))
+            .collect();
+
+        hash_dict_members(&mut members, hasher);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::MultiMap;
+    use digest;
+
+    #[test]
+    fn hashing_multimaps() {
+        let headers = [("accept", "text/html"), ("host", "example.com"), ("accept", "*/*")];
+
+        let mut expected = HashMap::new();
+        expected.insert("accept", vec!["text/html", "*/*"]);
+        expected.insert("host", vec!["example.com"]);
+        assert_eq!(digest(&MultiMap(headers.iter().cloned())), digest(&expected));
+
+        // Keys can come in any order, but each key's values are in order
+        let reordered = [("host", "example.com"), ("accept", "text/html"), ("accept", "*/*")];
+        assert_eq!(digest(&MultiMap(reordered.iter().cloned())), digest(&MultiMap(headers.iter().cloned())));
+
+        let swapped = [("accept", "*/*"), ("host", "example.com"), ("accept", "text/html")];
+        assert!(digest(&MultiMap(swapped.iter().cloned())) != digest(&MultiMap(headers.iter().cloned())));
+
+        assert_eq!(digest(&MultiMap(Vec::<(&str, &str)>::new())), digest(&HashMap::<&str, Vec<&str>>::new()));
+    }
+}
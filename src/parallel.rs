@@ -0,0 +1,108 @@
+//! Hashing large collections in parallel
+//!
+//! The elements of a list, and the members of a dict, are hashed
+//! independently of each other before their digests are combined, so large
+//! collections can hash them on rayon's thread pool. The digests are the same
+//! as those computed sequentially.
+//!
+//! Collections shorter than `MIN_PARALLEL_LEN` are hashed on the current
+//! thread, as that's faster than sharing them out.
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+#[cfg(feature = "objecthash-ring")]
+use hasher;
+use {Digest, ObjectHash, ObjectHasher};
+use types::{dict_member, digest_with, hash_dict_members, key_digest_with, LIST_TAG};
+
+/// Collections with fewer elements than this are hashed sequentially
+pub const MIN_PARALLEL_LEN: usize = 1024;
+
+/// Hash a list, computing its elements' digests in parallel
+pub fn hash_list<T, H>(list: &[T], hasher: &mut H)
+    where T: ObjectHash + Sync,
+          H: ObjectHasher + Sync
+{
+    let digests: Vec<Digest> = if list.len() < MIN_PARALLEL_LEN {
+        list.iter().map(|element| digest_with(element, hasher)).collect()
+    } else {
+        list.par_iter().map(|element| digest_with(element, hasher)).collect()
+    };
+
+    hasher.update(LIST_TAG);
+
+    for digest in &digests {
+        hasher.update(digest.as_ref());
+    }
+}
+
+/// Hash a map as a dict, computing its members' digests in parallel
+pub fn hash_map<K, V, S, H>(map: &HashMap<K, V, S>, hasher: &mut H)
+    where K: ObjectHash + Sync,
+          V: ObjectHash + Sync,
+          H: ObjectHasher + Sync
+{
+    let member = |(k, v): (&K, &V)| dict_member(&key_digest_with(k, hasher), &digest_with(v, hasher));
+
+    // Sharing out the entries by reference, rather than through the map's
+    // own parallel iterator, keeps the serial impl's bounds on the map
+    let mut members: Vec<_> = if map.len() < MIN_PARALLEL_LEN {
+        map.iter().map(member).collect()
+    } else {
+        map.iter().collect::<Vec<_>>().into_par_iter().map(member).collect()
+    };
+
+    hash_dict_members(&mut members, hasher);
+}
+
+/// Compute the digest of a list with the default hasher, hashing its
+/// elements in parallel
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_list<T: ObjectHash + Sync>(list: &[T]) -> Digest {
+    let mut hasher = hasher::default();
+    hash_list(list, &mut hasher);
+    hasher.finish()
+}
+
+/// Compute the digest of a map with the default hasher, hashing its members
+/// in parallel
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_map<K, V, S>(map: &HashMap<K, V, S>) -> Digest
+    where K: ObjectHash + Sync,
+          V: ObjectHash + Sync
+{
+    let mut hasher = hasher::default();
+    hash_map(map, &mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{digest_list, digest_map, MIN_PARALLEL_LEN};
+    use digest;
+
+    #[test]
+    fn lists() {
+        for &len in &[0, 10, MIN_PARALLEL_LEN, 5000] {
+            let list: Vec<String> = (0..len).map(|i| format!("element {}", i)).collect();
+            assert_eq!(digest_list(&list), digest(&list));
+        }
+    }
+
+    #[test]
+    fn maps() {
+        for &len in &[0, 10, MIN_PARALLEL_LEN, 5000] {
+            let map: HashMap<String, usize> = (0..len).map(|i| (format!("key {}", i), i)).collect();
+            assert_eq!(digest_map(&map), digest(&map));
+        }
+
+        // Like the serial impl, keys needn't be `Eq + Hash`
+        let floats: HashMap<f64, f64> = HashMap::new();
+        assert_eq!(digest_map(&floats), digest(&floats));
+    }
+}
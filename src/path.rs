@@ -0,0 +1,206 @@
+//! Paths to values within a nested structure
+//!
+//! A `Path` is a sequence of dict keys and list indexes, built up from the
+//! root or parsed from a JSON pointer (RFC 6901):
+//!
+//! ```
+//! use objecthash::path::Path;
+//!
+//! let path = Path::root().key("user").index(3);
+//! assert_eq!(path.to_string(), "/user/3");
+//! assert_eq!("/user/3".parse::<Path>().unwrap(), path);
+//! ```
+//!
+//! As with JSON pointers, numeric segments select list elements, and also
+//! dict members whose key is the number written in decimal.
+
+use std::fmt;
+use std::str::FromStr;
+
+use Error;
+
+/// A single step of a path
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Segment {
+    /// Member of a dict with the given key
+    Key(String),
+
+    /// Element of a list at the given index
+    Index(usize),
+}
+
+impl Segment {
+    /// Key of the dict member this segment selects
+    pub fn as_key(&self) -> String {
+        match *self {
+            Segment::Key(ref key) => key.clone(),
+            Segment::Index(index) => index.to_string(),
+        }
+    }
+
+    /// Index of the list element this segment selects, if any
+    pub fn as_index(&self) -> Option<usize> {
+        match *self {
+            Segment::Key(_) => None,
+            Segment::Index(index) => Some(index),
+        }
+    }
+}
+
+/// Path from the root of a structure to one of the values within it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    /// The empty path, which refers to the whole structure
+    pub fn root() -> Self {
+        Path::default()
+    }
+
+    /// Extend the path with a dict key
+    pub fn key<K: Into<String>>(mut self, key: K) -> Self {
+        self.segments.push(Segment::Key(key.into()));
+        self
+    }
+
+    /// Extend the path with a list index
+    pub fn index(mut self, index: usize) -> Self {
+        self.segments.push(Segment::Index(index));
+        self
+    }
+
+    /// Extend the path with a segment
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    /// Remove the last segment, returning it
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.segments.pop()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Is this path equal to or a prefix of the other?
+    pub fn contains(&self, other: &Path) -> bool {
+        other.segments.starts_with(&self.segments)
+    }
+
+    /// Parse a JSON pointer (RFC 6901)
+    pub fn parse_pointer(pointer: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidPointer(pointer.to_owned());
+        let mut path = Path::root();
+
+        if pointer.is_empty() {
+            return Ok(path);
+        }
+
+        if !pointer.starts_with('/') {
+            return Err(invalid());
+        }
+
+        for token in pointer[1..].split('/') {
+            if let Some(index) = parse_index(token) {
+                path.segments.push(Segment::Index(index));
+                continue;
+            }
+
+            let mut key = String::with_capacity(token.len());
+            let mut chars = token.chars();
+
+            while let Some(c) = chars.next() {
+                match c {
+                    '~' => {
+                        match chars.next() {
+                            Some('0') => key.push('~'),
+                            Some('1') => key.push('/'),
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    c => key.push(c),
+                }
+            }
+
+            path.segments.push(Segment::Key(key));
+        }
+
+        Ok(path)
+    }
+}
+
+// Array indexes in JSON pointers are decimal without leading zeros
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) ||
+       !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    token.parse().ok()
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(pointer: &str) -> Result<Self, Error> {
+        Path::parse_pointer(pointer)
+    }
+}
+
+/// Formats the path as a JSON pointer
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for segment in &self.segments {
+            f.write_str("/")?;
+
+            match *segment {
+                Segment::Key(ref key) => f.write_str(&key.replace('~', "~0").replace('/', "~1"))?,
+                Segment::Index(index) => write!(f, "{}", index)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Path, Segment};
+    use Error;
+
+    #[test]
+    fn building_paths() {
+        let path = Path::root().key("user").index(3).key("a/b~c");
+        assert_eq!(path.segments(),
+                   &[Segment::Key("user".to_owned()), Segment::Index(3), Segment::Key("a/b~c".to_owned())]);
+        assert_eq!(path.to_string(), "/user/3/a~1b~0c");
+        assert_eq!(Path::root().to_string(), "");
+
+        assert!(Path::root().key("user").contains(&path));
+        assert!(path.contains(&path));
+        assert!(!path.contains(&Path::root().key("user")));
+    }
+
+    #[test]
+    fn parsing_pointers() {
+        assert_eq!("".parse::<Path>().unwrap(), Path::root());
+        assert_eq!("/".parse::<Path>().unwrap(), Path::root().key(""));
+        assert_eq!("/user/3/a~1b~0c".parse::<Path>().unwrap(),
+                   Path::root().key("user").index(3).key("a/b~c"));
+        assert_eq!("/03/-".parse::<Path>().unwrap(), Path::root().key("03").key("-"));
+
+        for pointer in &["user", "/a~2", "/a~"] {
+            match pointer.parse::<Path>() {
+                Err(Error::InvalidPointer(_)) => (),
+                other => panic!("expected an invalid pointer error, got {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Settings which change how values are hashed
+//!
+//! Implementations of ObjectHash disagree on the details: how strings are
+//! normalized (or otherwise canonicalized), how floats are written out,
+//! what non-finite floats and JSON numbers hash as, which tags the
+//! non-standard types get. A `HashProfile` pins down all of them, so each
+//! hasher (and the hashers nested within it) can follow whichever set of
+//! choices its peers agreed on, independently of the others. A profile also
+//! sets the `Limits` for hashing untrusted input.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ptr;
+
+use {ExtensionTags, Normalization};
+
+/// How JSON numbers are mapped onto ObjectHash types
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumberMode {
+    /// Integers use the integer tag, and all other numbers the float tag
+    #[default]
+    Native,
+
+    /// All numbers are hashed as floats, for compatibility with digests of
+    /// JSON documents computed by the reference implementations
+    CommonJson,
+}
+
+/// Rules for rewriting strings into a canonical form before they're hashed,
+/// such as case folding or trimming whitespace, agreed with a peer. Every
+/// string is canonicalized, including dict keys, and then normalized as the
+/// profile says.
+///
+/// Functions from `&str` to `Cow<str>` are canonicalizers. Profiles are
+/// `Copy`, so they only refer to a canonicalizer, which has to be `'static`:
+///
+/// ```rust
+/// use std::borrow::Cow;
+/// use objecthash::HashProfile;
+///
+/// fn trim(s: &str) -> Cow<'_, str> {
+///     Cow::Borrowed(s.trim())
+/// }
+///
+/// let profile = HashProfile::default().with_canonicalizer(&trim);
+/// ```
+pub trait Canonicalizer: Send + Sync {
+    /// Rewrite a string into its canonical form, borrowing it if it's
+    /// already canonical
+    fn canonicalize<'a>(&self, s: &'a str) -> Cow<'a, str>;
+}
+
+impl<F> Canonicalizer for F
+    where F: Fn(&str) -> Cow<'_, str> + Send + Sync
+{
+    #[inline]
+    fn canonicalize<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        self(s)
+    }
+}
+
+// Profiles are compared by which canonicalizer they refer to, since there's
+// no telling whether two canonicalizers agree
+#[derive(Clone, Copy)]
+struct CanonicalizerRef(&'static dyn Canonicalizer);
+
+impl PartialEq for CanonicalizerRef {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::addr_eq(self.0, other.0)
+    }
+}
+
+impl Eq for CanonicalizerRef {}
+
+impl fmt::Debug for CanonicalizerRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Canonicalizer({:p})", self.0)
+    }
+}
+
+/// How floats are written out before they're hashed with the float tag
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// The reference implementation's normalization: a sign, a binary
+    /// exponent and the bits of the mantissa in [0.5, 1)
+    #[default]
+    Normalized,
+
+    /// The shortest decimal string which round-trips, formatted the way
+    /// ECMAScript formats numbers (`1.5`, `1e+21`), for peers which hash
+    /// floats as decimal text
+    Decimal,
+}
+
+/// Which types of dict keys are accepted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyPolicy {
+    /// Keys of any type, hashed as whatever they are. JSON and most other
+    /// implementations only have string keys, so dicts with other keys may
+    /// not hash the same (or at all) elsewhere
+    #[default]
+    Any,
+
+    /// Only string keys. Maps with keys of other types can be hashed with
+    /// their keys converted to strings through `keys::StringKeys`
+    Strings,
+}
+
+/// Limits on the size of the values a hasher accepts, for hashing untrusted
+/// input. Nothing is limited by default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    max_depth: Option<usize>,
+    max_elements: Option<u64>,
+    max_len: Option<usize>,
+}
+
+impl Limits {
+    /// How deeply values can be nested. The elements and members of the
+    /// value being hashed are at depth 1, their elements at depth 2, etc.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// How many values can be nested within the value being hashed, counting
+    /// each list element, dict key and dict value
+    pub fn max_elements(&self) -> Option<u64> {
+        self.max_elements
+    }
+
+    /// How long strings and octet strings can be, in bytes
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub fn with_max_elements(mut self, elements: u64) -> Self {
+        self.max_elements = Some(elements);
+        self
+    }
+
+    pub fn with_max_len(mut self, len: usize) -> Self {
+        self.max_len = Some(len);
+        self
+    }
+}
+
+/// Which of the `Limits` a value exceeded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Depth,
+    Elements,
+    Length,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Limit::Depth => "nesting depth",
+            Limit::Elements => "element count",
+            Limit::Length => "length",
+        })
+    }
+}
+
+/// Settings for hashing values, built up from the default with the `with_*`
+/// methods:
+///
+/// ```
+/// use objecthash::{HashProfile, Normalization, NumberMode};
+///
+/// let profile = HashProfile::default()
+///     .with_normalization(Normalization::Raw)
+///     .with_json_numbers(NumberMode::CommonJson);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HashProfile {
+    normalization: Normalization,
+    strict_floats: bool,
+    extension_tags: ExtensionTags,
+    json_numbers: NumberMode,
+    require_salts: bool,
+    reject_duplicate_keys: bool,
+    limits: Limits,
+    key_policy: KeyPolicy,
+    exact_numbers: bool,
+    reject_poisoned: bool,
+    detect_cycles: bool,
+    float_format: FloatFormat,
+    canonicalizer: Option<CanonicalizerRef>,
+}
+
+impl HashProfile {
+    /// Hash only what the ObjectHash spec defines, without extension types
+    pub fn spec() -> Self {
+        HashProfile::default().with_extension_tags(ExtensionTags::none())
+    }
+
+    /// Hash JSON the way the reference implementations' common JSON mode
+    /// does, with every number as a float
+    pub fn common_json() -> Self {
+        HashProfile::default().with_json_numbers(NumberMode::CommonJson)
+    }
+
+    /// Hash floats as their shortest round-tripping decimal strings
+    pub fn decimal_floats() -> Self {
+        HashProfile::default().with_float_format(FloatFormat::Decimal)
+    }
+
+    /// How strings are normalized before they're hashed. ObjectHash
+    /// specifies NFC, but some implementations hash strings as they are
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Rules for rewriting strings before they're normalized, if any
+    pub fn canonicalizer(&self) -> Option<&'static dyn Canonicalizer> {
+        self.canonicalizer.map(|c| c.0)
+    }
+
+    /// Should NaN and infinite floats be rejected, rather than hashed as the
+    /// reference implementation's strings for them?
+    pub fn strict_floats(&self) -> bool {
+        self.strict_floats
+    }
+
+    /// How floats are written out before they're hashed
+    pub fn float_format(&self) -> FloatFormat {
+        self.float_format
+    }
+
+    /// Tags for the non-standard types this crate can hash
+    pub fn extension_tags(&self) -> ExtensionTags {
+        self.extension_tags
+    }
+
+    /// How JSON numbers are hashed, unless a wrapper like `CommonJson` or
+    /// `RedactedJson` says otherwise
+    pub fn json_numbers(&self) -> NumberMode {
+        self.json_numbers
+    }
+
+    /// Should the leaves of salted JSON documents without a salt be
+    /// rejected, rather than hashed as they are?
+    pub fn require_salts(&self) -> bool {
+        self.require_salts
+    }
+
+    /// Should dicts with two keys that hash the same be rejected? Keys
+    /// which are distinct before they're normalized (or in types whose maps
+    /// allow duplicates) otherwise hash as separate members, which other
+    /// parsers of the same input might not agree with
+    pub fn reject_duplicate_keys(&self) -> bool {
+        self.reject_duplicate_keys
+    }
+
+    /// Limits on the size of the values being hashed. Only the hashers in
+    /// this crate enforce the depth and element limits
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Which types of dict keys are accepted. The hashers in this crate
+    /// record an error for keys of other types
+    pub fn key_policy(&self) -> KeyPolicy {
+        self.key_policy
+    }
+
+    /// Should JSON numbers which would be rounded to be hashed as floats be
+    /// rejected? They can only be told apart from the floats they round to
+    /// with serde_json's arbitrary precision, except for large integers in
+    /// common-JSON mode
+    pub fn exact_numbers(&self) -> bool {
+        self.exact_numbers
+    }
+
+    /// Should values behind a poisoned `Mutex` or `RwLock` be rejected?
+    /// They're otherwise hashed as they were left by the thread which
+    /// panicked while holding the lock
+    pub fn reject_poisoned(&self) -> bool {
+        self.reject_poisoned
+    }
+
+    /// Should `Rc`s and `Arc`s which refer back to a value containing them
+    /// be rejected, rather than followed? Hashing a cycle without detecting
+    /// it never finishes
+    pub fn detect_cycles(&self) -> bool {
+        self.detect_cycles
+    }
+
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn with_canonicalizer(mut self, canonicalizer: &'static dyn Canonicalizer) -> Self {
+        self.canonicalizer = Some(CanonicalizerRef(canonicalizer));
+        self
+    }
+
+    pub fn with_strict_floats(mut self, strict: bool) -> Self {
+        self.strict_floats = strict;
+        self
+    }
+
+    pub fn with_float_format(mut self, format: FloatFormat) -> Self {
+        self.float_format = format;
+        self
+    }
+
+    pub fn with_extension_tags(mut self, tags: ExtensionTags) -> Self {
+        self.extension_tags = tags;
+        self
+    }
+
+    pub fn with_json_numbers(mut self, mode: NumberMode) -> Self {
+        self.json_numbers = mode;
+        self
+    }
+
+    pub fn with_require_salts(mut self, require: bool) -> Self {
+        self.require_salts = require;
+        self
+    }
+
+    pub fn with_reject_duplicate_keys(mut self, reject: bool) -> Self {
+        self.reject_duplicate_keys = reject;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_key_policy(mut self, policy: KeyPolicy) -> Self {
+        self.key_policy = policy;
+        self
+    }
+
+    pub fn with_exact_numbers(mut self, exact: bool) -> Self {
+        self.exact_numbers = exact;
+        self
+    }
+
+    pub fn with_reject_poisoned(mut self, reject: bool) -> Self {
+        self.reject_poisoned = reject;
+        self
+    }
+
+    pub fn with_detect_cycles(mut self, detect: bool) -> Self {
+        self.detect_cycles = detect;
+        self
+    }
+
+    /// Can hashing a value with this profile fail?
+    pub(crate) fn rejects_values(&self) -> bool {
+        self.strict_floats || self.require_salts || self.reject_duplicate_keys ||
+        self.extension_tags != ExtensionTags::default() || self.limits != Limits::default() ||
+        self.key_policy != KeyPolicy::Any || self.exact_numbers || self.reject_poisoned ||
+        self.detect_cycles
+    }
+}
@@ -0,0 +1,433 @@
+//! Inclusion proofs
+//!
+//! A list's digest is the digest of its elements' digests in order, so an
+//! element can be shown to occur at a given index of a list with a known
+//! digest by revealing the digests of the other elements, without revealing
+//! their contents. Likewise a dict's digest is the digest of its sorted
+//! entries' key and value digests, so an entry can be proven by revealing the
+//! digests of the other entries.
+//!
+//! Proofs for each level of a nested structure chain together into a
+//! `PathProof`, which proves the value at a path within it.
+
+#[cfg(all(feature = "objecthash-ring", feature = "json"))]
+use serde_json::Value;
+
+#[cfg(feature = "objecthash-ring")]
+use hasher;
+use redaction::Redactable;
+#[cfg(all(feature = "objecthash-ring", feature = "json"))]
+use Error;
+use {Digest, ObjectHash, ObjectHasher};
+use path::{Path, Segment};
+use types::{dict_member, digest_with, hash_dict_members, key_digest_with, LIST_TAG};
+
+/// Proof that an element occurs at a given index of a list
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListProof {
+    /// Index of the element within the list
+    pub index: usize,
+
+    /// Digests of the elements before it
+    pub before: Vec<Digest>,
+
+    /// Digests of the elements after it
+    pub after: Vec<Digest>,
+}
+
+impl ListProof {
+    /// Prove the element at the given index of a list, or return `None` if
+    /// the index is out of bounds
+    #[cfg(feature = "objecthash-ring")]
+    pub fn new<T: ObjectHash>(list: &[T], index: usize) -> Option<Self> {
+        Self::new_with(list, index, &hasher::default())
+    }
+
+    /// Prove the element at the given index of a list, using hashers nested
+    /// within the given one for the digests of the other elements
+    pub fn new_with<T, H>(list: &[T], index: usize, hasher: &H) -> Option<Self>
+        where T: ObjectHash,
+              H: ObjectHasher
+    {
+        if index >= list.len() {
+            return None;
+        }
+
+        let digests = |elements: &[T]| elements.iter().map(|element| digest_with(element, hasher)).collect();
+
+        Some(ListProof {
+            index,
+            before: digests(&list[..index]),
+            after: digests(&list[index + 1..]),
+        })
+    }
+
+    /// Length of the list the proof is for
+    pub fn list_len(&self) -> usize {
+        self.before.len() + 1 + self.after.len()
+    }
+
+    /// Verify that the given element occurs at the proof's index of the list
+    /// with the given digest
+    #[cfg(feature = "objecthash-ring")]
+    pub fn verify<T: ObjectHash + ?Sized>(&self, element: &T, root: &Digest) -> bool {
+        self.verify_with(element, root, hasher::default())
+    }
+
+    /// Verify that the given element occurs at the proof's index of the list
+    /// with the given digest, hashing the list with the given hasher
+    pub fn verify_with<T, H>(&self, element: &T, root: &Digest, mut hasher: H) -> bool
+        where T: ObjectHash + ?Sized,
+              H: ObjectHasher
+    {
+        if self.index != self.before.len() {
+            return false;
+        }
+
+        Included(self, element).objecthash(&mut hasher);
+        hasher.finish() == *root
+    }
+
+    /// Digest of the list the proof is for, given the element's digest
+    #[cfg(feature = "objecthash-ring")]
+    pub fn root(&self, element: &Digest) -> Digest {
+        self.root_with(element, hasher::default())
+    }
+
+    /// Digest of the list the proof is for, given the element's digest,
+    /// hashing the list with the given hasher
+    pub fn root_with<H: ObjectHasher>(&self, element: &Digest, mut hasher: H) -> Digest {
+        Included(self, &Redactable::Redacted::<()>(*element)).objecthash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Proof that a key maps to a value within a dict
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DictProof {
+    /// Digests of the keys and values of the other entries, sorted
+    pub others: Vec<(Digest, Digest)>,
+}
+
+impl DictProof {
+    /// Prove the entry with the given key in a dict, or return `None` if
+    /// there's no such entry
+    #[cfg(feature = "objecthash-ring")]
+    pub fn new<K, V, I>(entries: I, key: &K) -> Option<Self>
+        where K: ObjectHash + PartialEq,
+              V: ObjectHash,
+              I: IntoIterator<Item = (K, V)>
+    {
+        Self::new_with(entries, key, &hasher::default())
+    }
+
+    /// Prove the entry with the given key in a dict, using hashers nested
+    /// within the given one for the digests of the other entries
+    pub fn new_with<K, V, I, H>(entries: I, key: &K, hasher: &H) -> Option<Self>
+        where K: ObjectHash + PartialEq,
+              V: ObjectHash,
+              I: IntoIterator<Item = (K, V)>,
+              H: ObjectHasher
+    {
+        let mut found = false;
+        let mut others = Vec::new();
+
+        for (k, v) in entries {
+            if k == *key {
+                found = true;
+            } else {
+                others.push((digest_with(&k, hasher), digest_with(&v, hasher)));
+            }
+        }
+
+        if !found {
+            return None;
+        }
+
+        others.sort_by(|a, b| (a.0.as_ref(), a.1.as_ref()).cmp(&(b.0.as_ref(), b.1.as_ref())));
+        Some(DictProof { others })
+    }
+
+    /// Verify that the given key maps to the given value in the dict with the
+    /// given digest
+    #[cfg(feature = "objecthash-ring")]
+    pub fn verify<K, V>(&self, key: &K, value: &V, root: &Digest) -> bool
+        where K: ObjectHash + ?Sized,
+              V: ObjectHash + ?Sized
+    {
+        self.root(&::digest(key), &::digest(value)) == Some(*root)
+    }
+
+    /// Verify that the given key maps to the given value in the dict with the
+    /// given digest, hashing the dict with the given hasher
+    pub fn verify_with<K, V, H>(&self, key: &K, value: &V, root: &Digest, hasher: H) -> bool
+        where K: ObjectHash + ?Sized,
+              V: ObjectHash + ?Sized,
+              H: ObjectHasher
+    {
+        let key = key_digest_with(key, &hasher);
+        let value = digest_with(value, &hasher);
+        self.root_with(&key, &value, hasher) == Some(*root)
+    }
+
+    /// Digest of the dict the proof is for, given the digests of the proven
+    /// entry's key and value, or `None` if one of the other entries has the
+    /// same key: a dict with the key twice could show it mapping to either
+    /// value
+    pub fn root_with<H: ObjectHasher>(&self, key: &Digest, value: &Digest, mut hasher: H) -> Option<Digest> {
+        if self.others.iter().any(|(k, _)| k == key) {
+            return None;
+        }
+
+        let mut members: Vec<_> = self.others
+            .iter()
+            .chain(Some((*key, *value)).iter())
+            .map(|(k, v)| dict_member(k, v))
+            .collect();
+
+        hash_dict_members(&mut members, &mut hasher);
+        Some(hasher.finish())
+    }
+
+    #[cfg(feature = "objecthash-ring")]
+    fn root(&self, key: &Digest, value: &Digest) -> Option<Digest> {
+        self.root_with(key, value, hasher::default())
+    }
+}
+
+/// Proof for one level of a nested structure
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    List(ListProof),
+    Dict(DictProof),
+}
+
+/// Proof of the value at a path within a nested structure, with one step for
+/// each segment of the path, starting from the root
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathProof {
+    pub path: Path,
+    pub steps: Vec<Step>,
+}
+
+impl PathProof {
+    /// Prove the value at the given path within a JSON document
+    #[cfg(all(feature = "objecthash-ring", feature = "json"))]
+    pub fn for_json(value: &Value, path: &Path) -> Result<Self, Error> {
+        let not_found = || Error::InvalidPointer(path.to_string());
+        let mut steps = Vec::with_capacity(path.segments().len());
+        let mut current = value;
+
+        for segment in path.segments() {
+            current = match *current {
+                Value::Array(ref a) => {
+                    let index = segment.as_index().ok_or_else(not_found)?;
+                    steps.push(Step::List(ListProof::new(a, index).ok_or_else(not_found)?));
+                    &a[index]
+                }
+                Value::Object(ref o) => {
+                    let key = segment.as_key();
+                    steps.push(Step::Dict(DictProof::new(o.iter(), &&key).ok_or_else(not_found)?));
+                    &o[&key]
+                }
+                _ => return Err(not_found()),
+            };
+        }
+
+        Ok(PathProof {
+            path: path.clone(),
+            steps,
+        })
+    }
+
+    /// Verify that the given value is at the proof's path within the
+    /// structure with the given digest
+    #[cfg(feature = "objecthash-ring")]
+    pub fn verify<T: ObjectHash + ?Sized>(&self, value: &T, root: &Digest) -> bool {
+        self.verify_with(value, root, hasher::default())
+    }
+
+    /// Verify that the given value is at the proof's path within the
+    /// structure with the given digest, hashing its root with the given
+    /// hasher and each level below it with one nested within the last
+    pub fn verify_with<T, H>(&self, value: &T, root: &Digest, hasher: H) -> bool
+        where T: ObjectHash + ?Sized,
+              H: ObjectHasher
+    {
+        let segments = self.path.segments();
+
+        if segments.len() != self.steps.len() {
+            return false;
+        }
+
+        let mut hashers = vec![hasher];
+        for _ in 0..segments.len() {
+            let nested = hashers.last().unwrap().nested();
+            hashers.push(nested);
+        }
+
+        let mut value_hasher = hashers.pop().unwrap();
+        value.objecthash(&mut value_hasher);
+        let mut digest = value_hasher.finish();
+
+        for ((segment, step), hasher) in segments.iter().zip(&self.steps).rev().zip(hashers.into_iter().rev()) {
+            digest = match (segment, step) {
+                (Segment::Index(index), Step::List(proof)) if proof.index == *index &&
+                                                              *index == proof.before.len() => {
+                    proof.root_with(&digest, hasher)
+                }
+                (segment, Step::Dict(proof)) => {
+                    let key = key_digest_with(&segment.as_key(), &hasher);
+                    match proof.root_with(&key, &digest, hasher) {
+                        Some(digest) => digest,
+                        None => return false,
+                    }
+                }
+                _ => return false,
+            };
+        }
+
+        digest == *root
+    }
+}
+
+/// Hashes as the list a proof is for, given the proven element
+struct Included<'a, T: 'a + ?Sized>(&'a ListProof, &'a T);
+
+impl<'a, T: ObjectHash + ?Sized> ObjectHash for Included<'a, T> {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let Included(proof, element) = *self;
+        hasher.update(LIST_TAG);
+
+        for digest in &proof.before {
+            hasher.update(digest.as_ref());
+        }
+
+        hasher.update_nested(|h| element.objecthash(h));
+
+        for digest in &proof.after {
+            hasher.update(digest.as_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{DictProof, ListProof};
+    use {digest, hasher, FloatFormat, HashProfile, ObjectHash, ObjectHasher};
+    use types::{dict_member, hash_dict_members};
+
+    #[test]
+    fn list_inclusion() {
+        let batch: Vec<String> = (0..10).map(|i| format!("entry {}", i)).collect();
+        let root = digest(&batch);
+
+        for (i, element) in batch.iter().enumerate() {
+            let proof = ListProof::new(&batch, i).unwrap();
+            assert_eq!(proof.list_len(), 10);
+            assert_eq!(proof.before.len(), i);
+            assert!(proof.verify(element, &root));
+        }
+
+        let proof = ListProof::new(&batch, 3).unwrap();
+        assert!(!proof.verify("entry 4", &root));
+        assert!(!proof.verify(&batch[3], &digest(&batch[..9].to_vec())));
+
+        let mut moved = proof.clone();
+        moved.index = 4;
+        assert!(!moved.verify(&batch[3], &root));
+
+        assert_eq!(ListProof::new(&batch, 10), None);
+    }
+
+    #[test]
+    fn list_inclusion_with_profile() {
+        let profile = HashProfile::default().with_float_format(FloatFormat::Decimal);
+        let readings = vec![1.5, 2.25, 0.1];
+        let mut hasher = hasher::default().with_profile(profile);
+        readings.objecthash(&mut hasher);
+        let root = hasher.finish();
+
+        let proof = ListProof::new_with(&readings, 2, &hasher::default().with_profile(profile)).unwrap();
+        assert!(proof.verify_with(&0.1, &root, hasher::default().with_profile(profile)));
+        assert!(!proof.verify(&0.1, &root));
+
+        let mut element = hasher::default().with_profile(profile);
+        0.1.objecthash(&mut element);
+        assert_eq!(proof.root_with(&element.finish(), hasher::default().with_profile(profile)), root);
+    }
+
+    #[test]
+    fn dict_inclusion() {
+        let mut record = HashMap::new();
+        for (k, v) in &[("name", "Alice"), ("email", "alice@example.com"), ("dob", "1990-01-01")] {
+            record.insert((*k).to_owned(), (*v).to_owned());
+        }
+        let root = digest(&record);
+
+        let proof = DictProof::new(&record, &&"email".to_owned()).unwrap();
+        assert_eq!(proof.others.len(), 2);
+        assert!(proof.verify("email", "alice@example.com", &root));
+        assert!(!proof.verify("email", "bob@example.com", &root));
+        assert!(!proof.verify("name", "alice@example.com", &root));
+
+        // A proof which lists the proven key among the others is for a dict
+        // with the key twice, whichever value it's verified with
+        let others = vec![(digest("email"), digest("bob@example.com"))];
+        let mut members = vec![dict_member(&digest("email"), &digest("alice@example.com")),
+                               dict_member(&others[0].0, &others[0].1)];
+        let mut hasher = hasher::default();
+        hash_dict_members(&mut members, &mut hasher);
+        let doubled = hasher.finish();
+
+        let forged = DictProof { others };
+        assert!(!forged.verify("email", "alice@example.com", &doubled));
+        assert!(!forged.verify("email", "bob@example.com", &doubled));
+        assert_eq!(forged.root_with(&digest("email"), &digest("alice@example.com"), hasher::default()), None);
+
+        assert_eq!(DictProof::new(&record, &&"phone".to_owned()), None);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn path_inclusion() {
+        use serde_json::{self, Value};
+        use path::Path;
+        use super::PathProof;
+
+        let value: Value = serde_json::from_str(r#"{"users": [{"name": "Alice"}, {"name": "Bob", "id": 2}], "n": 1.5}"#)
+            .unwrap();
+        let root = digest(&value);
+
+        let path = Path::root().key("users").index(1).key("name");
+        let proof = PathProof::for_json(&value, &path).unwrap();
+        assert_eq!(proof.steps.len(), 3);
+        assert!(proof.verify("Bob", &root));
+        assert!(!proof.verify("Alice", &root));
+
+        let mut moved = proof.clone();
+        moved.path = Path::root().key("users").index(0).key("name");
+        assert!(!moved.verify("Bob", &root));
+
+        let proof = PathProof::for_json(&value, &Path::root()).unwrap();
+        assert!(proof.verify(&value, &root));
+
+        assert!(PathProof::for_json(&value, &Path::root().key("users").key("name")).is_err());
+        assert!(PathProof::for_json(&value, &Path::root().key("n").index(0)).is_err());
+
+        // The steps' proofs only hold digests, so a proof made with the
+        // default profile verifies under another if the other entries'
+        // digests don't change, and only with a hasher using that profile
+        let profile = HashProfile::default().with_float_format(FloatFormat::Decimal);
+        let mut hasher = hasher::default().with_profile(profile);
+        value.objecthash(&mut hasher);
+        let root = hasher.finish();
+
+        let proof = PathProof::for_json(&value, &Path::root().key("n")).unwrap();
+        assert!(proof.verify_with(&1.5, &root, hasher::default().with_profile(profile)));
+        assert!(!proof.verify(&1.5, &root));
+    }
+}
@@ -0,0 +1,232 @@
+//! ObjectHash support for Protocol Buffers messages via `prost-reflect`
+//!
+//! Messages hash as dicts keyed by field name, so the digest doesn't depend
+//! on field numbers or on the order fields appear on the wire. Only fields
+//! which are present contribute to the digest: proto3 fields without explicit
+//! presence are omitted when they hold their default value, while fields with
+//! explicit presence (proto2 `optional`, proto3 `optional`, oneof members and
+//! messages) are included whenever they are set, even to their default.
+//!
+//! Values map onto ObjectHash types as follows:
+//!
+//! * integers of every width hash as integers, and enums as their number
+//! * `float` and `double` hash as floats
+//! * `bytes` hash as octet strings
+//! * repeated fields hash as lists, in order
+//! * map fields hash as dicts, whatever the type of their keys
+//! * extensions are keyed by their fully qualified name in brackets, the way
+//!   the protobuf JSON mapping names them
+//!
+//! Unknown fields are not part of the digest.
+
+use prost_reflect::{DynamicMessage, MapKey, ReflectMessage, Value};
+
+#[cfg(feature = "objecthash-ring")]
+use {hasher, Digest};
+use {ObjectHash, ObjectHasher};
+use types::{hash_dict, hash_dict_members, hash_member, LIST_TAG};
+
+/// Any message type generated with reflection support, hashed through its
+/// dynamic representation
+pub struct Reflected<'a, M: 'a>(pub &'a M);
+
+impl<'a, M: ReflectMessage> ObjectHash for Reflected<'a, M> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.0.transcode_to_dynamic().objecthash(hasher);
+    }
+}
+
+/// Compute the ObjectHash digest of a message type generated with reflection
+/// support
+#[cfg(feature = "objecthash-ring")]
+pub fn digest<M: ReflectMessage>(message: &M) -> Digest {
+    let mut hasher = hasher::default();
+    Reflected(message).objecthash(&mut hasher);
+    hasher.finish()
+}
+
+impl ObjectHash for DynamicMessage {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let mut members: Vec<_> = self.fields()
+            .map(|(field, value)| hash_member(field.name(), value, hasher))
+            .chain(self.extensions()
+                .map(|(ext, value)| hash_member(&format!("[{}]", ext.full_name()), value, hasher)))
+            .collect();
+
+        hash_dict_members(&mut members, hasher);
+    }
+}
+
+impl ObjectHash for Value {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            Value::Bool(b) => b.objecthash(hasher),
+            Value::I32(i) | Value::EnumNumber(i) => i.objecthash(hasher),
+            Value::I64(i) => i.objecthash(hasher),
+            Value::U32(u) => u.objecthash(hasher),
+            Value::U64(u) => u.objecthash(hasher),
+            Value::F32(f) => f.objecthash(hasher),
+            Value::F64(f) => f.objecthash(hasher),
+            Value::String(ref s) => s.objecthash(hasher),
+            Value::Bytes(ref b) => (**b).objecthash(hasher),
+            Value::Message(ref m) => m.objecthash(hasher),
+            Value::List(ref l) => {
+                hasher.update(LIST_TAG);
+
+                for v in l {
+                    let mut nested_hasher = hasher.nested();
+                    v.objecthash(&mut nested_hasher);
+                    hasher.update(nested_hasher.finish().as_ref());
+                }
+            }
+            Value::Map(ref m) => hash_dict(m.iter(), hasher),
+        }
+    }
+}
+
+impl ObjectHash for MapKey {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            MapKey::Bool(b) => b.objecthash(hasher),
+            MapKey::I32(i) => i.objecthash(hasher),
+            MapKey::I64(i) => i.objecthash(hasher),
+            MapKey::U32(u) => u.objecthash(hasher),
+            MapKey::U64(u) => u.objecthash(hasher),
+            MapKey::String(ref s) => s.objecthash(hasher),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use prost_reflect::{DescriptorPool, DynamicMessage, MapKey, MessageDescriptor, Value};
+    use prost_reflect::prost_types::{DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto,
+                                     FieldDescriptorProto, FileDescriptorProto, MessageOptions};
+    use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+    use rustc_serialize::hex::ToHex;
+
+    use {hasher, ObjectHash, ObjectHasher};
+
+    fn field(name: &str, number: i32, label: Label, ty: Type) -> FieldDescriptorProto {
+        let mut field = FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            number: Some(number),
+            ..Default::default()
+        };
+
+        field.set_label(label);
+        field.set_type(ty);
+        field
+    }
+
+    // message Person {
+    //   string name = 1;
+    //   int64 id = 2;
+    //   repeated string tags = 3;
+    //   bytes photo = 4;
+    //   map<string, int32> attrs = 5;
+    //   Kind kind = 6;
+    // }
+    fn person() -> MessageDescriptor {
+        let mut attrs = field("attrs", 5, Label::Repeated, Type::Message);
+        attrs.type_name = Some(".test.Person.AttrsEntry".to_owned());
+
+        let mut kind = field("kind", 6, Label::Optional, Type::Enum);
+        kind.type_name = Some(".test.Kind".to_owned());
+
+        let entry = DescriptorProto {
+            name: Some("AttrsEntry".to_owned()),
+            field: vec![field("key", 1, Label::Optional, Type::String),
+                        field("value", 2, Label::Optional, Type::Int32)],
+            options: Some(MessageOptions { map_entry: Some(true), ..Default::default() }),
+            ..Default::default()
+        };
+
+        let values = ["UNKNOWN", "ADMIN", "USER"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                EnumValueDescriptorProto {
+                    name: Some((*name).to_owned()),
+                    number: Some(i as i32),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let file = FileDescriptorProto {
+            name: Some("test.proto".to_owned()),
+            package: Some("test".to_owned()),
+            syntax: Some("proto3".to_owned()),
+            message_type: vec![DescriptorProto {
+                                   name: Some("Person".to_owned()),
+                                   field: vec![field("name", 1, Label::Optional, Type::String),
+                                               field("id", 2, Label::Optional, Type::Int64),
+                                               field("tags", 3, Label::Repeated, Type::String),
+                                               field("photo", 4, Label::Optional, Type::Bytes),
+                                               attrs,
+                                               kind],
+                                   nested_type: vec![entry],
+                                   ..Default::default()
+                               }],
+            enum_type: vec![EnumDescriptorProto {
+                                name: Some("Kind".to_owned()),
+                                value: values,
+                                ..Default::default()
+                            }],
+            ..Default::default()
+        };
+
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_proto(file).unwrap();
+        pool.get_message_by_name("test.Person").unwrap()
+    }
+
+    fn hex<T: ObjectHash>(value: &T) -> String {
+        let mut hasher = hasher::default();
+        value.objecthash(&mut hasher);
+        hasher.finish().as_ref().to_hex()
+    }
+
+    #[test]
+    fn messages() {
+        let mut attrs = HashMap::new();
+        attrs.insert(MapKey::String("x".to_owned()), Value::I32(1));
+
+        let mut message = DynamicMessage::new(person());
+        message.set_field_by_name("attrs", Value::Map(attrs));
+        message.set_field_by_name("photo", Value::Bytes(vec![1u8, 2].into()));
+        message.set_field_by_name("tags",
+                                  Value::List(vec![Value::String("a".to_owned()),
+                                                   Value::String("b".to_owned())]));
+        message.set_field_by_name("id", Value::I64(42));
+        message.set_field_by_name("name", Value::String("Alice".to_owned()));
+
+        // {"name": "Alice", "id": 42, "tags": ["a", "b"], "photo": b"\x01\x02", "attrs": {"x": 1}}
+        assert_eq!(hex(&message),
+                   "fcc6dcb39dc33ff5b0fb3f19eea8a71b4d6d78c5d71c3e86588bf8c3cab172bd");
+    }
+
+    #[test]
+    fn defaults() {
+        let mut message = DynamicMessage::new(person());
+        message.set_field_by_name("name", Value::String("Bob".to_owned()));
+        message.set_field_by_name("id", Value::I64(0));
+        message.set_field_by_name("tags", Value::List(vec![]));
+        message.set_field_by_name("kind", Value::EnumNumber(0));
+
+        // {"name": "Bob"}
+        assert_eq!(hex(&message),
+                   "ab5c73d29d1b16293c2d72c375c21c1e0b449ef8f0c857d625517ebb504c7b3c");
+
+        // {"name": "Bob", "kind": 2}
+        message.set_field_by_name("kind", Value::EnumNumber(2));
+        assert_eq!(hex(&message),
+                   "7f3cd8659fe3d9eb106e2fa3cfb19984f379e553beedf36d516627e3aa95d11e");
+    }
+}
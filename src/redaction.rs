@@ -0,0 +1,195 @@
+//! Redaction: replacing part of a structure with its digest
+//!
+//! ObjectHash digests of collections only depend on the digests of their
+//! contents, so any value can be swapped for its digest without changing the
+//! digest of the structure containing it. This allows sharing documents with
+//! some parts censored, whose root digest still verifies.
+//!
+//! Redacting a low-entropy value (a boolean, a date of birth) on its own
+//! doesn't hide much, since its digest can be found by hashing every
+//! candidate. `Salted<T>` guards against this by hashing the value together
+//! with a random salt, which is only disclosed alongside the cleartext.
+
+#[cfg(feature = "objecthash-ring")]
+use Error;
+use {hex, Digest, ObjectHash, ObjectHasher};
+use types::LIST_TAG;
+
+/// Length of the salts used by `Salted<T>`, in bytes
+pub const SALT_LEN: usize = 32;
+
+/// Random salt for a single value
+pub type Salt = [u8; SALT_LEN];
+
+/// A value which is either present in cleartext, or redacted down to its digest.
+/// Both forms make the same contribution to the digest of their parent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Redactable<T> {
+    /// The value itself
+    Clear(T),
+
+    /// The digest of a value which has been redacted
+    Redacted(Digest),
+}
+
+impl<T> Redactable<T> {
+    /// Is the value redacted?
+    pub fn is_redacted(&self) -> bool {
+        match *self {
+            Redactable::Clear(_) => false,
+            Redactable::Redacted(_) => true,
+        }
+    }
+
+    /// Get the value, if it hasn't been redacted
+    pub fn clear(&self) -> Option<&T> {
+        match *self {
+            Redactable::Clear(ref value) => Some(value),
+            Redactable::Redacted(_) => None,
+        }
+    }
+}
+
+impl<T: ObjectHash> Redactable<T> {
+    /// Redact the value using the given hasher, returning its digest.
+    /// Redacting a value which is already redacted leaves it as it is
+    pub fn redact_with<H: ObjectHasher>(&mut self, mut hasher: H) -> Digest {
+        let digest = match *self {
+            Redactable::Clear(ref value) => {
+                value.objecthash(&mut hasher);
+                hasher.finish()
+            }
+            Redactable::Redacted(digest) => return digest,
+        };
+
+        *self = Redactable::Redacted(digest);
+        digest
+    }
+
+    /// Redact the value using the default hasher, returning its digest
+    #[cfg(feature = "objecthash-ring")]
+    pub fn redact(&mut self) -> Digest {
+        self.redact_with(::hasher::default())
+    }
+}
+
+impl<T> From<T> for Redactable<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Redactable::Clear(value)
+    }
+}
+
+impl<T: ObjectHash> ObjectHash for Redactable<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            Redactable::Clear(ref value) => value.objecthash(hasher),
+            Redactable::Redacted(ref digest) => hasher.redact(digest),
+        }
+    }
+}
+
+/// Generate a new random salt
+#[cfg(feature = "objecthash-ring")]
+pub fn random_salt() -> Result<Salt, Error> {
+    let mut salt = [0u8; SALT_LEN];
+    ::hasher::ring::fill_random(&mut salt)?;
+    Ok(salt)
+}
+
+/// A value hashed together with a salt, as the two-element list of the salt
+/// (as a hex string) and the value. This matches the salting done by the
+/// reference implementations' redactable JSON mode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Salted<T> {
+    pub salt: Salt,
+    pub value: T,
+}
+
+impl<T> Salted<T> {
+    /// Salt the given value with a new random salt
+    #[cfg(feature = "objecthash-ring")]
+    pub fn new(value: T) -> Result<Self, Error> {
+        Ok(Salted {
+            salt: random_salt()?,
+            value,
+        })
+    }
+}
+
+impl<T: ObjectHash> ObjectHash for Salted<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_salted(&self.salt, hasher, |h| self.value.objecthash(h));
+    }
+}
+
+pub(crate) fn hash_salted<H, F>(salt: &Salt, hasher: &mut H, value: F)
+    where H: ObjectHasher,
+          F: Fn(&mut H)
+{
+    hasher.update(LIST_TAG);
+    hasher.update_nested(|h| hex::encode(salt).objecthash(h));
+    hasher.update_nested(value);
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Redactable, Salted};
+    use digest;
+    use rustc_serialize::hex::ToHex;
+
+    #[test]
+    fn redacted_list_elements() {
+        let mut list = vec![Redactable::Clear("foo".to_owned()), Redactable::Clear("bar".to_owned())];
+        let expected = "32ae896c413cfdc79eec68be9139c86ded8b279238467c216cf2bec4d5f1e4a2";
+        assert_eq!(digest(&list).as_ref().to_hex(), expected);
+
+        let bar = list[1].redact();
+        assert_eq!(bar, digest("bar"));
+        assert!(list[1].is_redacted());
+        assert_eq!(list[1].clear(), None);
+        assert_eq!(digest(&list).as_ref().to_hex(), expected);
+
+        // Redacting twice is a no-op
+        assert_eq!(list[1].redact(), bar);
+        assert_eq!(digest(&Redactable::Redacted::<String>(bar)), bar);
+    }
+
+    #[test]
+    fn redacted_dict_values() {
+        let mut dict = HashMap::new();
+        dict.insert("foo".to_owned(), Redactable::Clear(vec!["bar".to_owned(), "baz".to_owned()]));
+        dict.insert("qux".to_owned(), Redactable::Clear(vec!["norf".to_owned()]));
+        let expected = digest(&dict);
+
+        dict.get_mut("qux").unwrap().redact();
+        assert_eq!(digest(&dict), expected);
+
+        dict.get_mut("foo").unwrap().redact();
+        assert_eq!(digest(&dict), expected);
+    }
+
+    #[test]
+    fn salted_values() {
+        let salted = Salted {
+            salt: [0x11; 32],
+            value: "Alice".to_owned(),
+        };
+
+        let list = vec!["1111111111111111111111111111111111111111111111111111111111111111".to_owned(),
+                        "Alice".to_owned()];
+        assert_eq!(digest(&salted), digest(&list));
+
+        let mut redactable = Redactable::Clear(Salted::new(true).unwrap());
+        let expected = digest(&redactable);
+        assert!(expected != digest(&Salted::new(true).unwrap()));
+
+        redactable.redact();
+        assert_eq!(digest(&redactable), expected);
+    }
+}
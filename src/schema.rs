@@ -0,0 +1,303 @@
+//! Fingerprints of the shapes of types
+//!
+//! Two services can only compare the digests of a record if they agree on
+//! its fields and their types: a peer which added a field, or changed one
+//! from a string to a list, hashes different values for the same data. The
+//! `Schema` trait describes a type's structure as a `TypeSchema`, without any
+//! value of it, so peers can compare `T::schema_hash()` at startup before
+//! exchanging digests of instances.
+//!
+//! `Schema` is implemented for the types this crate can hash, and for
+//! structs with `objecthash_schema!`, which takes the same keys as the
+//! `objecthash_struct!` the struct is hashed with:
+//!
+//! ```text
+//! objecthash_schema!(Record, "id" => u64, "tags" => Vec<String>);
+//! ```
+//!
+//! With the `derive` feature, `#[derive(ObjectHash, Schema)]` implements
+//! both from the struct's definition instead, so the keys aren't repeated.
+//!
+//! Schemas are hashed independently of any values, as:
+//!
+//! ```text
+//! "u64"                                  primitive types, by their Rust names
+//! {"list": <element>}                    Vec<T>
+//! {"set": <element>}                     HashSet<T>, BTreeSet<T>
+//! {"map": [<key>, <value>]}              HashMap<K, V>, BTreeMap<K, V>
+//! {"option": <value>}                    Option<T>
+//! {"struct": <name>, "fields": {<field>: <schema>, ...}}
+//! ```
+//!
+//! Pointers such as `Box<T>` and `Arc<T>` have the schema of what they point
+//! to, like they're hashed as it. Types which contain themselves don't have
+//! a schema, since their description would never end, and structs with two
+//! fields of the same name don't have a digest.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[cfg(feature = "derive")]
+pub use objecthash_derive::Schema;
+#[cfg(feature = "objecthash-ring")]
+use {hasher, Digest, Error, HashProfile};
+use {ObjectHash, ObjectHasher};
+use types::hash_dict;
+
+/// Description of the structure of a type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeSchema {
+    /// A type hashed as a single value, given its name
+    Primitive(String),
+
+    /// A list of elements with the given schema
+    List(Box<TypeSchema>),
+
+    /// A set of elements with the given schema
+    Set(Box<TypeSchema>),
+
+    /// A dict with keys and values with the given schemas
+    Map(Box<TypeSchema>, Box<TypeSchema>),
+
+    /// A value with the given schema, or null
+    Option(Box<TypeSchema>),
+
+    /// A struct with the given name and named fields
+    Struct {
+        name: String,
+        fields: Vec<(String, TypeSchema)>,
+    },
+}
+
+impl TypeSchema {
+    pub fn primitive<S: Into<String>>(name: S) -> Self {
+        TypeSchema::Primitive(name.into())
+    }
+}
+
+impl ObjectHash for TypeSchema {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            TypeSchema::Primitive(ref name) => name.objecthash(hasher),
+            TypeSchema::List(ref element) => objecthash_struct!(hasher, "list" => element),
+            TypeSchema::Set(ref element) => objecthash_struct!(hasher, "set" => element),
+            TypeSchema::Map(ref key, ref value) => objecthash_struct!(hasher, "map" => vec![key, value]),
+            TypeSchema::Option(ref value) => objecthash_struct!(hasher, "option" => value),
+            TypeSchema::Struct { ref name, ref fields } => {
+                objecthash_struct!(hasher, "struct" => name, "fields" => Fields(fields))
+            }
+        }
+    }
+}
+
+// A struct's fields, hashed as a dict from their names to their schemas.
+// Every field is hashed, so a repeated name is a duplicate key rather than
+// one of the fields going missing
+struct Fields<'a>(&'a [(String, TypeSchema)]);
+
+impl<'a> ObjectHash for Fields<'a> {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_dict(self.0.iter().map(|(field, schema)| (field.as_str(), schema)), hasher);
+    }
+}
+
+/// Types with a structure which can be described independently of values
+pub trait Schema {
+    /// Describe the structure of this type
+    fn schema() -> TypeSchema;
+
+    /// The digest of this type's schema, to compare with peers'. Fails with
+    /// `Error::DuplicateKey` if a struct within it has two fields with the
+    /// same name
+    #[cfg(feature = "objecthash-ring")]
+    fn schema_hash() -> Result<Digest, Error> {
+        let mut hasher = hasher::default().with_profile(HashProfile::default().with_reject_duplicate_keys(true));
+        Self::schema().objecthash(&mut hasher);
+        hasher.try_finish()
+    }
+}
+
+macro_rules! impl_primitive_schema (($($type:ty),+) => (
+    $(
+        impl Schema for $type {
+            fn schema() -> TypeSchema {
+                TypeSchema::primitive(stringify!($type))
+            }
+        }
+    )+
+));
+
+impl_primitive_schema!((), bool, f32, f64, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl Schema for str {
+    fn schema() -> TypeSchema {
+        TypeSchema::primitive("string")
+    }
+}
+
+impl Schema for String {
+    fn schema() -> TypeSchema {
+        str::schema()
+    }
+}
+
+impl<T: Schema> Schema for Option<T> {
+    fn schema() -> TypeSchema {
+        TypeSchema::Option(Box::new(T::schema()))
+    }
+}
+
+impl<T: Schema> Schema for Vec<T> {
+    fn schema() -> TypeSchema {
+        TypeSchema::List(Box::new(T::schema()))
+    }
+}
+
+impl<T: Schema, S> Schema for HashSet<T, S> {
+    fn schema() -> TypeSchema {
+        TypeSchema::Set(Box::new(T::schema()))
+    }
+}
+
+impl<T: Schema> Schema for BTreeSet<T> {
+    fn schema() -> TypeSchema {
+        TypeSchema::Set(Box::new(T::schema()))
+    }
+}
+
+impl<K: Schema, V: Schema, S> Schema for HashMap<K, V, S> {
+    fn schema() -> TypeSchema {
+        TypeSchema::Map(Box::new(K::schema()), Box::new(V::schema()))
+    }
+}
+
+impl<K: Schema, V: Schema> Schema for BTreeMap<K, V> {
+    fn schema() -> TypeSchema {
+        TypeSchema::Map(Box::new(K::schema()), Box::new(V::schema()))
+    }
+}
+
+macro_rules! impl_pointer_schema (($($pointer:ident),+) => (
+    $(
+        impl<T: Schema + ?Sized> Schema for $pointer<T> {
+            fn schema() -> TypeSchema {
+                T::schema()
+            }
+        }
+    )+
+));
+
+impl_pointer_schema!(Box, Rc, Arc);
+
+impl<T: Schema + ?Sized> Schema for &T {
+    fn schema() -> TypeSchema {
+        T::schema()
+    }
+}
+
+impl<'a, T: Schema + ToOwned + ?Sized> Schema for Cow<'a, T> {
+    fn schema() -> TypeSchema {
+        T::schema()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::sync::Arc;
+
+    use super::{Schema, TypeSchema};
+    use {digest, Error};
+
+    struct Record;
+    objecthash_schema!(Record, "id" => u64, "tags" => Vec<String>, "parent" => Option<Box<Parent>>);
+
+    struct Parent;
+    objecthash_schema!(Parent, "id" => u64);
+
+    fn record(id: &str, tags: TypeSchema) -> TypeSchema {
+        TypeSchema::Struct {
+            name: "Record".to_owned(),
+            fields: vec![("parent".to_owned(), Option::<Parent>::schema()),
+                         ("tags".to_owned(), tags),
+                         (id.to_owned(), u64::schema())],
+        }
+    }
+
+    #[test]
+    fn describing_types() {
+        assert_eq!(Parent::schema(),
+                   TypeSchema::Struct {
+                       name: "Parent".to_owned(),
+                       fields: vec![("id".to_owned(), TypeSchema::primitive("u64"))],
+                   });
+
+        assert_eq!(HashMap::<String, Vec<i32>>::schema(),
+                   TypeSchema::Map(Box::new(TypeSchema::primitive("string")),
+                                   Box::new(TypeSchema::List(Box::new(TypeSchema::primitive("i32"))))));
+        assert_eq!(HashSet::<u8>::schema(), TypeSchema::Set(Box::new(TypeSchema::primitive("u8"))));
+        assert_eq!(Arc::<str>::schema(), String::schema());
+    }
+
+    #[test]
+    fn hashing_schemas() {
+        assert_eq!(u64::schema_hash().unwrap(), digest("u64"));
+        assert!(u64::schema_hash().unwrap() != i64::schema_hash().unwrap());
+        assert_eq!(BTreeMap::<String, bool>::schema_hash().unwrap(), HashMap::<&str, bool>::schema_hash().unwrap());
+
+        let mut expected = HashMap::new();
+        expected.insert("list", "u64");
+        assert_eq!(Vec::<u64>::schema_hash().unwrap(), digest(&expected));
+
+        // The order of the fields doesn't matter, but their names and types do
+        assert_eq!(Record::schema_hash().unwrap(), digest(&record("id", Vec::<&str>::schema())));
+        assert!(Record::schema_hash().unwrap() != digest(&record("key", Vec::<String>::schema())));
+        assert!(Record::schema_hash().unwrap() != digest(&record("id", Vec::<Vec<String>>::schema())));
+        assert!(Record::schema_hash().unwrap() != Parent::schema_hash().unwrap());
+    }
+
+    #[test]
+    fn repeated_fields() {
+        struct Repeated;
+        objecthash_schema!(Repeated, "id" => u64, "tags" => Vec<String>, "id" => String);
+
+        match Repeated::schema_hash() {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, digest("id")),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+
+        // Both fields are hashed, rather than one replacing the other
+        let once = record("id", Vec::<String>::schema());
+        let mut twice = once.clone();
+        if let TypeSchema::Struct { ref mut fields, .. } = twice {
+            fields.push(("id".to_owned(), u64::schema()));
+        }
+        assert!(digest(&twice) != digest(&once));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_schemas() {
+        // Shadows the struct with the same schema written out by hand
+        #[derive(Schema)]
+        #[allow(dead_code)]
+        struct Record {
+            id: u64,
+            tags: Vec<String>,
+            #[objecthash(rename = "parent")]
+            up: Option<Box<Parent>>,
+        }
+
+        assert_eq!(Record::schema(),
+                   TypeSchema::Struct {
+                       name: "Record".to_owned(),
+                       fields: vec![("id".to_owned(), u64::schema()),
+                                    ("tags".to_owned(), Vec::<String>::schema()),
+                                    ("parent".to_owned(), Option::<Parent>::schema())],
+                   });
+        assert_eq!(Record::schema_hash().unwrap(), super::tests::Record::schema_hash().unwrap());
+    }
+}
@@ -0,0 +1,546 @@
+//! Hash any type implementing `serde::Serialize`
+//!
+//! Values are mapped onto ObjectHash types the same way `serde_json` maps
+//! them onto JSON, so a value hashes identically to its JSON representation:
+//!
+//! * Structs and maps are dicts, keyed by field name
+//! * Sequences, tuples, and tuple structs are lists
+//! * `None` and unit values are null; `Some(value)` is the bare value
+//! * Unit enum variants are strings of the variant name
+//! * Other enum variants are single-member dicts from the variant name to
+//!   their contents
+//! * Newtype structs are their inner value
+//! * Bytes are octet strings when the `octet-strings` feature is enabled and
+//!   lists of integers otherwise
+
+use serde::ser::{self, Serialize};
+
+use {Digest, Error, ObjectHash, ObjectHasher};
+use types::{check_key, hash_dict_members, DICT_TAG, LIST_TAG};
+
+pub struct ObjectHashSerializer<'a, H: ObjectHasher + 'a> {
+    hasher: &'a mut H,
+}
+
+impl<'a, H: ObjectHasher + 'a> ObjectHashSerializer<'a, H> {
+    pub fn new(hasher: &'a mut H) -> Self {
+        ObjectHashSerializer { hasher }
+    }
+}
+
+/// Compute the ObjectHash digest of a `Serialize` value
+#[cfg(feature = "objecthash-ring")]
+pub fn digest<T: Serialize + ?Sized>(value: &T) -> Result<Digest, Error> {
+    let mut hasher = ::hasher::default();
+    value.serialize(ObjectHashSerializer::new(&mut hasher))?;
+    Ok(hasher.finish())
+}
+
+fn nested_digest<H, T>(hasher: &H, value: &T) -> Result<Digest, Error>
+    where H: ObjectHasher,
+          T: Serialize + ?Sized
+{
+    let mut nested_hasher = hasher.nested();
+    value.serialize(ObjectHashSerializer::new(&mut nested_hasher))?;
+    Ok(nested_hasher.finish())
+}
+
+fn update_variant<H, T>(hasher: &mut H, variant: &str, value: &T) -> Result<(), Error>
+    where H: ObjectHasher,
+          T: Serialize + ?Sized
+{
+    let mut key_hasher = hasher.nested();
+    variant.objecthash(&mut key_hasher);
+    let key_digest = key_hasher.finish();
+    let value_digest = nested_digest(hasher, value)?;
+
+    hasher.update(DICT_TAG);
+    hasher.update(key_digest.as_ref());
+    hasher.update(value_digest.as_ref());
+    Ok(())
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::Serializer for ObjectHashSerializer<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, H>;
+    type SerializeTuple = Compound<'a, H>;
+    type SerializeTupleStruct = Compound<'a, H>;
+    type SerializeTupleVariant = Compound<'a, H>;
+    type SerializeMap = Compound<'a, H>;
+    type SerializeStruct = Compound<'a, H>;
+    type SerializeStructVariant = Compound<'a, H>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        v.encode_utf8(&mut [0u8; 4]).objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    #[cfg(feature = "octet-strings")]
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        v.objecthash(self.hasher);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "octet-strings"))]
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.hasher.update(LIST_TAG);
+
+        for byte in v {
+            self.hasher.update_nested(|h| byte.objecthash(h));
+        }
+
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        ().objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        ().objecthash(self.hasher);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self,
+                              _name: &'static str,
+                              _variant_index: u32,
+                              variant: &'static str)
+                              -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self,
+                                                       _name: &'static str,
+                                                       value: &T)
+                                                       -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self,
+                                                        _name: &'static str,
+                                                        _variant_index: u32,
+                                                        variant: &'static str,
+                                                        value: &T)
+                                                        -> Result<(), Error> {
+        update_variant(self.hasher, variant, value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a, H>, Error> {
+        Ok(Compound::list(self.hasher, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, H>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self,
+                              _name: &'static str,
+                              len: usize)
+                              -> Result<Compound<'a, H>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self,
+                               _name: &'static str,
+                               _variant_index: u32,
+                               variant: &'static str,
+                               _len: usize)
+                               -> Result<Compound<'a, H>, Error> {
+        Ok(Compound::list(self.hasher, Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a, H>, Error> {
+        Ok(Compound::dict(self.hasher, None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, H>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self,
+                                _name: &'static str,
+                                _variant_index: u32,
+                                variant: &'static str,
+                                _len: usize)
+                                -> Result<Compound<'a, H>, Error> {
+        Ok(Compound::dict(self.hasher, Some(variant)))
+    }
+}
+
+/// State for serializing lists and dicts, optionally wrapped in an enum variant
+pub struct Compound<'a, H: ObjectHasher + 'a> {
+    hasher: &'a mut H,
+    variant: Option<(&'static str, H)>,
+    members: Vec<Vec<u8>>,
+    key_digest: Option<Digest>,
+}
+
+impl<'a, H: ObjectHasher + 'a> Compound<'a, H> {
+    fn list(hasher: &'a mut H, variant: Option<&'static str>) -> Self {
+        let mut compound = Self::dict(hasher, variant);
+        compound.target().update(LIST_TAG);
+        compound
+    }
+
+    fn dict(hasher: &'a mut H, variant: Option<&'static str>) -> Self {
+        let variant = variant.map(|name| {
+            let nested_hasher = hasher.nested();
+            (name, nested_hasher)
+        });
+
+        Compound {
+            hasher,
+            variant,
+            members: Vec::new(),
+            key_digest: None,
+        }
+    }
+
+    fn target(&mut self) -> &mut H {
+        match self.variant {
+            Some((_, ref mut nested_hasher)) => nested_hasher,
+            None => self.hasher,
+        }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let value_digest = nested_digest(self.hasher, value)?;
+        self.target().update(value_digest.as_ref());
+        Ok(())
+    }
+
+    fn key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let mut key_hasher = self.hasher.nested();
+        key.serialize(ObjectHashSerializer::new(&mut key_hasher))?;
+        check_key(&mut key_hasher);
+        self.key_digest = Some(key_hasher.finish());
+        Ok(())
+    }
+
+    fn value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key_digest = self.key_digest
+            .take()
+            .ok_or_else(|| Error::Serialize(String::from("map value serialized before key")))?;
+        let value_digest = nested_digest(self.hasher, value)?;
+
+        let mut member = Vec::with_capacity(key_digest.as_ref().len() + value_digest.as_ref().len());
+        member.extend_from_slice(key_digest.as_ref());
+        member.extend_from_slice(value_digest.as_ref());
+        self.members.push(member);
+        Ok(())
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    fn end_list(self) -> Result<(), Error> {
+        if let Some((name, nested_hasher)) = self.variant {
+            let mut key_hasher = self.hasher.nested();
+            name.objecthash(&mut key_hasher);
+
+            self.hasher.update(DICT_TAG);
+            self.hasher.update(key_hasher.finish().as_ref());
+            self.hasher.update(nested_hasher.finish().as_ref());
+        }
+
+        Ok(())
+    }
+
+    fn end_dict(mut self) -> Result<(), Error> {
+        let mut members = ::std::mem::take(&mut self.members);
+        hash_dict_members(&mut members, self.target());
+        self.end_list()
+    }
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::SerializeSeq for Compound<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.end_list()
+    }
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::SerializeTuple for Compound<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.end_list()
+    }
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::SerializeTupleStruct for Compound<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.end_list()
+    }
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::SerializeTupleVariant for Compound<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.end_list()
+    }
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::SerializeMap for Compound<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.end_dict()
+    }
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::SerializeStruct for Compound<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self,
+                                              key: &'static str,
+                                              value: &T)
+                                              -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.end_dict()
+    }
+}
+
+impl<'a, H: ObjectHasher + 'a> ser::SerializeStructVariant for Compound<'a, H> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self,
+                                              key: &'static str,
+                                              value: &T)
+                                              -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.end_dict()
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use serde::{Serialize, Serializer};
+
+    use super::digest;
+    use rustc_serialize::hex::ToHex;
+
+    macro_rules! h {
+        ($value:expr) => {
+            digest(&$value).unwrap().as_ref().to_hex()
+        };
+    }
+
+    #[derive(Serialize)]
+    struct Example {
+        foo: u32,
+    }
+
+    #[derive(Serialize)]
+    struct Nested {
+        bar: Vec<Element>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Element {
+        String(&'static str),
+        Float(Option<f64>),
+    }
+
+    #[derive(Serialize)]
+    struct Wrapper(Vec<u8>);
+
+    #[derive(Serialize)]
+    enum Variants {
+        Unit,
+        Newtype(u32),
+        Tuple(u32, u32),
+        Struct { foo: u32 },
+    }
+
+    struct Bytes(&'static [u8]);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(h!(1000), "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b");
+        assert_eq!(h!("ԱԲաբ"), "2a2a4485a4e338d8df683971956b1090d2f5d33955a81ecaad1a75125f7a316c");
+        assert_eq!(h!(true), "7dc96f776c8423e57a2785489a3f9c43fb6e756876d6ad9a9cac4aa4e72ec193");
+        assert_eq!(h!(1.5), "7d9d2d2489ee3a73c6e6e7b84469a5f697e902793cbbb3b4b1c0da46b9b4bdec");
+        assert_eq!(h!(()), "1b16b1df538ba12dc3f97edbb85caa7050d46c148134290feba80f8236c83db9");
+    }
+
+    #[test]
+    fn structs() {
+        assert_eq!(h!(Example { foo: 1 }), "bf4c58f5e308e31e2cd64bdbf7a01b9b595a13602438be5e912c7d94f6d8177a");
+        assert_eq!(h!(Wrapper(vec![1, 2, 3])), "157bf16c70bd4c9673ffb5030552df0ee2c40282042ccdf6167850edc9044ab7");
+
+        // ["foo", {"bar": ["baz", null, 1.0, 1.5, 0.0001, 1000.0, 2.0, -23.1234, 2.0]}]
+        let nested = Nested {
+            bar: vec![Element::String("baz"),
+                      Element::Float(None),
+                      Element::Float(Some(1.0)),
+                      Element::Float(Some(1.5)),
+                      Element::Float(Some(0.0001)),
+                      Element::Float(Some(1000.0)),
+                      Element::Float(Some(2.0)),
+                      Element::Float(Some(-23.1234)),
+                      Element::Float(Some(2.0))],
+        };
+
+        assert_eq!(h!(("foo", nested)), "783a423b094307bcb28d005bc2f026ff44204442ef3513585e7e73b66e3c2213");
+    }
+
+    #[test]
+    fn enums() {
+        assert_eq!(h!(Variants::Unit), h!("Unit"));
+        assert_eq!(h!(Variants::Newtype(1)), "d83a9549ed1f62e86a0ccc4d7e271628443af75d60cf0703465117f18dc07622");
+        assert_eq!(h!(Variants::Tuple(1, 2)), "6d4e4a793a080b8613eec05d641137a1c20b025b3d5ccf5a70b93319c7162592");
+        assert_eq!(h!(Variants::Struct { foo: 1 }), "59f0684902338514b5450a480a07dca24987b69a2dae9861897e8e75cd32e3c2");
+    }
+
+    #[test]
+    fn tuples_and_options() {
+        assert_eq!(h!((1, 2, 3)), h!(vec![1, 2, 3]));
+        assert_eq!(h!(Some(1000)), h!(1000));
+        assert_eq!(h!(None::<u32>), h!(()));
+    }
+
+    #[cfg(feature = "octet-strings")]
+    #[test]
+    fn bytes() {
+        assert_eq!(h!(Bytes(b"foo")), ::digest(&b"foo"[..]).as_ref().to_hex());
+    }
+
+    #[cfg(not(feature = "octet-strings"))]
+    #[test]
+    fn bytes() {
+        assert_eq!(h!(Bytes(&[1, 2, 3])), h!(vec![1, 2, 3]));
+    }
+}
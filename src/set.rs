@@ -0,0 +1,58 @@
+//! Hashing sequences as sets
+//!
+//! Data which is semantically a set often arrives as a `Vec` or some other
+//! sequence. `Set<I>` hashes any such sequence the way ObjectHash hashes
+//! sets, with the set tag and the sorted digests of its distinct members,
+//! so the order and repetition of elements don't affect the digest.
+
+use {ObjectHash, ObjectHasher};
+use types::hash_set;
+
+/// Hashes the members produced by the wrapped iterable as a set.
+///
+/// The iterable is cloned each time the set is hashed, as hashing consumes
+/// it and `objecthash` only borrows the wrapper. Wrap a reference to a
+/// collection (`Set(&vec)`), or a cheaply cloned iterator such as
+/// `names.iter().map(String::as_str)`, rather than an owned collection,
+/// which would be copied every time. `StringKeys` and `MultiMap` wrap
+/// iterables the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Set<I>(pub I);
+
+impl<I> ObjectHash for Set<I>
+    where I: IntoIterator + Clone,
+          I::Item: ObjectHash
+{
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_set(self.0.clone(), hasher);
+    }
+}
+
+/// Hash the members produced by the given iterable as a set
+pub fn as_set<I>(members: I) -> Set<I>
+    where I: IntoIterator + Clone,
+          I::Item: ObjectHash
+{
+    Set(members)
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{as_set, Set};
+    use digest;
+
+    #[test]
+    fn hashing_sets() {
+        let tags = vec!["foo", "bar", "foo", "baz"];
+        let set: BTreeSet<&str> = tags.iter().cloned().collect();
+
+        assert_eq!(digest(&as_set(&tags)), digest(&set));
+        assert_eq!(digest(&Set(tags.iter().rev())), digest(&set));
+        assert_eq!(digest(&as_set(&tags[..2])), digest(&as_set(&["bar", "foo"])));
+        assert!(digest(&as_set(&tags)) != digest(&tags));
+    }
+}
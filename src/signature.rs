@@ -0,0 +1,220 @@
+//! Signed digests
+//!
+//! `SignedDigest` is an envelope for the signature of an object's digest.
+//! What gets signed isn't the bare digest but the digest of a list which
+//! also names how it was computed, so a signature can't be passed off as one
+//! over a digest computed with another algorithm or `HashProfile`:
+//!
+//! ```text
+//! ["objecthash-signed-digest", hash_algorithm, profile, signature_algorithm, <digest>]
+//! ```
+//!
+//! where `<digest>` stands in for the object's digest, as in a redacted
+//! list. Profiles are named by identifiers which signers and verifiers agree
+//! on, with `DEFAULT_PROFILE` for `HashProfile::default()`. Ed25519 is the
+//! only signature algorithm so far.
+
+extern crate ring;
+
+#[cfg(feature = "json")]
+use serde_json::{Map, Value};
+
+use self::ring::rand::SystemRandom;
+use self::ring::signature::{self as ring_signature, Ed25519KeyPair, ED25519};
+use untrusted::Input;
+
+use {digest, Digest, Error, ObjectHash};
+#[cfg(feature = "json")]
+use hex;
+use stream::ListHasher;
+
+/// Identifier of the SHA-256 hash algorithm, which `hasher::default()` uses
+pub const SHA256: &str = "sha256";
+
+/// Identifier of the Ed25519 signature algorithm
+pub const ED25519_ALGORITHM: &str = "ed25519";
+
+/// Identifier of `HashProfile::default()`
+pub const DEFAULT_PROFILE: &str = "default";
+
+const ENVELOPE_DOMAIN: &str = "objecthash-signed-digest";
+
+/// An Ed25519 key for signing digests
+pub struct SigningKey {
+    pair: Ed25519KeyPair,
+}
+
+impl SigningKey {
+    /// Generate a new key, returning it as a PKCS#8 document to be loaded
+    /// with `from_pkcs8`
+    pub fn generate_pkcs8() -> Result<Vec<u8>, Error> {
+        Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map(|document| document.to_vec())
+            .map_err(|_| Error::Random)
+    }
+
+    /// Load a key from a PKCS#8 document
+    pub fn from_pkcs8(document: &[u8]) -> Result<Self, Error> {
+        Ed25519KeyPair::from_pkcs8(Input::from(document))
+            .map(|pair| SigningKey { pair })
+            .map_err(|_| Error::InvalidKey)
+    }
+
+    /// Load a key from its 32-byte seed
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        Ed25519KeyPair::from_seed_unchecked(Input::from(seed))
+            .map(|pair| SigningKey { pair })
+            .map_err(|_| Error::InvalidKey)
+    }
+
+    /// The public key which verifies this key's signatures
+    pub fn public_key(&self) -> &[u8] {
+        self.pair.public_key_bytes()
+    }
+}
+
+/// The signature of an object's digest, along with identifiers of how the
+/// digest was computed and signed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedDigest {
+    pub digest: Digest,
+    pub hash_algorithm: String,
+    pub profile: String,
+    pub signature_algorithm: String,
+    pub signature: Vec<u8>,
+}
+
+impl SignedDigest {
+    /// Hash the given value with the default hasher and profile, and sign
+    /// its digest
+    pub fn sign<T: ObjectHash + ?Sized>(value: &T, key: &SigningKey) -> Self {
+        Self::sign_digest(&digest(value), SHA256, DEFAULT_PROFILE, key)
+    }
+
+    /// Sign a digest computed with the given hash algorithm and profile
+    pub fn sign_digest(digest: &Digest, hash_algorithm: &str, profile: &str, key: &SigningKey) -> Self {
+        let mut signed = SignedDigest {
+            digest: *digest,
+            hash_algorithm: hash_algorithm.to_owned(),
+            profile: profile.to_owned(),
+            signature_algorithm: ED25519_ALGORITHM.to_owned(),
+            signature: Vec::new(),
+        };
+
+        signed.signature = key.pair.sign(signed.message().as_ref()).as_ref().to_vec();
+        signed
+    }
+
+    /// Check the signature against the given public key
+    pub fn verify(&self, public_key: &[u8]) -> Result<(), Error> {
+        if self.signature_algorithm != ED25519_ALGORITHM {
+            return Err(Error::InvalidSignature);
+        }
+
+        ring_signature::verify(&ED25519,
+                               Input::from(public_key),
+                               Input::from(self.message().as_ref()),
+                               Input::from(&self.signature))
+            .map_err(|_| Error::InvalidSignature)
+    }
+
+    /// Check that this is a signature of the given value's digest, computed
+    /// with the default hasher and profile, by the given public key
+    pub fn verify_value<T: ObjectHash + ?Sized>(&self, value: &T, public_key: &[u8]) -> Result<(), Error> {
+        if self.hash_algorithm != SHA256 || self.profile != DEFAULT_PROFILE || self.digest != digest(value) {
+            return Err(Error::InvalidSignature);
+        }
+
+        self.verify(public_key)
+    }
+
+    /// Serialize the envelope as a JSON object, with the digest and
+    /// signature in hex
+    #[cfg(feature = "json")]
+    pub fn to_value(&self) -> Value {
+        let mut map = Map::new();
+        map.insert("digest".to_owned(), Value::String(hex::encode(self.digest.as_ref())));
+        map.insert("hash".to_owned(), Value::String(self.hash_algorithm.clone()));
+        map.insert("profile".to_owned(), Value::String(self.profile.clone()));
+        map.insert("alg".to_owned(), Value::String(self.signature_algorithm.clone()));
+        map.insert("sig".to_owned(), Value::String(hex::encode(&self.signature)));
+        Value::Object(map)
+    }
+
+    /// Restore an envelope serialized with `to_value`
+    #[cfg(feature = "json")]
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let field = |name: &str| value.get(name).and_then(Value::as_str).ok_or(Error::InvalidSignature);
+        let bytes = |name: &str| field(name).and_then(|s| hex::decode(s).ok_or(Error::InvalidSignature));
+
+        Ok(SignedDigest {
+            digest: Digest::new(&bytes("digest")?)?,
+            hash_algorithm: field("hash")?.to_owned(),
+            profile: field("profile")?.to_owned(),
+            signature_algorithm: field("alg")?.to_owned(),
+            signature: bytes("sig")?,
+        })
+    }
+
+    // The digest which is actually signed
+    fn message(&self) -> Digest {
+        let mut hasher = ListHasher::default();
+        hasher.push(ENVELOPE_DOMAIN);
+        hasher.push(&self.hash_algorithm);
+        hasher.push(&self.profile);
+        hasher.push(&self.signature_algorithm);
+        hasher.push_digest(&self.digest);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignedDigest, SigningKey, DEFAULT_PROFILE, SHA256};
+    use {digest, Error};
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_seed(&[seed; 32]).unwrap()
+    }
+
+    #[test]
+    fn signing() {
+        let key = key(1);
+        let value = vec!["foo", "bar"];
+
+        let signed = SignedDigest::sign(&value, &key);
+        assert_eq!(signed.digest, digest(&value));
+        assert!(signed.verify(key.public_key()).is_ok());
+        assert!(signed.verify_value(&value, key.public_key()).is_ok());
+
+        assert!(signed.verify_value(&vec!["foo"], key.public_key()).is_err());
+        assert!(signed.verify(self::key(2).public_key()).is_err());
+
+        // The same digest under another profile is a different signature
+        let other = SignedDigest::sign_digest(&signed.digest, SHA256, "strict", &key);
+        assert!(other.signature != signed.signature);
+        assert!(other.verify(key.public_key()).is_ok());
+
+        let mut forged = other.clone();
+        forged.profile = DEFAULT_PROFILE.to_owned();
+        match forged.verify(key.public_key()) {
+            Err(Error::InvalidSignature) => (),
+            other => panic!("expected an invalid signature, got {:?}", other),
+        }
+
+        let document = SigningKey::generate_pkcs8().unwrap();
+        let generated = SigningKey::from_pkcs8(&document).unwrap();
+        assert!(SignedDigest::sign(&value, &generated).verify(generated.public_key()).is_ok());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serializing() {
+        let key = key(1);
+        let signed = SignedDigest::sign("foo", &key);
+
+        let restored = SignedDigest::from_value(&signed.to_value()).unwrap();
+        assert_eq!(restored, signed);
+        assert!(restored.verify(key.public_key()).is_ok());
+    }
+}
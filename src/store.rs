@@ -0,0 +1,266 @@
+//! Content-addressed storage keyed by ObjectHash digests
+//!
+//! A `ContentStore` keeps encoded values under their digests, and hashes
+//! every value it reads back to check it's the one that was asked for, so a
+//! corrupted or tampered backend can't hand out the wrong content. Values are
+//! encoded through the `Content` trait, which is implemented for strings
+//! and, with the `json` feature, JSON values.
+//!
+//! `MemoryStore` keeps values in a map, and `FileStore` in files named after
+//! their digests in a directory.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "json")]
+use serde_json::{self, Value};
+
+use {digest, hex, Digest, Error, ObjectHash};
+
+/// Values which can be kept in a content store
+pub trait Content: ObjectHash + Sized {
+    fn encode(&self) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+impl Content for String {
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| Error::InvalidContent(e.to_string()))
+    }
+}
+
+#[cfg(feature = "json")]
+impl Content for Value {
+    fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("JSON values always serialize")
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Storage for encoded values keyed by their digests. Backends implement
+/// the raw methods, and the others encode, hash and verify values on top of
+/// them
+pub trait ContentStore {
+    /// Store the encoding of a value under its digest
+    fn put_raw(&mut self, digest: &Digest, bytes: &[u8]) -> Result<(), Error>;
+
+    /// Get the encoding stored under the given digest, if there is one
+    fn get_raw(&self, digest: &Digest) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Is anything stored under the given digest?
+    fn contains(&self, digest: &Digest) -> Result<bool, Error> {
+        self.get_raw(digest).map(|bytes| bytes.is_some())
+    }
+
+    /// Store a value, returning its digest
+    fn put<T: Content>(&mut self, value: &T) -> Result<Digest, Error> {
+        let digest = digest(value);
+        self.put_raw(&digest, &value.encode())?;
+        Ok(digest)
+    }
+
+    /// Get the value stored under the given digest, checking that it has
+    /// that digest
+    fn get_verified<T: Content>(&self, digest: &Digest) -> Result<T, Error> {
+        let bytes = self.get_raw(digest)?.ok_or(Error::MissingContent(*digest))?;
+        let value = T::decode(&bytes)?;
+
+        if ::digest(&value) != *digest {
+            return Err(Error::InvalidContent(format!("content doesn't match digest {}",
+                                                     hex::encode(digest.as_ref()))));
+        }
+
+        Ok(value)
+    }
+}
+
+/// A content store which keeps values in memory
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    objects: HashMap<Digest, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl ContentStore for MemoryStore {
+    fn put_raw(&mut self, digest: &Digest, bytes: &[u8]) -> Result<(), Error> {
+        self.objects.insert(*digest, bytes.to_vec());
+        Ok(())
+    }
+
+    fn get_raw(&self, digest: &Digest) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.objects.get(digest).cloned())
+    }
+}
+
+/// A content store which keeps each value in a file named after its digest
+/// in hex, under a subdirectory named after the digest's first byte
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    /// Open a store in the given directory, creating it if need be
+    pub fn open<P: Into<PathBuf>>(root: P) -> Result<Self, Error> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(FileStore { root })
+    }
+
+    fn path(&self, digest: &Digest) -> Result<PathBuf, Error> {
+        // An empty digest has no first byte to name its subdirectory after
+        if digest.as_ref().is_empty() {
+            return Err(Error::DigestLength(0));
+        }
+
+        let name = hex::encode(digest.as_ref());
+        Ok(self.root.join(&name[..2]).join(&name))
+    }
+}
+
+impl ContentStore for FileStore {
+    fn put_raw(&mut self, digest: &Digest, bytes: &[u8]) -> Result<(), Error> {
+        write_atomically(&self.path(digest)?, bytes)?;
+        Ok(())
+    }
+
+    fn get_raw(&self, digest: &Digest) -> Result<Option<Vec<u8>>, Error> {
+        match fs::read(self.path(digest)?) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Write a file by writing a temporary file next to it and renaming it into
+/// place, so readers never see it partly written. The temporary file's name
+/// is unique to the process and the call, so concurrent writers of the same
+/// file don't clobber each other's
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fs::create_dir_all(path.parent().expect("path without a parent"))?;
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp = path.with_extension(format!("{}.{}.tmp", process::id(), count));
+
+    let written = fs::File::create(&temp)
+        .and_then(|mut file| file.write_all(bytes))
+        .and_then(|()| fs::rename(&temp, path));
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::thread;
+
+    use super::{ContentStore, FileStore, MemoryStore};
+    use {digest, Digest, Error};
+
+    fn check<S: ContentStore>(store: &mut S) {
+        let value = "foo".to_owned();
+        let key = store.put(&value).unwrap();
+        assert_eq!(key, digest("foo"));
+        assert!(store.contains(&key).unwrap());
+        assert_eq!(store.get_verified::<String>(&key).unwrap(), value);
+
+        match store.get_verified::<String>(&digest("bar")) {
+            Err(Error::MissingContent(d)) => assert_eq!(d, digest("bar")),
+            other => panic!("expected missing content, got {:?}", other),
+        }
+
+        // Content which doesn't match its digest is rejected
+        store.put_raw(&key, b"bar").unwrap();
+        match store.get_verified::<String>(&key) {
+            Err(Error::InvalidContent(_)) => (),
+            other => panic!("expected invalid content, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn memory_store() {
+        let mut store = MemoryStore::new();
+        check(&mut store);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn file_store() {
+        let root = env::temp_dir().join(format!("objecthash-store-{}", ::std::process::id()));
+        let mut store = FileStore::open(&root).unwrap();
+        check(&mut store);
+
+        match store.put_raw(&Digest::new(&[]).unwrap(), b"") {
+            Err(Error::DigestLength(0)) => (),
+            other => panic!("expected an empty digest to be rejected, got {:?}", other),
+        }
+        assert!(store.get_raw(&Digest::new(&[]).unwrap()).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn concurrent_file_stores() {
+        let root = env::temp_dir().join(format!("objecthash-store-concurrent-{}", ::std::process::id()));
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let mut store = FileStore::open(&root).unwrap();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        store.put(&"foo".to_owned()).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let store = FileStore::open(&root).unwrap();
+        assert_eq!(store.get_verified::<String>(&digest("foo")).unwrap(), "foo");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_content() {
+        use serde_json::{self, Value};
+
+        let value: Value = serde_json::from_str(r#"{"foo": [1, 1.5, "bar", null]}"#).unwrap();
+        let mut store = MemoryStore::new();
+        let key = store.put(&value).unwrap();
+        assert_eq!(store.get_verified::<Value>(&key).unwrap(), value);
+    }
+}
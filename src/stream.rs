@@ -0,0 +1,519 @@
+//! Hashing values as they're produced
+//!
+//! A list's digest only depends on its elements' digests in order, so the
+//! elements of a sequence generated on the fly (by a database cursor or a
+//! decoder, say) can be hashed one at a time without collecting them first.
+//! A dict's digest only depends on the digests of its members, so a stream
+//! of key-value pairs can be hashed as a dict without collecting it into a
+//! map either. `ListHasher` and `DictHasher` do the same for values which
+//! arrive over time, keeping only the digests a dict's members need sorting.
+//!
+//! Likewise an octet string's digest is that of its bytes, so with the
+//! `octet-strings` feature, files and blobs can be hashed from an `io::Read`
+//! or in chunks without reading them into memory.
+//!
+//! Encoders which only write to an `io::Write` can write straight into a
+//! hasher through `OctetsWriter`, which hashes what's written as an octet
+//! string, or `RawWriter`, which feeds it to the hasher as it is.
+
+use std::io::{self, Write};
+#[cfg(feature = "octet-strings")]
+use std::io::Read;
+
+#[cfg(feature = "objecthash-ring")]
+use hasher;
+use {Digest, Error, ObjectHash, ObjectHasher, MAX_OUTPUT_LEN};
+use types::{dict_member, hash_dict, hash_dict_members, hash_member, LIST_TAG};
+#[cfg(feature = "octet-strings")]
+use types::{check_len, update_extension_tag, Extension};
+
+/// Hashes a list one element at a time
+pub struct ListHasher<H: ObjectHasher> {
+    hasher: H,
+    len: usize,
+}
+
+impl<H: ObjectHasher> ListHasher<H> {
+    /// Start hashing a list into the given hasher
+    pub fn new(mut hasher: H) -> Self {
+        hasher.update(LIST_TAG);
+        ListHasher { hasher, len: 0 }
+    }
+
+    /// Hash the next element of the list
+    pub fn push<T: ObjectHash + ?Sized>(&mut self, element: &T) -> &mut Self {
+        self.hasher.update_nested(|h| element.objecthash(h));
+        self.len += 1;
+        self
+    }
+
+    /// Add the next element of the list given its digest
+    pub fn push_digest(&mut self, digest: &Digest) -> &mut Self {
+        self.hasher.update(digest.as_ref());
+        self.len += 1;
+        self
+    }
+
+    /// Number of elements hashed so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Finish hashing, returning the digest of the list
+    pub fn finish(self) -> Digest {
+        self.hasher.finish()
+    }
+
+    /// Finish hashing, returning the digest of the list or the first error
+    /// recorded while hashing its elements
+    pub fn try_finish(self) -> Result<Digest, Error> {
+        self.hasher.try_finish()
+    }
+}
+
+#[cfg(feature = "objecthash-ring")]
+impl Default for ListHasher<hasher::ring::Hasher> {
+    fn default() -> Self {
+        ListHasher::new(hasher::default())
+    }
+}
+
+/// Hashes a dict one member at a time. Members can be inserted in any order,
+/// since only the digest of each is kept until they're sorted by `finish`
+pub struct DictHasher<H: ObjectHasher> {
+    hasher: H,
+    members: Vec<[u8; 2 * MAX_OUTPUT_LEN]>,
+}
+
+impl<H: ObjectHasher> DictHasher<H> {
+    /// Start hashing a dict into the given hasher
+    pub fn new(hasher: H) -> Self {
+        DictHasher {
+            hasher,
+            members: Vec::new(),
+        }
+    }
+
+    /// Hash the next member of the dict
+    pub fn insert<K, V>(&mut self, key: &K, value: &V) -> &mut Self
+        where K: ObjectHash + ?Sized,
+              V: ObjectHash + ?Sized
+    {
+        self.members.push(hash_member(key, value, &self.hasher));
+        self
+    }
+
+    /// Add the next member of the dict given the digests of its key and
+    /// value
+    pub fn insert_digests(&mut self, key: &Digest, value: &Digest) -> &mut Self {
+        self.members.push(dict_member(key, value));
+        self
+    }
+
+    /// Number of members hashed so far
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Finish hashing, returning the digest of the dict
+    pub fn finish(self) -> Digest {
+        self.into_hasher().finish()
+    }
+
+    /// Finish hashing, returning the digest of the dict or the first error
+    /// recorded while hashing it, such as a duplicate key
+    pub fn try_finish(self) -> Result<Digest, Error> {
+        self.into_hasher().try_finish()
+    }
+
+    fn into_hasher(mut self) -> H {
+        hash_dict_members(&mut self.members, &mut self.hasher);
+        self.hasher
+    }
+}
+
+#[cfg(feature = "objecthash-ring")]
+impl Default for DictHasher<hasher::ring::Hasher> {
+    fn default() -> Self {
+        DictHasher::new(hasher::default())
+    }
+}
+
+/// Hash the key-value pairs produced by an iterator as a dict, without
+/// collecting them into a map. Only the digests of the members are kept, to
+/// be sorted. Keys which appear more than once are hashed as distinct
+/// members, unless the hasher's profile rejects duplicate keys
+pub fn hash_dict_from_iter<K, V, I, H>(iter: I, hasher: &mut H)
+    where K: ObjectHash,
+          V: ObjectHash,
+          I: IntoIterator<Item = (K, V)>,
+          H: ObjectHasher
+{
+    hash_dict(iter, hasher);
+}
+
+/// Compute the digest of the key-value pairs produced by an iterator as a
+/// dict, using the default hasher
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_dict_from_iter<K, V, I>(iter: I) -> Digest
+    where K: ObjectHash,
+          V: ObjectHash,
+          I: IntoIterator<Item = (K, V)>
+{
+    let mut hasher = hasher::default();
+    hash_dict_from_iter(iter, &mut hasher);
+    hasher.finish()
+}
+
+/// Hash the elements produced by an iterator as a list, without collecting
+/// them
+pub fn hash_list_from_iter<T, I, H>(iter: I, hasher: &mut H)
+    where T: ObjectHash,
+          I: IntoIterator<Item = T>,
+          H: ObjectHasher
+{
+    hasher.update(LIST_TAG);
+
+    for element in iter {
+        hasher.update_nested(|h| element.objecthash(h));
+    }
+}
+
+/// Compute the digest of the elements produced by an iterator as a list,
+/// using the default hasher
+#[cfg(feature = "objecthash-ring")]
+pub fn digest_list_from_iter<T, I>(iter: I) -> Digest
+    where T: ObjectHash,
+          I: IntoIterator<Item = T>
+{
+    let mut hasher = hasher::default();
+    hash_list_from_iter(iter, &mut hasher);
+    hasher.finish()
+}
+
+/// Hashes an octet string fed to it in chunks, e.g. through its `io::Write`
+/// impl
+#[cfg(feature = "octet-strings")]
+pub struct OctetsHasher<H: ObjectHasher> {
+    hasher: H,
+    len: u64,
+}
+
+#[cfg(feature = "octet-strings")]
+impl<H: ObjectHasher> OctetsHasher<H> {
+    /// Start hashing an octet string into the given hasher
+    pub fn new(mut hasher: H) -> Self {
+        update_extension_tag(&mut hasher, Extension::Octets);
+        OctetsHasher { hasher, len: 0 }
+    }
+
+    /// Hash the next chunk of the octet string
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+        self.len += bytes.len() as u64;
+        check_len(self.len, &mut self.hasher);
+    }
+
+    /// Number of bytes hashed so far
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Finish hashing, returning the digest of the octet string
+    pub fn finish(self) -> Digest {
+        self.hasher.finish()
+    }
+
+    /// Finish hashing, returning the digest of the octet string or the
+    /// error recorded while hashing it, such as a string over the profile's
+    /// length limit
+    pub fn try_finish(self) -> Result<Digest, Error> {
+        self.hasher.try_finish()
+    }
+}
+
+#[cfg(all(feature = "objecthash-ring", feature = "octet-strings"))]
+impl Default for OctetsHasher<hasher::ring::Hasher> {
+    fn default() -> Self {
+        OctetsHasher::new(hasher::default())
+    }
+}
+
+#[cfg(feature = "octet-strings")]
+impl<H: ObjectHasher> Write for OctetsHasher<H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes bytes into a hasher as they are, without a tag. For implementing
+/// `ObjectHash` with an encoder which writes to an `io::Write`, after
+/// hashing the tag of the encoded value
+pub struct RawWriter<'a, H: ObjectHasher + 'a> {
+    hasher: &'a mut H,
+}
+
+impl<'a, H: ObjectHasher> RawWriter<'a, H> {
+    pub fn new(hasher: &'a mut H) -> Self {
+        RawWriter { hasher }
+    }
+}
+
+impl<'a, H: ObjectHasher> Write for RawWriter<'a, H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes everything written to it into a hasher as a single octet string.
+/// Unlike `OctetsHasher`, it borrows the hasher, so it can be used inside
+/// an `ObjectHash` impl
+#[cfg(feature = "octet-strings")]
+pub struct OctetsWriter<'a, H: ObjectHasher + 'a> {
+    hasher: &'a mut H,
+    len: u64,
+}
+
+#[cfg(feature = "octet-strings")]
+impl<'a, H: ObjectHasher> OctetsWriter<'a, H> {
+    /// Start hashing an octet string into the given hasher
+    pub fn new(hasher: &'a mut H) -> Self {
+        update_extension_tag(hasher, Extension::Octets);
+        OctetsWriter { hasher, len: 0 }
+    }
+
+    /// Number of bytes hashed so far
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(feature = "octet-strings")]
+impl<'a, H: ObjectHasher> Write for OctetsWriter<'a, H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.update(buf);
+        self.len += buf.len() as u64;
+        check_len(self.len, self.hasher);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hash everything read from the reader as a single octet string. Reading
+/// stops early if it exceeds the hasher's length limit
+#[cfg(feature = "octet-strings")]
+pub fn hash_reader<R, H>(reader: &mut R, hasher: &mut H) -> io::Result<u64>
+    where R: Read + ?Sized,
+          H: ObjectHasher
+{
+    update_extension_tag(hasher, Extension::Octets);
+
+    let mut buf = [0u8; 8192];
+    let mut len = 0;
+
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(len),
+            Ok(n) => {
+                hasher.update(&buf[..n]);
+                len += n as u64;
+
+                // Don't keep reading an oversized stream just to hash it
+                if !check_len(len, hasher) {
+                    return Ok(len);
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Compute the digest of everything read from the reader as a single octet
+/// string, using the default hasher
+#[cfg(all(feature = "objecthash-ring", feature = "octet-strings"))]
+pub fn digest_reader<R: Read + ?Sized>(reader: &mut R) -> Result<Digest, Error> {
+    let mut hasher = hasher::default();
+    hash_reader(reader, &mut hasher)?;
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{digest_dict_from_iter, digest_list_from_iter, hash_dict_from_iter, DictHasher, ListHasher};
+    use {digest, hasher, Error, HashProfile, ObjectHasher};
+
+    #[test]
+    fn hashing_iterators() {
+        let list: Vec<String> = (0..100).map(|i| format!("row {}", i)).collect();
+
+        assert_eq!(digest_list_from_iter((0..100).map(|i| format!("row {}", i))), digest(&list));
+        assert_eq!(digest_list_from_iter(&list), digest(&list));
+        assert_eq!(digest_list_from_iter(Vec::<String>::new()), digest(&Vec::<String>::new()));
+    }
+
+    #[test]
+    fn hashing_pairs() {
+        let map: HashMap<String, u64> = (0..100).map(|i| (format!("key {}", i), i)).collect();
+
+        assert_eq!(digest_dict_from_iter((0..100).rev().map(|i| (format!("key {}", i), i))), digest(&map));
+        assert_eq!(digest_dict_from_iter(&map), digest(&map));
+        assert_eq!(digest_dict_from_iter(Vec::<(String, u64)>::new()), digest(&HashMap::<String, u64>::new()));
+
+        // Repeated keys are only rejected if the profile says to
+        let rows = [("id", 1), ("id", 2)];
+        let mut hasher = hasher::default().with_profile(HashProfile::default().with_reject_duplicate_keys(true));
+        hash_dict_from_iter(rows.iter().cloned(), &mut hasher);
+        match hasher.try_finish() {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, digest("id")),
+            other => panic!("expected a duplicate key, got {:?}", other),
+        }
+
+        let mut hasher = hasher::default();
+        hash_dict_from_iter(rows.iter().cloned(), &mut hasher);
+        assert!(hasher.try_finish().is_ok());
+    }
+
+    #[test]
+    fn hashing_incrementally() {
+        let list = vec!["foo".to_owned(), "bar".to_owned(), "baz".to_owned()];
+
+        let mut hasher = ListHasher::default();
+        assert!(hasher.is_empty());
+        hasher.push("foo").push(&list[1]).push_digest(&digest("baz"));
+        assert_eq!(hasher.len(), 3);
+        assert_eq!(hasher.finish(), digest(&list));
+
+        let mut map = HashMap::new();
+        map.insert("foo".to_owned(), vec![1, 2]);
+        map.insert("bar".to_owned(), vec![]);
+        map.insert("baz".to_owned(), vec![3]);
+
+        let mut hasher = DictHasher::default();
+        assert!(hasher.is_empty());
+        hasher.insert("baz", &vec![3]).insert("foo", &map["foo"]);
+        hasher.insert_digests(&digest("bar"), &digest(&Vec::<i32>::new()));
+        assert_eq!(hasher.len(), 3);
+        assert_eq!(hasher.finish(), digest(&map));
+        assert_eq!(DictHasher::default().finish(), digest(&HashMap::<String, i32>::new()));
+
+        let profile = HashProfile::default().with_reject_duplicate_keys(true);
+        let mut hasher = DictHasher::new(hasher::default().with_profile(profile));
+        hasher.insert("foo", &1).insert("foo", &2);
+        match hasher.try_finish() {
+            Err(Error::DuplicateKey(key)) => assert_eq!(key, digest("foo")),
+            other => panic!("expected a duplicate key, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "octet-strings")]
+    #[test]
+    fn hashing_octets() {
+        use std::io::{self, Write};
+        use super::{digest_reader, OctetsHasher};
+        use {Limit, Limits};
+
+        let blob: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+        let expected = digest(&blob[..]);
+
+        assert_eq!(digest_reader(&mut &blob[..]).unwrap(), expected);
+        assert_eq!(digest_reader(&mut io::empty()).unwrap(), digest(&[][..]));
+
+        let mut hasher = OctetsHasher::default();
+        for chunk in blob.chunks(1000) {
+            hasher.write_all(chunk).unwrap();
+        }
+        assert_eq!(hasher.len(), 20000);
+        assert_eq!(hasher.finish(), expected);
+
+        let mut hasher = OctetsHasher::default();
+        io::copy(&mut &blob[..], &mut hasher).unwrap();
+        assert_eq!(hasher.try_finish().unwrap(), expected);
+
+        let profile = HashProfile::default().with_limits(Limits::default().with_max_len(2500));
+        let mut hasher = OctetsHasher::new(hasher::default().with_profile(profile));
+        for chunk in blob.chunks(1000) {
+            hasher.write_all(chunk).unwrap();
+        }
+        match hasher.try_finish() {
+            Err(Error::LimitExceeded(Limit::Length)) => (),
+            other => panic!("expected a length limit error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn writing_raw_bytes() {
+        use std::io::Write;
+        use super::RawWriter;
+        use {hasher, ObjectHasher};
+        use types::STRING_TAG;
+
+        // An encoder which writes a string's bytes hashes it like a string
+        let mut hasher = hasher::default();
+        hasher.update(STRING_TAG);
+        write!(RawWriter::new(&mut hasher), "foo-{}", 42).unwrap();
+        assert_eq!(hasher.finish(), digest("foo-42"));
+    }
+
+    #[cfg(feature = "octet-strings")]
+    #[test]
+    fn writing_octets() {
+        use std::io::Write;
+        use super::OctetsWriter;
+        use {hasher, ObjectHash, ObjectHasher};
+
+        struct Encoded(Vec<u32>);
+
+        impl ObjectHash for Encoded {
+            fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+                let mut writer = OctetsWriter::new(hasher);
+
+                for n in &self.0 {
+                    writer.write_all(&n.to_be_bytes()).unwrap();
+                }
+            }
+        }
+
+        let expected: Vec<u8> = vec![0, 0, 0, 1, 0, 0, 1, 0];
+        assert_eq!(digest(&Encoded(vec![1, 256])), digest(&expected[..]));
+
+        let mut hasher = hasher::default();
+        let mut writer = OctetsWriter::new(&mut hasher);
+        assert!(writer.is_empty());
+        writer.write_all(b"foo").unwrap();
+        assert_eq!(writer.len(), 3);
+        assert_eq!(hasher.finish(), digest(&b"foo"[..]));
+    }
+}
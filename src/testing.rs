@@ -0,0 +1,427 @@
+//! Finding where the digests of two values diverge
+//!
+//! When two values which should hash the same don't, their root digests say
+//! nothing about why. `first_difference` hashes both with a `TraceHasher`,
+//! which records the digest of every nested value along with the structure
+//! it was hashed in, and walks the two traces down to the first path at
+//! which they differ: a dict member with another value in each, or present
+//! in only one of them, a list element, or a whole value of another type.
+//! `assert_objecthash_eq!` panics with that path and the two digests there.
+//!
+//! Dict members are matched by the strings (or integers) they're keyed by,
+//! and reported under them, so values built from different types (a struct
+//! and the `BTreeMap` a peer's JSON was parsed into, say) can be compared.
+//! Members with other keys are named by the hex digests of their keys.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use {hasher, hex, Digest, Error, HashProfile, ObjectHash, ObjectHasher};
+use path::{Path, Segment};
+use types::{DICT_TAG, INTEGER_TAG, LIST_TAG, STRING_TAG};
+
+// How many of the bytes hashed into each value are kept, to name dict keys
+const MAX_RECORDED_LEN: usize = 256;
+
+#[derive(Debug, Default)]
+struct Node {
+    bytes: Vec<u8>,
+    children: Vec<usize>,
+    digest: Option<Digest>,
+}
+
+/// Hasher which records the digests of the values nested within the one it
+/// hashes, as well as hashing it with the default hasher
+pub struct TraceHasher {
+    inner: hasher::ring::Hasher,
+    node: usize,
+    nodes: Rc<RefCell<Vec<Node>>>,
+}
+
+impl TraceHasher {
+    pub fn new() -> Self {
+        TraceHasher {
+            inner: hasher::default(),
+            node: 0,
+            nodes: Rc::new(RefCell::new(vec![Node::default()])),
+        }
+    }
+
+    /// Hash with the given profile
+    pub fn with_profile(mut self, profile: HashProfile) -> Self {
+        self.inner = self.inner.with_profile(profile);
+        self
+    }
+
+    /// Finish hashing, returning the trace of the value
+    pub fn finish_trace(self) -> Trace {
+        let nodes = self.nodes.clone();
+        self.finish();
+
+        let nodes = Rc::try_unwrap(nodes)
+            .map(RefCell::into_inner)
+            .unwrap_or_else(|nodes| nodes.borrow_mut().drain(..).collect());
+
+        Trace { nodes }
+    }
+}
+
+impl Default for TraceHasher {
+    fn default() -> Self {
+        TraceHasher::new()
+    }
+}
+
+impl ObjectHasher for TraceHasher {
+    #[inline]
+    fn output_len(&self) -> usize {
+        self.inner.output_len()
+    }
+
+    fn nested(&self) -> Self {
+        let mut nodes = self.nodes.borrow_mut();
+        let node = nodes.len();
+        nodes.push(Node::default());
+        nodes[self.node].children.push(node);
+
+        TraceHasher {
+            inner: self.inner.nested(),
+            node,
+            nodes: self.nodes.clone(),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        let mut nodes = self.nodes.borrow_mut();
+        let recorded = &mut nodes[self.node].bytes;
+        let len = bytes.len().min(MAX_RECORDED_LEN.saturating_sub(recorded.len()));
+        recorded.extend_from_slice(&bytes[..len]);
+
+        self.inner.update(bytes);
+    }
+
+    fn update_nested<F>(&mut self, nested: F)
+        where F: Fn(&mut Self)
+    {
+        let mut nested_hasher = self.nested();
+        nested(&mut nested_hasher);
+        self.update(nested_hasher.finish().as_ref());
+    }
+
+    #[inline]
+    fn profile(&self) -> HashProfile {
+        self.inner.profile()
+    }
+
+    #[inline]
+    fn tag(&self) -> Option<u8> {
+        self.inner.tag()
+    }
+
+    #[inline]
+    fn fail(&mut self, error: Error) {
+        self.inner.fail(error);
+    }
+
+    #[inline]
+    fn enter_shared(&mut self, address: usize) -> bool {
+        self.inner.enter_shared(address)
+    }
+
+    #[inline]
+    fn leave_shared(&mut self) {
+        self.inner.leave_shared();
+    }
+
+    fn redact(&mut self, digest: &Digest) {
+        self.inner.redact(digest);
+    }
+
+    fn finish(self) -> Digest {
+        let digest = self.inner.finish();
+        self.nodes.borrow_mut()[self.node].digest = Some(digest);
+        digest
+    }
+
+    fn try_finish(self) -> Result<Digest, Error> {
+        let digest = self.inner.try_finish()?;
+        self.nodes.borrow_mut()[self.node].digest = Some(digest);
+        Ok(digest)
+    }
+}
+
+/// The digests of a value and of the values nested within it
+#[derive(Debug)]
+pub struct Trace {
+    nodes: Vec<Node>,
+}
+
+impl Trace {
+    /// Hash a value with the default hasher, recording its trace
+    pub fn of<T: ObjectHash + ?Sized>(value: &T) -> Self {
+        let mut hasher = TraceHasher::new();
+        value.objecthash(&mut hasher);
+        hasher.finish_trace()
+    }
+
+    /// The digest of the value
+    pub fn digest(&self) -> Digest {
+        self.digest_of(0)
+    }
+
+    fn digest_of(&self, node: usize) -> Digest {
+        self.nodes[node].digest.expect("nested hasher dropped before finishing")
+    }
+
+    fn tag_of(&self, node: usize) -> Option<u8> {
+        self.nodes[node].bytes.first().cloned()
+    }
+
+    // Redacted and memoized values are hashed without a tag of their own,
+    // around a nested hasher which computed the digest they stand for
+    fn resolve(&self, mut node: usize) -> usize {
+        while self.tag_of(node).is_none() && self.nodes[node].children.len() == 1 {
+            let child = self.nodes[node].children[0];
+
+            if self.nodes[child].digest != self.nodes[node].digest {
+                break;
+            }
+
+            node = child;
+        }
+
+        node
+    }
+
+    // The members of a dict by the names of their keys, if it was hashed as
+    // a sequence of key and value hashers
+    fn members(&self, node: usize) -> Option<BTreeMap<String, usize>> {
+        let children = &self.nodes[node].children;
+
+        if !children.len().is_multiple_of(2) {
+            return None;
+        }
+
+        let members: BTreeMap<String, usize> = children.chunks(2)
+            .map(|pair| (self.key_name(pair[0]), pair[1]))
+            .collect();
+
+        Some(members).filter(|members| members.len() * 2 == children.len())
+    }
+
+    fn key_name(&self, node: usize) -> String {
+        let bytes = &self.nodes[node].bytes;
+
+        match bytes.split_first() {
+            Some((&tag, text)) if tag == STRING_TAG[0] || tag == INTEGER_TAG[0] => {
+                if let Ok(text) = ::std::str::from_utf8(text) {
+                    if bytes.len() < MAX_RECORDED_LEN {
+                        return text.to_owned();
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        hex::encode(self.digest_of(node).as_ref())
+    }
+}
+
+/// Where the digests of two values first differ, and what they are there.
+/// A digest is missing where a list element or dict member is only present
+/// in one of the values
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub path: Path,
+    pub left: Option<Digest>,
+    pub right: Option<Digest>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let describe = |digest: &Option<Digest>| match *digest {
+            Some(ref digest) => hex::encode(digest.as_ref()),
+            None => "(missing)".to_owned(),
+        };
+
+        let path = if self.path.is_root() { "/".to_owned() } else { self.path.to_string() };
+        write!(f, "objecthashes differ at {}: {} != {}", path, describe(&self.left), describe(&self.right))
+    }
+}
+
+/// Find the first path at which the digests of two values differ, if they
+/// don't hash the same
+pub fn first_difference<A, B>(left: &A, right: &B) -> Option<Difference>
+    where A: ObjectHash + ?Sized,
+          B: ObjectHash + ?Sized
+{
+    trace_difference(&Trace::of(left), &Trace::of(right))
+}
+
+/// Find the first path at which two traces differ, if they don't have the
+/// same digest
+pub fn trace_difference(left: &Trace, right: &Trace) -> Option<Difference> {
+    let mut path = Path::root();
+    difference_at(left, 0, right, 0, &mut path)
+}
+
+fn difference_at(left: &Trace, l: usize, right: &Trace, r: usize, path: &mut Path) -> Option<Difference> {
+    if left.digest_of(l) == right.digest_of(r) {
+        return None;
+    }
+
+    let (l, r) = (left.resolve(l), right.resolve(r));
+    let whole = |path: &Path| {
+        Some(Difference {
+            path: path.clone(),
+            left: Some(left.digest_of(l)),
+            right: Some(right.digest_of(r)),
+        })
+    };
+
+    match (left.tag_of(l), right.tag_of(r)) {
+        (Some(a), Some(b)) if a == LIST_TAG[0] && b == LIST_TAG[0] => {
+            let (elements, other_elements) = (&left.nodes[l].children, &right.nodes[r].children);
+
+            for index in 0..elements.len().max(other_elements.len()) {
+                path.push(Segment::Index(index));
+
+                let difference = match (elements.get(index), other_elements.get(index)) {
+                    (Some(&x), Some(&y)) => difference_at(left, x, right, y, path),
+                    (x, y) => {
+                        Some(Difference {
+                            path: path.clone(),
+                            left: x.map(|&x| left.digest_of(x)),
+                            right: y.map(|&y| right.digest_of(y)),
+                        })
+                    }
+                };
+
+                path.pop();
+
+                if difference.is_some() {
+                    return difference;
+                }
+            }
+
+            whole(path)
+        }
+        (Some(a), Some(b)) if a == DICT_TAG[0] && b == DICT_TAG[0] => {
+            let (members, other_members) = match (left.members(l), right.members(r)) {
+                (Some(m), Some(n)) => (m, n),
+                _ => return whole(path),
+            };
+
+            let mut keys: Vec<&String> = members.keys().chain(other_members.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                path.push(Segment::Key(key.clone()));
+
+                let difference = match (members.get(key), other_members.get(key)) {
+                    (Some(&x), Some(&y)) => difference_at(left, x, right, y, path),
+                    (x, y) => {
+                        Some(Difference {
+                            path: path.clone(),
+                            left: x.map(|&x| left.digest_of(x)),
+                            right: y.map(|&y| right.digest_of(y)),
+                        })
+                    }
+                };
+
+                path.pop();
+
+                if difference.is_some() {
+                    return difference;
+                }
+            }
+
+            whole(path)
+        }
+        _ => whole(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::{first_difference, Difference, Trace};
+    use path::Path;
+    use {digest, Memoized, Redactable};
+
+    fn user(name: &str, email: &str) -> HashMap<&'static str, Vec<String>> {
+        let mut user = HashMap::new();
+        user.insert("name", vec![name.to_owned()]);
+        user.insert("emails", vec!["alice@example.com".to_owned(), email.to_owned()]);
+        user
+    }
+
+    #[test]
+    fn tracing_values() {
+        let value = vec![user("Alice", "alice@example.org")];
+        assert_eq!(Trace::of(&value).digest(), digest(&value));
+        assert_eq!(first_difference(&value, &value), None);
+
+        // Values built from other types which hash the same don't differ
+        let mut other = BTreeMap::new();
+        other.insert("emails".to_owned(), vec!["alice@example.com", "alice@example.org"]);
+        other.insert("name".to_owned(), vec!["Alice"]);
+        assert_eq!(first_difference(&value, &vec![other]), None);
+    }
+
+    #[test]
+    fn finding_differences() {
+        let left = vec![user("Alice", "alice@example.org"), user("Bob", "bob@example.org")];
+        let right = vec![user("Alice", "alice@example.org"), user("Bob", "bob@example.net")];
+
+        let difference = first_difference(&left, &right).unwrap();
+        assert_eq!(difference.path, Path::root().index(1).key("emails").index(1));
+        assert_eq!(difference.left, Some(digest("bob@example.org")));
+        assert_eq!(difference.right, Some(digest("bob@example.net")));
+        assert_eq!(difference.to_string(),
+                   format!("objecthashes differ at /1/emails/1: {} != {}",
+                           ::hex::encode(digest("bob@example.org").as_ref()),
+                           ::hex::encode(digest("bob@example.net").as_ref())));
+
+        // Missing elements and members
+        let difference = first_difference(&left, &left[..1].to_vec()).unwrap();
+        assert_eq!(difference.path, Path::root().index(1));
+        assert_eq!((difference.left, difference.right), (Some(digest(&left[1])), None));
+
+        let mut extra = user("Alice", "alice@example.org");
+        extra.insert("phone", vec![]);
+        let difference = first_difference(&left[0], &extra).unwrap();
+        assert_eq!(difference.path, Path::root().key("phone"));
+        assert_eq!((difference.left, difference.right), (None, Some(digest(&Vec::<String>::new()))));
+
+        // Values of different types differ as a whole
+        assert_eq!(first_difference(&left[0], "Alice"),
+                   Some(Difference {
+                       path: Path::root(),
+                       left: Some(digest(&left[0])),
+                       right: Some(digest("Alice")),
+                   }));
+    }
+
+    #[test]
+    fn redacted_values() {
+        let mut fields = vec![Redactable::Clear("foo".to_owned()), Redactable::Clear("bar".to_owned())];
+        let memoized = vec![Memoized::new("foo".to_owned()), Memoized::new("baz".to_owned())];
+        assert_eq!(first_difference(&fields, &memoized).unwrap().path, Path::root().index(1));
+
+        fields[1].redact();
+        assert_eq!(first_difference(&fields, &memoized).unwrap().path, Path::root().index(1));
+        assert_eq!(first_difference(&fields, &vec!["foo", "bar"]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "objecthashes differ at /name/0")]
+    fn assertions() {
+        assert_objecthash_eq!(user("Alice", "alice@example.org"), user("Alice", "alice@example.org"));
+        assert_objecthash_eq!(user("Alice", "alice@example.org"), user("Bob", "alice@example.org"));
+    }
+}
@@ -0,0 +1,199 @@
+use std::convert::TryFrom;
+
+use apache_avro::Decimal;
+use apache_avro::types::Value;
+
+use {ObjectHash, ObjectHasher};
+use types::{hash_dict, Extension};
+
+const MILLIS: u32 = 1_000_000;
+const MICROS: u32 = 1_000;
+
+impl ObjectHash for Value {
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            Value::Null => ().objecthash(hasher),
+            Value::Boolean(b) => b.objecthash(hasher),
+            Value::Int(i) => i.objecthash(hasher),
+            Value::Long(l) => l.objecthash(hasher),
+            Value::Float(f) => f.objecthash(hasher),
+            Value::Double(d) => d.objecthash(hasher),
+            Value::Bytes(ref b) |
+            Value::Fixed(_, ref b) => b[..].objecthash(hasher),
+            Value::String(ref s) => s.objecthash(hasher),
+            // Enums hash as their symbol and unions as the selected value, so
+            // digests survive reordering symbols or branches in the schema
+            Value::Enum(_, ref symbol) => symbol.objecthash(hasher),
+            Value::Union(_, ref value) => value.objecthash(hasher),
+            Value::Array(ref a) => a.objecthash(hasher),
+            Value::Map(ref m) => hash_dict(m.iter(), hasher),
+            Value::Record(ref fields) => hash_dict(fields.iter().map(|(k, v)| (k, v)), hasher),
+            Value::Date(days) => hash_timestamp(Some(i64::from(days) * 86_400), None, false, hasher),
+            Value::TimeMillis(ms) => hash_timestamp(None, Some((i64::from(ms), MILLIS)), false, hasher),
+            Value::TimeMicros(us) => hash_timestamp(None, Some((us, MICROS)), false, hasher),
+            Value::TimestampMillis(ms) => hash_instant(ms, MILLIS, true, hasher),
+            Value::TimestampMicros(us) => hash_instant(us, MICROS, true, hasher),
+            Value::TimestampNanos(ns) => hash_instant(ns, 1, true, hasher),
+            Value::LocalTimestampMillis(ms) => hash_instant(ms, MILLIS, false, hasher),
+            Value::LocalTimestampMicros(us) => hash_instant(us, MICROS, false, hasher),
+            Value::LocalTimestampNanos(ns) => hash_instant(ns, 1, false, hasher),
+            Value::Decimal(ref d) => hash_decimal(d, hasher),
+            Value::BigDecimal(ref d) => d.to_string().objecthash(hasher),
+            Value::Duration(ref d) => <[u8; 12]>::from(d)[..].objecthash(hasher),
+            Value::Uuid(ref u) => u.hyphenated().to_string().objecthash(hasher),
+        }
+    }
+}
+
+// Decimals hash as the minimal two's complement encoding of their unscaled
+// value, so fixed and bytes encodings of the same decimal agree. The scale
+// lives in the schema and isn't part of the digest
+fn hash_decimal<H: ObjectHasher>(decimal: &Decimal, hasher: &mut H) {
+    // Only a zero decoded from empty bytes fails to re-encode
+    let bytes = Vec::try_from(decimal).unwrap_or_else(|_| vec![0]);
+    let mut start = 0;
+
+    while start + 1 < bytes.len() &&
+          (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0 ||
+           bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0) {
+        start += 1;
+    }
+
+    objecthash_extension!(hasher, Extension::Octets, &bytes[start..]);
+}
+
+// Split a count of `unit` nanoseconds since the epoch into a date and time
+fn hash_instant<H: ObjectHasher>(count: i64, unit: u32, utc: bool, hasher: &mut H) {
+    let per_day = 86_400 * (1_000_000_000 / i64::from(unit));
+    let days = count.div_euclid(per_day);
+
+    hash_timestamp(Some(days * 86_400),
+                   Some((count.rem_euclid(per_day), unit)),
+                   utc,
+                   hasher);
+}
+
+// Hash a date (as seconds since the epoch, on a day boundary) and/or a time
+// of day (as a count of `unit` nanoseconds) as canonical RFC 3339 text
+fn hash_timestamp<H: ObjectHasher>(date: Option<i64>, time: Option<(i64, u32)>, utc: bool, hasher: &mut H) {
+    let mut text = String::with_capacity(35);
+
+    if let Some(seconds) = date {
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+        text.push_str(&format!("{:04}-{:02}-{:02}", year, month, day));
+    }
+
+    if let Some((count, unit)) = time {
+        if date.is_some() {
+            text.push('T');
+        }
+
+        let nanos = count * i64::from(unit);
+        let seconds = nanos / 1_000_000_000;
+
+        text.push_str(&format!("{:02}:{:02}:{:02}",
+                               seconds / 3600,
+                               seconds / 60 % 60,
+                               seconds % 60));
+
+        let nanosecond = nanos % 1_000_000_000;
+        if nanosecond != 0 {
+            text.push_str(format!(".{:09}", nanosecond).trim_end_matches('0'));
+        }
+    }
+
+    if utc {
+        text.push('Z');
+    }
+
+    objecthash_extension!(hasher, Extension::Timestamp, text.as_bytes());
+}
+
+// Proleptic Gregorian date for a count of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use apache_avro::Decimal;
+    use apache_avro::types::Value;
+
+    use {hasher, ObjectHash, ObjectHasher};
+    use rustc_serialize::hex::ToHex;
+
+    fn h(value: Value) -> String {
+        let mut hasher = hasher::default();
+        value.objecthash(&mut hasher);
+        hasher.finish().as_ref().to_hex()
+    }
+
+    fn record(name: &str, value: Value) -> Value {
+        Value::Record(vec![(name.to_owned(), value)])
+    }
+
+    #[test]
+    fn records() {
+        let mut scores = HashMap::new();
+        scores.insert("a".to_owned(), Value::Double(1.5));
+
+        let value = Value::Record(vec![("name".to_owned(), Value::String("Alice".to_owned())),
+                                       ("favorite".to_owned(), Value::Union(1, Box::new(Value::Long(7)))),
+                                       ("color".to_owned(), Value::Enum(1, "GREEN".to_owned())),
+                                       ("data".to_owned(), Value::Fixed(2, vec![1, 2])),
+                                       ("none".to_owned(), Value::Union(0, Box::new(Value::Null))),
+                                       ("scores".to_owned(), Value::Map(scores)),
+                                       ("tags".to_owned(), Value::Array(vec![Value::String("x".to_owned())]))]);
+
+        // {"name": "Alice", "favorite": 7, "color": "GREEN", "data": b"\x01\x02",
+        //  "none": null, "scores": {"a": 1.5}, "tags": ["x"]}
+        assert_eq!(h(value),
+                   "dece86ea359d7c43ffe01a9ad68620f5ed55422272176ef04b5b02c2220a0712");
+
+        assert_eq!(h(Value::Bytes(vec![1, 2])), h(Value::Fixed(2, vec![1, 2])));
+        assert_eq!(h(Value::Union(3, Box::new(Value::Int(1)))), h(Value::Int(1)));
+    }
+
+    #[test]
+    fn timestamps() {
+        // {"t": <timestamp "1979-05-27T07:32:00Z">}, as in the TOML tests
+        let digest = "fbe6db76650311ff9105dc8e4f3a1e17d6684f897a830bf4568e2ecdc4b91b4c";
+        assert_eq!(h(record("t", Value::TimestampMillis(296_638_320_000))), digest);
+        assert_eq!(h(record("t", Value::TimestampMicros(296_638_320_000_000))), digest);
+        assert_eq!(h(record("t", Value::TimestampNanos(296_638_320_000_000_000))), digest);
+
+        assert_eq!(h(Value::TimestampMillis(-1)),
+                   "06c2ac5fbf28e9ec7caff3f9a1f10244f4e9897ea262c70dc8abe97ae07f9e73");
+        assert_eq!(h(Value::Date(0)),
+                   "3079b95034215bfc50dd8b451e701d144e65630bef211b7bab927609237d948b");
+        assert_eq!(h(Value::TimeMicros(27_120_000_001)),
+                   "e4147f51f454d498e02897b9cff862794a8f47c8bce21ba9c685102fe03b25a4");
+
+        assert_eq!(h(Value::TimeMillis(27_120_500)), h(Value::TimeMicros(27_120_500_000)));
+        assert!(h(Value::LocalTimestampMillis(0)) != h(Value::TimestampMillis(0)));
+    }
+
+    #[test]
+    fn decimals() {
+        // b"\x85" and b"\x00\x80"
+        assert_eq!(h(Value::Decimal(Decimal::from(vec![0xff, 0xff, 0x85]))),
+                   "27a199390d2e1d100a50925fc162ca4af7135d43782c77dc4f3415181780f63e");
+        assert_eq!(h(Value::Decimal(Decimal::from(vec![0x00, 0x00, 0x80]))),
+                   "9de487ee47061b69a061d9ab875f1f7c99ff78ef921223e5915bc1c92c2dc418");
+        assert_eq!(h(Value::Decimal(Decimal::from(Vec::new()))),
+                   h(Value::Decimal(Decimal::from(vec![0, 0]))));
+    }
+}
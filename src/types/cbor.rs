@@ -0,0 +1,103 @@
+use ciborium::value::Value;
+
+use {Error, ObjectHash, ObjectHasher};
+use types::{hash_dict, NULL_TAG};
+
+// Byte strings are hashed as octet strings, and maps as dicts (with keys of
+// any type). Tags are transparent: a tagged value hashes the same as the
+// value it wraps
+impl ObjectHash for Value {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            Value::Integer(ref i) => i128::from(*i).objecthash(hasher),
+            Value::Bytes(ref b) => b[..].objecthash(hasher),
+            Value::Float(ref f) => f.objecthash(hasher),
+            Value::Text(ref s) => s.objecthash(hasher),
+            Value::Bool(ref b) => b.objecthash(hasher),
+            Value::Null => ().objecthash(hasher),
+            Value::Tag(_, ref v) => v.objecthash(hasher),
+            Value::Array(ref a) => a.objecthash(hasher),
+            Value::Map(ref m) => hash_dict(m.iter().map(|(k, v)| (k, v)), hasher),
+
+            // `Value` is non-exhaustive, so values of kinds added after this
+            // was written are an error, and are hashed as null so something
+            // is hashed anyway, like extension types with no tag
+            _ => {
+                hasher.fail(Error::UnsupportedExtension("unknown CBOR"));
+                hasher.update(NULL_TAG);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use ciborium;
+    use ciborium::value::Value;
+
+    use {hasher, ObjectHash, ObjectHasher};
+    use rustc_serialize::hex::ToHex;
+
+    macro_rules! h {
+        ($value:expr) => {
+            {
+                let mut hasher = hasher::default();
+                $value.objecthash(&mut hasher);
+                hasher.finish().as_ref().to_hex()
+            }
+        };
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(h!(Value::Integer(1000.into())), "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b");
+        assert_eq!(h!(Value::Text(String::from("ԱԲաբ"))), "2a2a4485a4e338d8df683971956b1090d2f5d33955a81ecaad1a75125f7a316c");
+        assert_eq!(h!(Value::Bytes(b"hello".to_vec())), "9028cf5555a156ca88740f47a7f8bc794ee0d189d00a3ce7ee0c40142ce405e1");
+        assert_eq!(h!(Value::Null), "1b16b1df538ba12dc3f97edbb85caa7050d46c148134290feba80f8236c83db9");
+        assert_eq!(h!(Value::Tag(24, Box::new(Value::Bytes(b"hello".to_vec())))),
+                   "9028cf5555a156ca88740f47a7f8bc794ee0d189d00a3ce7ee0c40142ce405e1");
+    }
+
+    #[test]
+    fn decoded() {
+        // {"foo": h'010203', "bar": [1, -2, 1.5, null, true]}
+        let mut encoded = Vec::new();
+        let value = Value::Map(vec![(Value::Text(String::from("foo")), Value::Bytes(vec![1, 2, 3])),
+                                    (Value::Text(String::from("bar")),
+                                     Value::Array(vec![Value::Integer(1.into()),
+                                                       Value::Integer((-2).into()),
+                                                       Value::Float(1.5),
+                                                       Value::Null,
+                                                       Value::Bool(true)]))]);
+        ciborium::into_writer(&value, &mut encoded).unwrap();
+
+        let decoded: Value = ciborium::from_reader(&encoded[..]).unwrap();
+        assert_eq!(h!(decoded), "a00b2a32c0e5c35d0d67b4650d6b5665186c097068278ccbd97b2fea49b09c3d");
+    }
+
+    #[test]
+    fn non_string_keys() {
+        // {1: "one", "two": 2}
+        let value = Value::Map(vec![(Value::Text(String::from("two")), Value::Integer(2.into())),
+                                    (Value::Integer(1.into()), Value::Text(String::from("one")))]);
+        assert_eq!(h!(value), "c482f1656b5133b7685a0283e88abc222ba4023363094f11a3e4d2a488e8946d");
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        use {Error, HashProfile};
+
+        // {"a": 1, "a": 2}
+        let value = Value::Map(vec![(Value::Text(String::from("a")), Value::Integer(1.into())),
+                                    (Value::Text(String::from("a")), Value::Integer(2.into()))]);
+
+        let mut hasher = hasher::default().with_profile(HashProfile::default().with_reject_duplicate_keys(true));
+        value.objecthash(&mut hasher);
+        match hasher.try_finish() {
+            Err(Error::DuplicateKey(_)) => (),
+            other => panic!("expected a duplicate key error, got {:?}", other),
+        }
+    }
+}
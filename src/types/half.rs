@@ -0,0 +1,42 @@
+use half::{bf16, f16};
+
+use {ObjectHash, ObjectHasher};
+
+// Half-precision floats are widened to f64 and hashed like any other float, so
+// a value hashes the same whatever precision it's stored at
+impl ObjectHash for f16 {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.to_f64().objecthash(hasher);
+    }
+}
+
+impl ObjectHash for bf16 {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.to_f64().objecthash(hasher);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::f64;
+
+    use half::{bf16, f16};
+
+    use digest;
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(digest(&f16::from_f64(1.5)), digest(&1.5f64));
+        assert_eq!(digest(&bf16::from_f64(-256.0)), digest(&-256.0f64));
+        assert_eq!(digest(&f16::INFINITY), digest(&f64::INFINITY));
+        assert_eq!(digest(&bf16::NAN), digest(&f64::NAN));
+
+        // Values are hashed as they're stored, not as what they were rounded
+        // from
+        assert_eq!(digest(&f16::from_f64(0.1)), digest(&0.0999755859375f64));
+        assert_eq!(digest(&vec![f16::ONE, f16::ZERO]), digest(&vec![1.0f64, 0.0]));
+    }
+}
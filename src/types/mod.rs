@@ -0,0 +1,1228 @@
+use std;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+
+use {Digest, Error, FloatFormat, KeyPolicy, Limit, ObjectHash, ObjectHasher, MAX_OUTPUT_LEN};
+
+use unicode_normalization::{is_nfc_quick, is_nfd_quick, IsNormalized, UnicodeNormalization};
+
+pub const NULL_TAG: &[u8; 1] = b"n";
+pub const BOOLEAN_TAG: &[u8; 1] = b"b";
+pub const INTEGER_TAG: &[u8; 1] = b"i";
+pub const FLOAT_TAG: &[u8; 1] = b"f";
+pub const STRING_TAG: &[u8; 1] = b"u";
+pub const LIST_TAG: &[u8; 1] = b"l";
+pub const SET_TAG: &[u8; 1] = b"s";
+pub const DICT_TAG: &[u8; 1] = b"d";
+
+// Not a type: the outermost digest of a hasher with a context is bound to it
+// with this tag
+pub const CONTEXT_TAG: &[u8; 1] = b"c";
+
+#[cfg(feature = "octet-strings")]
+pub const OCTET_TAG: &[u8; 1] = b"o";
+
+// Non-standard extension: timestamps are hashed as RFC 3339 text in a
+// canonical form, with an uppercase "T" separator, seconds always present,
+// no trailing zeros in fractional seconds, and "Z" for a zero UTC offset.
+// Dates, times, and local date-times omit the parts they don't have
+pub const TIMESTAMP_TAG: &[u8; 1] = b"t";
+
+macro_rules! objecthash_digest {
+    ($hasher:expr, $tag:expr, $bytes:expr) => {
+        $hasher.update($tag);
+        $hasher.update($bytes);
+    };
+}
+
+// Like `objecthash_digest!`, but with the hasher's tag for an extension type
+#[cfg(any(feature = "octet-strings", feature = "toml"))]
+macro_rules! objecthash_extension {
+    ($hasher:expr, $extension:expr, $bytes:expr) => {
+        $crate::types::update_extension_tag($hasher, $extension);
+        $hasher.update($bytes);
+    };
+}
+
+/// Tags for the non-standard types this crate can hash, which applications
+/// need to agree on with their peers.
+///
+/// The default tags are `o` for octet strings and `t` for timestamps. Types
+/// without a tag can't be hashed: hashers report an error from `try_finish`
+/// if they are
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtensionTags {
+    octets: Option<u8>,
+    timestamp: Option<u8>,
+}
+
+impl ExtensionTags {
+    /// No extension types, only those the ObjectHash spec defines
+    pub fn none() -> Self {
+        ExtensionTags {
+            octets: None,
+            timestamp: None,
+        }
+    }
+
+    /// Tag for octet strings, if they can be hashed
+    pub fn octets(&self) -> Option<u8> {
+        self.octets
+    }
+
+    /// Tag for timestamps, if they can be hashed
+    pub fn timestamp(&self) -> Option<u8> {
+        self.timestamp
+    }
+
+    /// Hash octet strings with the given tag, which mustn't be one of the
+    /// spec's tags or used by another extension
+    pub fn with_octets(mut self, tag: u8) -> Result<Self, Error> {
+        self.check(tag, self.timestamp)?;
+        self.octets = Some(tag);
+        Ok(self)
+    }
+
+    /// Hash timestamps with the given tag, which mustn't be one of the
+    /// spec's tags or used by another extension
+    pub fn with_timestamp(mut self, tag: u8) -> Result<Self, Error> {
+        self.check(tag, self.octets)?;
+        self.timestamp = Some(tag);
+        Ok(self)
+    }
+
+    fn check(&self, tag: u8, other: Option<u8>) -> Result<(), Error> {
+        if b"nbifulsdc".contains(&tag) || other == Some(tag) {
+            return Err(Error::InvalidTag(tag));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ExtensionTags {
+    fn default() -> Self {
+        ExtensionTags {
+            octets: Some(b'o'),
+            timestamp: Some(b't'),
+        }
+    }
+}
+
+/// Non-standard types
+#[cfg(any(feature = "octet-strings", feature = "toml"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Extension {
+    #[cfg(feature = "octet-strings")]
+    Octets,
+    #[cfg(any(feature = "avro", feature = "toml"))]
+    Timestamp,
+}
+
+// Tags for extension types come from the hasher. Hashing one it has no tag
+// for is an error, but something has to be hashed anyway, so it's hashed
+// with the default tag
+#[cfg(any(feature = "octet-strings", feature = "toml"))]
+pub(crate) fn update_extension_tag<H: ObjectHasher>(hasher: &mut H, extension: Extension) {
+    let tags = hasher.profile().extension_tags();
+
+    let (tag, default, name) = match extension {
+        #[cfg(feature = "octet-strings")]
+        Extension::Octets => (tags.octets, OCTET_TAG[0], "octet string"),
+        #[cfg(any(feature = "avro", feature = "toml"))]
+        Extension::Timestamp => (tags.timestamp, TIMESTAMP_TAG[0], "timestamp"),
+    };
+
+    match tag {
+        Some(tag) => hasher.update(&[tag]),
+        None => {
+            hasher.fail(Error::UnsupportedExtension(name));
+            hasher.update(&[default]);
+        }
+    }
+}
+
+#[cfg(feature = "avro")]
+mod avro;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "half")]
+mod half;
+#[cfg(feature = "ndarray")]
+mod ndarray;
+#[cfg(feature = "ordered-float")]
+mod ordered_float;
+#[cfg(feature = "semver")]
+mod semver;
+#[cfg(feature = "toml")]
+mod toml;
+
+impl ObjectHash for () {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        objecthash_digest!(hasher, NULL_TAG, b"");
+    }
+}
+
+impl ObjectHash for bool {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let value: &[u8] = if *self { b"1" } else { b"0" };
+        objecthash_digest!(hasher, BOOLEAN_TAG, value);
+    }
+}
+
+// None is hashed as null, and Some(value) the same as the bare value, which
+// matches how optional fields are usually represented in JSON
+impl<T: ObjectHash> ObjectHash for Option<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            Some(ref value) => value.objecthash(hasher),
+            None => ().objecthash(hasher),
+        }
+    }
+}
+
+impl<T: ObjectHash + ?Sized> ObjectHash for &T {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        (**self).objecthash(hasher);
+    }
+}
+
+impl<T: ObjectHash + ?Sized> ObjectHash for &mut T {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        (**self).objecthash(hasher);
+    }
+}
+
+// Borrowed and owned values hash the same, so views assembled from
+// references to existing data (`Vec<&T>`, `HashMap<&str, &T>`,
+// `BTreeMap<Cow<str>, V>`) have the digests of their owned counterparts
+impl<'a, T: ObjectHash + ToOwned + ?Sized> ObjectHash for Cow<'a, T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        (**self).objecthash(hasher);
+    }
+}
+
+impl<T: ObjectHash + ?Sized> ObjectHash for Box<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        (**self).objecthash(hasher);
+    }
+}
+
+// Shared values are hashed as the value they point to, so an allocation
+// which is shared by several parts of a structure is hashed once for each.
+// An `Rc` or `Arc` which leads back to a value containing it (through a
+// `RefCell` or `Mutex`, say) makes a cycle, which is only caught by a hasher
+// whose profile detects cycles: it's hashed as null, with an error recorded
+
+impl<T: ObjectHash + ?Sized> ObjectHash for Rc<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_shared(&**self, hasher);
+    }
+}
+
+impl<T: ObjectHash + ?Sized> ObjectHash for Arc<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_shared(&**self, hasher);
+    }
+}
+
+fn hash_shared<T: ObjectHash + ?Sized, H: ObjectHasher>(value: &T, hasher: &mut H) {
+    if hasher.enter_shared(value as *const T as *const () as usize) {
+        value.objecthash(hasher);
+        hasher.leave_shared();
+    } else {
+        ().objecthash(hasher);
+    }
+}
+
+// Shared mutable values are hashed as whatever they hold while they're
+// borrowed or locked, so hashing a `RefCell` which is mutably borrowed panics,
+// and hashing a lock the current thread holds deadlocks
+
+impl<T: ObjectHash + ?Sized> ObjectHash for RefCell<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.borrow().objecthash(hasher);
+    }
+}
+
+impl<T: ObjectHash + ?Sized> ObjectHash for Mutex<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let guard = self.lock().unwrap_or_else(|poisoned| {
+            check_poisoned(hasher);
+            poisoned.into_inner()
+        });
+
+        guard.objecthash(hasher);
+    }
+}
+
+impl<T: ObjectHash + ?Sized> ObjectHash for RwLock<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let guard = self.read().unwrap_or_else(|poisoned| {
+            check_poisoned(hasher);
+            poisoned.into_inner()
+        });
+
+        guard.objecthash(hasher);
+    }
+}
+
+fn check_poisoned<H: ObjectHasher>(hasher: &mut H) {
+    if hasher.profile().reject_poisoned() {
+        hasher.fail(Error::PoisonedLock);
+    }
+}
+
+impl<T: ObjectHash> ObjectHash for Vec<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hasher.update(LIST_TAG);
+
+        for value in self {
+            hasher.update_nested(|h| value.objecthash(h));
+        }
+    }
+}
+
+// Maps only need their keys and values to be hashable: how the map itself
+// compares or orders keys doesn't matter, since members are ordered by digest
+impl<K: ObjectHash, V: ObjectHash, S> ObjectHash for HashMap<K, V, S> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_dict(self, hasher);
+    }
+}
+
+impl<K: ObjectHash, V: ObjectHash> ObjectHash for BTreeMap<K, V> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_dict(self, hasher);
+    }
+}
+
+/// Hash key-value pairs as a dict, rejecting keys the hasher's profile
+/// doesn't accept and (if it says to) duplicate keys
+pub(crate) fn hash_dict<K, V, I, H>(pairs: I, hasher: &mut H)
+    where K: ObjectHash,
+          V: ObjectHash,
+          I: IntoIterator<Item = (K, V)>,
+          H: ObjectHasher
+{
+    let pairs = pairs.into_iter();
+    let mut members = Vec::with_capacity(pairs.size_hint().0);
+
+    for (k, v) in pairs {
+        members.push(hash_member(&k, &v, hasher));
+    }
+
+    hash_dict_members(&mut members, hasher);
+}
+
+/// Hash a dict member's key and value with hashers nested in the given one,
+/// rejecting the key if the hasher's profile doesn't accept it. Public for
+/// the impls derived by objecthash-derive
+#[doc(hidden)]
+pub fn hash_member<K, V, H>(key: &K, value: &V, hasher: &H) -> [u8; 2 * MAX_OUTPUT_LEN]
+    where K: ObjectHash + ?Sized,
+          V: ObjectHash + ?Sized,
+          H: ObjectHasher
+{
+    let key_digest = key_digest_with(key, hasher);
+
+    let mut value_hasher = hasher.nested();
+    value.objecthash(&mut value_hasher);
+    dict_member(&key_digest, &value_hasher.finish())
+}
+
+/// Digest of a value, hashed with a hasher nested in the given one
+pub(crate) fn digest_with<T: ObjectHash + ?Sized, H: ObjectHasher>(value: &T, hasher: &H) -> Digest {
+    let mut nested_hasher = hasher.nested();
+    value.objecthash(&mut nested_hasher);
+    nested_hasher.finish()
+}
+
+/// Digest of a dict key, hashed with a hasher nested in the given one, which
+/// records an error if the hasher's profile doesn't accept the key
+pub(crate) fn key_digest_with<K: ObjectHash + ?Sized, H: ObjectHasher>(key: &K, hasher: &H) -> Digest {
+    let mut key_hasher = hasher.nested();
+    key.objecthash(&mut key_hasher);
+    check_key(&mut key_hasher);
+    key_hasher.finish()
+}
+
+/// Hash a dict given the concatenated key and value digests of its members,
+/// like those `dict_member` returns. The members are sorted, and checked for
+/// duplicate keys if the hasher's profile says to
+pub(crate) fn hash_dict_members<M, H>(members: &mut [M], hasher: &mut H)
+    where M: AsRef<[u8]> + Ord,
+          H: ObjectHasher
+{
+    let member_len = 2 * hasher.output_len();
+    members.sort_unstable();
+    check_duplicate_keys(members, hasher);
+    hasher.update(DICT_TAG);
+
+    for member in members.iter() {
+        hasher.update(&member.as_ref()[..member_len]);
+    }
+}
+
+/// Hashes dicts from their members for `objecthash_struct!`, which has to
+/// work whether it's given a hasher or a mutable reference to one
+#[doc(hidden)]
+pub trait DictMembers {
+    fn hash_dict_members<M: AsRef<[u8]> + Ord>(&mut self, members: &mut [M]);
+}
+
+impl<H: ObjectHasher> DictMembers for H {
+    #[inline]
+    fn hash_dict_members<M: AsRef<[u8]> + Ord>(&mut self, members: &mut [M]) {
+        hash_dict_members(members, self);
+    }
+}
+
+impl<T, S> ObjectHash for HashSet<T, S>
+    where T: ObjectHash + Eq + std::hash::Hash,
+          S: std::hash::BuildHasher
+{
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_set(self, hasher);
+    }
+}
+
+impl<T: ObjectHash> ObjectHash for BTreeSet<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_set(self, hasher);
+    }
+}
+
+/// Hash the given members as a set: their digests are sorted, and members
+/// with the same digest only count once
+pub(crate) fn hash_set<I, H>(members: I, hasher: &mut H)
+    where I: IntoIterator,
+          I::Item: ObjectHash,
+          H: ObjectHasher
+{
+    let mut digests: Vec<Digest> = members.into_iter()
+        .map(|member| {
+            let mut nested_hasher = hasher.nested();
+            member.objecthash(&mut nested_hasher);
+            nested_hasher.finish()
+        })
+        .collect();
+
+    digests.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    digests.dedup();
+    hasher.update(SET_TAG);
+
+    for digest in &digests {
+        hasher.update(digest.as_ref());
+    }
+}
+
+/// Record an error in the hasher if a string or octet string of the given
+/// length exceeds its profile's limit, returning whether it's within it
+pub(crate) fn check_len<H: ObjectHasher>(len: u64, hasher: &mut H) -> bool {
+    let exceeded = hasher.profile().limits().max_len().is_some_and(|max| len > max as u64);
+
+    if exceeded {
+        hasher.fail(Error::LimitExceeded(Limit::Length));
+    }
+
+    !exceeded
+}
+
+/// Record an error in the hasher a dict key was just hashed into if the key
+/// isn't a string and its profile only accepts string keys
+pub(crate) fn check_key<H: ObjectHasher>(key_hasher: &mut H) {
+    if key_hasher.profile().key_policy() != KeyPolicy::Strings {
+        return;
+    }
+
+    match key_hasher.tag() {
+        Some(tag) if tag != STRING_TAG[0] => key_hasher.fail(Error::NonStringKey(tag)),
+        _ => (),
+    }
+}
+
+/// Record an error in the hasher if two of a dict's sorted members have the
+/// same key digest and its profile rejects duplicate keys. Members with the
+/// same key are next to each other once sorted, since the key comes first
+pub(crate) fn check_duplicate_keys<M, H>(members: &[M], hasher: &mut H)
+    where M: AsRef<[u8]>,
+          H: ObjectHasher
+{
+    if !hasher.profile().reject_duplicate_keys() {
+        return;
+    }
+
+    let key_len = hasher.output_len();
+    let duplicate = members.windows(2)
+        .map(|pair| (&pair[0].as_ref()[..key_len], &pair[1].as_ref()[..key_len]))
+        .find(|&(a, b)| a == b);
+
+    if let Some((key, _)) = duplicate {
+        let key = Digest::new(key).unwrap();
+        hasher.fail(Error::DuplicateKey(key));
+    }
+}
+
+/// The concatenated key and value digests of a dict member, which dicts sort
+/// and hash their members as.
+///
+/// Members are copied into fixed-size arrays rather than each getting its own
+/// allocation. The unused tails are all zero, so they don't affect the order
+/// of members whose digests are the same length.
+pub(crate) fn dict_member(key: &Digest, value: &Digest) -> [u8; 2 * MAX_OUTPUT_LEN] {
+    let (key_bytes, value_bytes) = (key.as_ref(), value.as_ref());
+    let mut member = [0u8; 2 * MAX_OUTPUT_LEN];
+
+    member[..key_bytes.len()].copy_from_slice(key_bytes);
+    member[key_bytes.len()..key_bytes.len() + value_bytes.len()].copy_from_slice(value_bytes);
+    member
+}
+
+/// Unicode normalization applied to strings before they're hashed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Normalization {
+    /// Normalization Form C, as the ObjectHash spec requires
+    #[default]
+    Nfc,
+
+    /// Normalization Form D
+    Nfd,
+
+    /// Hash the UTF-8 bytes of strings as they are, like implementations
+    /// which don't normalize
+    Raw,
+}
+
+impl ObjectHash for str {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        check_len(self.len() as u64, hasher);
+
+        match hasher.profile().canonicalizer() {
+            Some(canonicalizer) => hash_canonical(&canonicalizer.canonicalize(self), hasher),
+            None => hash_canonical(self, hasher),
+        }
+    }
+}
+
+// Hash a string which has been canonicalized, but maybe not normalized
+fn hash_canonical<H: ObjectHasher>(s: &str, hasher: &mut H) {
+    // Most strings are already normalized (and ASCII always is), so only
+    // normalize them when the quick check can't rule it out
+    match hasher.profile().normalization() {
+        Normalization::Raw => (),
+        _ if s.is_ascii() => (),
+        Normalization::Nfc if is_nfc_quick(s.chars()) == IsNormalized::Yes => (),
+        Normalization::Nfc => return hash_normalized(s.nfc(), hasher),
+        Normalization::Nfd if is_nfd_quick(s.chars()) == IsNormalized::Yes => (),
+        Normalization::Nfd => return hash_normalized(s.nfd(), hasher),
+    }
+
+    objecthash_digest!(hasher, STRING_TAG, s.as_bytes());
+}
+
+// Hash the characters of a normalized string through a buffer on the stack,
+// rather than collecting them into a new string
+fn hash_normalized<I, H>(chars: I, hasher: &mut H)
+    where I: Iterator<Item = char>,
+          H: ObjectHasher
+{
+    let mut buf = [0u8; 256];
+    let mut len = 0;
+
+    hasher.update(STRING_TAG);
+
+    for c in chars {
+        if len + c.len_utf8() > buf.len() {
+            hasher.update(&buf[..len]);
+            len = 0;
+        }
+
+        len += c.encode_utf8(&mut buf[len..]).len();
+    }
+
+    hasher.update(&buf[..len]);
+}
+
+impl ObjectHash for String {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.as_str().objecthash(hasher);
+    }
+}
+
+// Technically ObjectHash does not define a representation for binary data
+// For now this is a non-standard extension of ObjectHash
+#[cfg(feature = "octet-strings")]
+impl ObjectHash for [u8] {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        check_len(self.len() as u64, hasher);
+        objecthash_extension!(hasher, Extension::Octets, self);
+    }
+}
+
+macro_rules! impl_signed_inttype (($inttype:ident) => (
+    impl ObjectHash for $inttype {
+        #[inline]
+        fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+            let mut buf = [0u8; INTEGER_BUF_LEN];
+            let digits = format_integer(*self < 0, self.unsigned_abs() as u128, &mut buf);
+            objecthash_digest!(hasher, INTEGER_TAG, digits);
+        }
+    }
+));
+
+macro_rules! impl_unsigned_inttype (($inttype:ident) => (
+    impl ObjectHash for $inttype {
+        #[inline]
+        fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+            let mut buf = [0u8; INTEGER_BUF_LEN];
+            let digits = format_integer(false, *self as u128, &mut buf);
+            objecthash_digest!(hasher, INTEGER_TAG, digits);
+        }
+    }
+));
+
+impl_signed_inttype!(i8);
+impl_signed_inttype!(i16);
+impl_signed_inttype!(i32);
+impl_signed_inttype!(i64);
+impl_unsigned_inttype!(u8);
+impl_unsigned_inttype!(u16);
+impl_unsigned_inttype!(u32);
+impl_unsigned_inttype!(u64);
+impl_signed_inttype!(i128);
+impl_unsigned_inttype!(u128);
+impl_signed_inttype!(isize);
+impl_unsigned_inttype!(usize);
+
+// Enough for the digits of u128::MAX, or a sign and the digits of i128::MIN
+const INTEGER_BUF_LEN: usize = 40;
+
+// Integers are formatted in decimal into a buffer on the stack, rather than
+// allocating a string for every integer hashed
+fn format_integer(negative: bool, mut magnitude: u128, buf: &mut [u8; INTEGER_BUF_LEN]) -> &[u8] {
+    let mut pos = buf.len();
+
+    // 128-bit division is slow, so only use it for the digits which don't
+    // fit in a u64
+    while magnitude > u128::from(u64::MAX) {
+        pos -= 1;
+        buf[pos] = b'0' + (magnitude % 10) as u8;
+        magnitude /= 10;
+    }
+
+    let mut small = magnitude as u64;
+
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (small % 10) as u8;
+        small /= 10;
+
+        if small == 0 {
+            break;
+        }
+    }
+
+    if negative {
+        pos -= 1;
+        buf[pos] = b'-';
+    }
+
+    &buf[pos..]
+}
+
+/// Format a finite double the way ECMAScript's `Number.prototype.toString`
+/// does, as required by JCS and used by `FloatFormat::Decimal`
+pub(crate) fn format_double(value: f64) -> String {
+    if value == 0.0 {
+        return String::from("0");
+    }
+
+    // Rust's exponential formatting yields the shortest digits which round
+    // trip, which is what ECMAScript uses too. Only the layout differs
+    let formatted = format!("{:e}", value.abs());
+    let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap());
+    let digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exponent[1..].parse::<i32>().unwrap() + 1;
+
+    let mut result = String::with_capacity(32);
+
+    if value < 0.0 {
+        result.push('-');
+    }
+
+    if k <= n && n <= 21 {
+        result.push_str(&digits);
+        result.extend((0..n - k).map(|_| '0'));
+    } else if 0 < n && n <= 21 {
+        result.push_str(&digits[..n as usize]);
+        result.push('.');
+        result.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        result.push_str("0.");
+        result.extend((0..-n).map(|_| '0'));
+        result.push_str(&digits);
+    } else {
+        result.push_str(&digits[..1]);
+
+        if k > 1 {
+            result.push('.');
+            result.push_str(&digits[1..]);
+        }
+
+        result.push('e');
+        result.push(if n - 1 < 0 { '-' } else { '+' });
+        result.push_str(&(n - 1).abs().to_string());
+    }
+
+    result
+}
+
+// Floats are normalized to a sign, a binary exponent, and the bits of the
+// mantissa in [0.5, 1), following the reference implementation
+fn normalize_float(mut value: f64) -> String {
+    if value.is_nan() {
+        return String::from("NaN");
+    }
+
+    if value.is_infinite() {
+        return String::from(if value > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+
+    let mut result = String::with_capacity(64);
+
+    if value < 0.0 {
+        result.push('-');
+        value = -value;
+    } else {
+        result.push('+');
+    }
+
+    if value == 0.0 {
+        result.push_str("0:");
+        return result;
+    }
+
+    let mut exponent = 0i32;
+
+    while value > 1.0 {
+        value /= 2.0;
+        exponent += 1;
+    }
+
+    while value <= 0.5 {
+        value *= 2.0;
+        exponent -= 1;
+    }
+
+    result.push_str(&exponent.to_string());
+    result.push(':');
+
+    while value != 0.0 {
+        if value >= 1.0 {
+            result.push('1');
+            value -= 1.0;
+        } else {
+            result.push('0');
+        }
+
+        value *= 2.0;
+    }
+
+    result
+}
+
+impl ObjectHash for f64 {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        if !self.is_finite() && hasher.profile().strict_floats() {
+            hasher.fail(Error::NonFiniteFloat(*self));
+        }
+
+        let formatted = match hasher.profile().float_format() {
+            FloatFormat::Decimal if self.is_finite() => format_double(*self),
+            _ => normalize_float(*self),
+        };
+
+        objecthash_digest!(hasher, FLOAT_TAG, formatted.as_bytes());
+    }
+}
+
+impl ObjectHash for f32 {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        f64::from(*self).objecthash(hasher);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Normalization;
+    use HashProfile;
+    use {hasher, ObjectHash, ObjectHasher};
+    use rustc_serialize::hex::ToHex;
+
+    macro_rules! h {
+       ($value:expr) => {
+            {
+                let mut hasher = hasher::default();
+                $value.objecthash(&mut hasher);
+                hasher.finish().as_ref().to_hex()
+            }
+        };
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(h!(-1), "f105b11df43d5d321f5c773ef904af979024887b4d2b0fab699387f59e2ff01e");
+        assert_eq!(h!(0), "a4e167a76a05add8a8654c169b07b0447a916035aef602df103e8ae0fe2ff390");
+        assert_eq!(h!(10), "73f6128db300f3751f2e509545be996d162d20f9e030864632f85e34fd0324ce");
+        assert_eq!(h!(1000), "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b");
+
+        assert_eq!(h!(-1i8), "f105b11df43d5d321f5c773ef904af979024887b4d2b0fab699387f59e2ff01e");
+        assert_eq!(h!(-1i16), "f105b11df43d5d321f5c773ef904af979024887b4d2b0fab699387f59e2ff01e");
+        assert_eq!(h!(-1i32), "f105b11df43d5d321f5c773ef904af979024887b4d2b0fab699387f59e2ff01e");
+        assert_eq!(h!(-1i64), "f105b11df43d5d321f5c773ef904af979024887b4d2b0fab699387f59e2ff01e");
+        assert_eq!(h!(-1isize), "f105b11df43d5d321f5c773ef904af979024887b4d2b0fab699387f59e2ff01e");
+
+        assert_eq!(h!(10u8), "73f6128db300f3751f2e509545be996d162d20f9e030864632f85e34fd0324ce");
+        assert_eq!(h!(10u16), "73f6128db300f3751f2e509545be996d162d20f9e030864632f85e34fd0324ce");
+        assert_eq!(h!(10u32), "73f6128db300f3751f2e509545be996d162d20f9e030864632f85e34fd0324ce");
+        assert_eq!(h!(10u64), "73f6128db300f3751f2e509545be996d162d20f9e030864632f85e34fd0324ce");
+        assert_eq!(h!(10usize), "73f6128db300f3751f2e509545be996d162d20f9e030864632f85e34fd0324ce");
+
+        assert_eq!(h!(-1i128), "f105b11df43d5d321f5c773ef904af979024887b4d2b0fab699387f59e2ff01e");
+        assert_eq!(h!(10u128), "73f6128db300f3751f2e509545be996d162d20f9e030864632f85e34fd0324ce");
+    }
+
+    #[test]
+    fn formatting_integers() {
+        use super::format_integer;
+
+        let mut buf = [0u8; 40];
+        let cases: &[(bool, u128)] = &[(false, 0), (false, 7), (true, 1), (false, u64::MAX as u128),
+                                       (false, u64::MAX as u128 + 1), (false, u128::MAX),
+                                       (true, i64::MIN.unsigned_abs() as u128),
+                                       (true, i128::MIN.unsigned_abs())];
+
+        for &(negative, magnitude) in cases {
+            let expected = format!("{}{}", if negative { "-" } else { "" }, magnitude);
+            assert_eq!(format_integer(negative, magnitude, &mut buf), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(h!(0.0), "60101d8c9cb988411468e38909571f357daa67bff5a7b0a3f9ae295cd4aba33d");
+        assert_eq!(h!(-0.0), "60101d8c9cb988411468e38909571f357daa67bff5a7b0a3f9ae295cd4aba33d");
+        assert_eq!(h!(1.0), "f01adc732390ab024d64080e0b173f0ee3a1610efbdd4ce2a13bbf8d9b26c639");
+        assert_eq!(h!(-1.0), "f706daa44d7e40e21ea202c36119057924bb28a49949d8ddaa9c8c3c9367e602");
+        assert_eq!(h!(1.5), "7d9d2d2489ee3a73c6e6e7b84469a5f697e902793cbbb3b4b1c0da46b9b4bdec");
+        assert_eq!(h!(1000.0), "09b29bf3f8bea85fbf7dd5b3e185e9c3a007761f8824a54d4d518578c9360419");
+        assert_eq!(h!(0.0001), "1195afc7f0b70bb9d7960c3615668e072a1cbfbbb001f84871fd2e222a87be1d");
+        assert_eq!(h!(-23.1234), "50f96ea3c7eb4c50e0bfe43260283e00aaad6213085a9fd1d92b24471f46d20d");
+        assert_eq!(h!(1e300), "655885d263c8c2b5ba47d6d9c1fe47a1c947b31b6a39718bd11805682eb53415");
+        assert_eq!(h!(5e-324), "6ef2dbe8b6d4a085b4f219e4580db89c2347545b0a99f609ecdc1451316d5fa7");
+
+        assert_eq!(h!(f64::NAN), "5d6c301a98d835732d459d7018a8d546872f7ba3c39a45ba481746d2c6d566d9");
+        assert_eq!(h!(f64::INFINITY), "e0309b2362dc6aaf595338cd9e116761640f74927bcdc4f76e8e6433738f25c7");
+
+        assert_eq!(h!(1.5f32), "7d9d2d2489ee3a73c6e6e7b84469a5f697e902793cbbb3b4b1c0da46b9b4bdec");
+    }
+
+    #[test]
+    fn decimal_floats() {
+        use std::f64;
+        use FloatFormat;
+
+        let decimal = || hasher::default().with_profile(HashProfile::decimal_floats());
+        assert_eq!(HashProfile::decimal_floats().float_format(), FloatFormat::Decimal);
+
+        for &(value, formatted) in &[(1.5, "1.5"), (-0.0, "0"), (1e21, "1e+21"), (0.1, "0.1"), (-23.1234, "-23.1234")] {
+            let mut hasher = decimal();
+            value.objecthash(&mut hasher);
+
+            let mut expected = hasher::default();
+            expected.update(super::FLOAT_TAG);
+            expected.update(formatted.as_bytes());
+            assert_eq!(hasher.finish(), expected.finish());
+        }
+
+        // Non-finite floats still hash the way the profile says
+        let mut hasher = decimal();
+        f64::NAN.objecthash(&mut hasher);
+        assert_eq!(hasher.finish().as_ref().to_hex(), h!(f64::NAN));
+    }
+
+    #[test]
+    fn strict_floats() {
+        use std::f64;
+        use Error;
+
+        let strict = || hasher::default().with_profile(HashProfile::default().with_strict_floats(true));
+
+        let mut hasher = strict();
+        vec![1.5, 2.0].objecthash(&mut hasher);
+        assert_eq!(hasher.finish().as_ref().to_hex(), h!(vec![1.5, 2.0]));
+
+        for &value in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut hasher = strict();
+            vec![1.5, value].objecthash(&mut hasher);
+            match hasher.try_finish() {
+                Err(Error::NonFiniteFloat(n)) => assert!(n.is_nan() && value.is_nan() || n == value),
+                other => panic!("expected a non-finite float error, got {:?}", other),
+            }
+
+            let mut map = HashMap::new();
+            map.insert("x".to_owned(), value as f32);
+            let mut hasher = strict();
+            map.objecthash(&mut hasher);
+            assert!(hasher.try_finish().is_err());
+
+            let mut hasher = hasher::default();
+            value.objecthash(&mut hasher);
+            assert!(hasher.try_finish().is_ok());
+        }
+    }
+
+    #[test]
+    fn extension_tags() {
+        use {Error, ExtensionTags};
+
+        let tags = ExtensionTags::default();
+        assert_eq!((tags.octets(), tags.timestamp()), (Some(b'o'), Some(b't')));
+        assert_eq!(tags.with_octets(b'x').unwrap().octets(), Some(b'x'));
+
+        for &tag in b"nbifulsdct" {
+            match tags.with_octets(tag) {
+                Err(Error::InvalidTag(t)) => assert_eq!(t, tag),
+                other => panic!("expected an invalid tag error, got {:?}", other),
+            }
+        }
+        assert!(ExtensionTags::none().with_timestamp(b'o').is_ok());
+    }
+
+    #[cfg(feature = "octet-strings")]
+    #[test]
+    fn octet_strings() {
+        use {Error, ExtensionTags};
+
+        assert_eq!(h!(&b"foo"[..]), h!(&b"foo".to_vec()[..]));
+
+        let with = |tags| {
+            let mut hasher = hasher::default().with_profile(HashProfile::default().with_extension_tags(tags));
+            vec![&b"foo"[..]].objecthash(&mut hasher);
+            hasher.try_finish()
+        };
+
+        assert_eq!(with(ExtensionTags::default()).unwrap().as_ref().to_hex(), h!(vec![&b"foo"[..]]));
+        assert!(with(ExtensionTags::default().with_octets(b'x').unwrap()).unwrap().as_ref().to_hex() !=
+                h!(vec![&b"foo"[..]]));
+
+        match with(ExtensionTags::none()) {
+            Err(Error::UnsupportedExtension(_)) => (),
+            other => panic!("expected an unsupported extension error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn nulls_and_booleans() {
+        assert_eq!(h!(()), "1b16b1df538ba12dc3f97edbb85caa7050d46c148134290feba80f8236c83db9");
+        assert_eq!(h!(None::<u32>), "1b16b1df538ba12dc3f97edbb85caa7050d46c148134290feba80f8236c83db9");
+        assert_eq!(h!(Some(1000)), "a3346d18105ef801c3598fec426dcc5d4be9d0374da5343f6c8dcbdf24cb8e0b");
+
+        assert_eq!(h!(true), "7dc96f776c8423e57a2785489a3f9c43fb6e756876d6ad9a9cac4aa4e72ec193");
+        assert_eq!(h!(false), "c02c0b965e023abee808f2b548d8d5193a8b5229be6f3121a6f16e2d41a449b3");
+    }
+
+    #[test]
+    fn strings() {
+        let u1n = "\u{03D3}";
+        let u1d = "\u{03D2}\u{0301}";
+
+        let digest = "f72826713a01881404f34975447bd6edcb8de40b191dc57097ebf4f5417a554d";
+        assert_eq!(h!(u1n), digest);
+        assert_eq!(h!(&u1d), digest);
+        assert_eq!(h!(String::from(u1d)), digest);
+
+        assert_eq!(h!("foo"), "a6a6e5e783c363cd95693ec189c2682315d956869397738679b56305f2095038");
+
+        let with = |normalization, value: &str| {
+            let mut hasher = hasher::default().with_profile(HashProfile::default().with_normalization(normalization));
+            value.objecthash(&mut hasher);
+            hasher.finish()
+        };
+
+        let u1d_raw = with(Normalization::Raw, u1d);
+        assert!(u1d_raw.as_ref().to_hex() != digest);
+        assert_eq!(with(Normalization::Raw, u1n).as_ref().to_hex(), digest);
+        assert_eq!(with(Normalization::Nfd, u1n), u1d_raw);
+        assert_eq!(with(Normalization::Nfd, u1d), u1d_raw);
+        assert_eq!(with(Normalization::Nfc, u1d).as_ref().to_hex(), digest);
+
+        assert_eq!(h!("ԱԲաբ"), "2a2a4485a4e338d8df683971956b1090d2f5d33955a81ecaad1a75125f7a316c");
+        assert_eq!(h!(String::from("ԱԲաբ")), "2a2a4485a4e338d8df683971956b1090d2f5d33955a81ecaad1a75125f7a316c");
+
+        // Strings which are normalized as they're hashed can be longer than
+        // the buffer they're normalized through
+        let long_d = u1d.repeat(200);
+        let long_n = u1n.repeat(200);
+        assert_eq!(h!(long_d.as_str()), h!(long_n.as_str()));
+        assert_eq!(with(Normalization::Nfd, &long_n), with(Normalization::Raw, &long_d));
+    }
+
+    #[test]
+    fn canonicalized_strings() {
+        use std::borrow::Cow;
+
+        fn fold(s: &str) -> Cow<'_, str> {
+            let trimmed = s.trim();
+
+            if trimmed.chars().any(char::is_uppercase) {
+                Cow::Owned(trimmed.to_lowercase())
+            } else {
+                Cow::Borrowed(trimmed)
+            }
+        }
+
+        let profile = HashProfile::default().with_canonicalizer(&fold);
+        assert!(profile.canonicalizer().is_some());
+        assert!(profile != HashProfile::default());
+
+        let with = |value: &dyn Fn(&mut hasher::ring::Hasher)| {
+            let mut hasher = hasher::default().with_profile(profile);
+            value(&mut hasher);
+            hasher.finish().as_ref().to_hex()
+        };
+
+        assert_eq!(with(&|h| " Foo ".objecthash(h)), h!("foo"));
+        assert_eq!(with(&|h| String::from("FOO").objecthash(h)), h!("foo"));
+
+        // Dict keys are canonicalized too, and canonical strings normalized
+        let mut map = HashMap::new();
+        map.insert(" Key", "\u{03D2}\u{0301} ");
+        let mut expected = HashMap::new();
+        expected.insert("key", "\u{03D3}");
+        assert_eq!(with(&|h| map.objecthash(h)), h!(expected));
+    }
+
+    #[test]
+    fn vectors() {
+        assert_eq!(h!(vec![123]), "1b93f704451e1a7a1b8c03626ffcd6dec0bc7ace947ff60d52e1b69b4658ccaa");
+        assert_eq!(h!(vec![1, 2, 3]), "157bf16c70bd4c9673ffb5030552df0ee2c40282042ccdf6167850edc9044ab7");
+        assert_eq!(h!(vec![123456789012345u64]), "3488b9bc37cce8223a032760a9d4ef488cdfebddd9e1af0b31fcd1d7006369a4");
+        assert_eq!(h!(vec![123456789012345u64, 678901234567890u64]), "031ef1aaeccea3bced3a1c6237a4fc00ed4d629c9511922c5a3f4e5c128b0ae4");
+    }
+
+    #[test]
+    fn sets() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let set: HashSet<u32> = vec![1, 2, 3].into_iter().collect();
+        assert_eq!(h!(set), "9c5c4b666d00f4a77d74156ed9cc1e38fe8e66289bb0f49d2618b2fca2610d60");
+
+        let set: BTreeSet<&str> = vec!["foo", "bar", "baz"].into_iter().collect();
+        assert_eq!(h!(set), "11ca84666b2b6238f3b21cdc86ea52f93d1025f46e89bc97a1eb5061aade11c4");
+        assert_eq!(h!(HashSet::<String>::new()), "043a718774c572bd8a25adbeb1bfcd5c0256ae11cecf9f9c3f925d0e52beaf89");
+    }
+
+    #[test]
+    fn hashmaps() {
+        {
+            let hashmap: HashMap<String, String> = HashMap::new();
+            assert_eq!(h!(hashmap), "18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4");
+        }
+
+        {
+            let mut hashmap = HashMap::new();
+            hashmap.insert(String::from("foo"), 1);
+            assert_eq!(h!(hashmap), "bf4c58f5e308e31e2cd64bdbf7a01b9b595a13602438be5e912c7d94f6d8177a");
+        }
+    }
+
+    #[test]
+    fn hashmap_ordering() {
+        {
+            let mut hashmap = HashMap::new();
+            hashmap.insert(String::from("k1"), String::from("v1"));
+            hashmap.insert(String::from("k2"), String::from("v2"));
+            hashmap.insert(String::from("k3"), String::from("v3"));
+
+            assert_eq!(h!(hashmap), "ddd65f1f7568269a30df7cafc26044537dc2f02a1a0d830da61762fc3e687057");
+        }
+
+        {
+            let mut hashmap = HashMap::new();
+            hashmap.insert(String::from("k2"), String::from("v2"));
+            hashmap.insert(String::from("k1"), String::from("v1"));
+            hashmap.insert(String::from("k3"), String::from("v3"));
+
+            assert_eq!(h!(hashmap), "ddd65f1f7568269a30df7cafc26044537dc2f02a1a0d830da61762fc3e687057");
+        }
+    }
+
+    #[test]
+    fn shared_mutable_values() {
+        use std::cell::RefCell;
+        use std::sync::{Arc, Mutex, RwLock};
+        use std::thread;
+        use {digest, Error};
+
+        assert_eq!(digest(&RefCell::new("foo")), digest("foo"));
+        assert_eq!(digest(&Mutex::new(vec![1, 2])), digest(&vec![1, 2]));
+        assert_eq!(digest(&RwLock::new(Some(1.5))), digest(&1.5));
+
+        let lock = Arc::new(Mutex::new(String::from("foo")));
+        let poisoner = Arc::clone(&lock);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poisoning the lock");
+        }).join();
+        assert!(lock.is_poisoned());
+
+        // Poisoned values are hashed as they are, unless the profile rejects
+        // them
+        assert_eq!(digest(&*lock), digest("foo"));
+
+        let mut hasher = hasher::default().with_profile(HashProfile::default().with_reject_poisoned(true));
+        vec![&*lock].objecthash(&mut hasher);
+        match hasher.try_finish() {
+            Err(Error::PoisonedLock) => (),
+            other => panic!("expected a poisoned lock error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shared_values() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        use std::sync::Arc;
+        use {digest, Error};
+
+        struct Node(RefCell<Vec<Rc<Node>>>);
+
+        impl ObjectHash for Node {
+            fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+                self.0.objecthash(hasher);
+            }
+        }
+
+        assert_eq!(digest(&Box::new(1)), digest(&1));
+        assert_eq!(digest(&Arc::new("foo")), digest("foo"));
+
+        // An allocation shared by several parts of a value isn't a cycle
+        let leaf = Rc::new(Node(RefCell::new(Vec::new())));
+        let diamond = Node(RefCell::new(vec![leaf.clone(), leaf.clone()]));
+        let profile = HashProfile::default().with_detect_cycles(true);
+
+        let mut hasher = hasher::default().with_profile(profile);
+        diamond.objecthash(&mut hasher);
+        assert_eq!(hasher.try_finish().unwrap(), digest(&vec![Vec::<()>::new(), Vec::new()]));
+
+        let looped = Rc::new(Node(RefCell::new(Vec::new())));
+        looped.0.borrow_mut().push(leaf.clone());
+        looped.0.borrow_mut().push(looped.clone());
+
+        let mut hasher = hasher::default().with_profile(profile);
+        looped.objecthash(&mut hasher);
+        match hasher.try_finish() {
+            Err(Error::Cycle) => (),
+            other => panic!("expected a cycle error, got {:?}", other),
+        }
+
+        // Break the cycle so the nodes are freed
+        looped.0.borrow_mut().clear();
+    }
+
+    #[test]
+    fn map_bounds() {
+        use std::collections::BTreeMap;
+        use digest;
+
+        // Values only need to be hashable, not comparable
+        struct Reading(f64);
+
+        impl ObjectHash for Reading {
+            fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+                self.0.objecthash(hasher);
+            }
+        }
+
+        let mut readings = HashMap::new();
+        readings.insert("k1", Reading(1.5));
+        readings.insert("k2", Reading(-2.0));
+
+        let mut floats = HashMap::new();
+        floats.insert(String::from("k1"), 1.5);
+        floats.insert(String::from("k2"), -2.0);
+        assert_eq!(digest(&readings), digest(&floats));
+
+        // The digests of maps are unchanged, and don't depend on their type
+        let mut hashmap = HashMap::new();
+        hashmap.insert(String::from("k1"), String::from("v1"));
+        hashmap.insert(String::from("k2"), String::from("v2"));
+        hashmap.insert(String::from("k3"), String::from("v3"));
+        let btreemap: BTreeMap<_, _> = hashmap.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+
+        assert_eq!(h!(btreemap), "ddd65f1f7568269a30df7cafc26044537dc2f02a1a0d830da61762fc3e687057");
+        assert_eq!(digest(&btreemap), digest(&hashmap));
+    }
+
+    #[test]
+    fn borrowed_values() {
+        use std::borrow::Cow;
+        use std::collections::BTreeMap;
+        use digest;
+
+        let owned: Vec<String> = vec![String::from("foo"), String::from("bar")];
+        let borrowed: Vec<&String> = owned.iter().collect();
+        assert_eq!(digest(&borrowed), digest(&owned));
+
+        let mut owned_map = HashMap::new();
+        owned_map.insert(String::from("k1"), vec![1, 2]);
+        owned_map.insert(String::from("k2"), vec![3]);
+
+        let borrowed_map: HashMap<&str, &Vec<i32>> = owned_map.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        assert_eq!(digest(&borrowed_map), digest(&owned_map));
+
+        let mut cow_map: BTreeMap<Cow<str>, Vec<i32>> = BTreeMap::new();
+        cow_map.insert(Cow::Borrowed("k1"), vec![1, 2]);
+        cow_map.insert(Cow::Owned(String::from("k2")), vec![3]);
+        assert_eq!(digest(&cow_map), digest(&owned_map));
+
+        #[cfg(feature = "octet-strings")]
+        {
+            let cow_bytes: Cow<[u8]> = Cow::Borrowed(b"foo");
+            assert_eq!(digest(&cow_bytes), digest(&b"foo"[..]));
+        }
+
+        assert_eq!(digest(&vec![&mut 1, &mut 2]), digest(&vec![1, 2]));
+    }
+}
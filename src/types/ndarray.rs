@@ -0,0 +1,64 @@
+use ndarray::{ArrayBase, ArrayViewD, Axis, Data, Dimension};
+
+use {ObjectHash, ObjectHasher};
+use types::LIST_TAG;
+
+// Arrays are hashed in row-major order as lists nested as deeply as they have
+// axes, so their shape is part of the structure being hashed: a 2x3 array
+// hashes the same as the equivalent `Vec<Vec<T>>` (or JSON array of arrays),
+// and differently from a 3x2 or flat array of the same elements. As with
+// nested `Vec`s, the lengths of the axes after an empty one don't make a
+// difference. Zero-dimensional arrays hash as their only element
+impl<A, S, D> ObjectHash for ArrayBase<S, D>
+    where A: ObjectHash,
+          S: Data<Elem = A>,
+          D: Dimension
+{
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_array(self.view().into_dyn(), hasher);
+    }
+}
+
+fn hash_array<A: ObjectHash, H: ObjectHasher>(array: ArrayViewD<A>, hasher: &mut H) {
+    if array.ndim() == 0 {
+        array.iter().next().expect("zero-dimensional array without an element").objecthash(hasher);
+        return;
+    }
+
+    hasher.update(LIST_TAG);
+
+    for row in array.axis_iter(Axis(0)) {
+        hasher.update_nested(|h| hash_array(row.view(), h));
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use ndarray::{arr0, arr1, arr2, Array2, ArrayD, IxDyn};
+
+    use digest;
+
+    #[test]
+    fn arrays() {
+        assert_eq!(digest(&arr1(&[1, 2, 3])), digest(&vec![1, 2, 3]));
+        assert_eq!(digest(&arr2(&[[1.5, 2.0, 3.0], [4.0, 5.0, 6.0]])),
+                   digest(&vec![vec![1.5, 2.0, 3.0], vec![4.0, 5.0, 6.0]]));
+        assert_eq!(digest(&arr0(7)), digest(&7));
+
+        // The shape is part of the digest
+        let flat = arr1(&[1, 2, 3, 4, 5, 6]);
+        let wide = flat.clone().into_shape_with_order((2, 3)).unwrap();
+        let tall = flat.clone().into_shape_with_order((3, 2)).unwrap();
+        assert!(digest(&flat) != digest(&wide));
+        assert!(digest(&wide) != digest(&tall));
+
+        // Views, transposes and dynamic dimensions hash as the elements they
+        // show, in row-major order
+        assert_eq!(digest(&wide.t()), digest(&vec![vec![1, 4], vec![2, 5], vec![3, 6]]));
+        assert_eq!(digest(&wide.clone().into_dyn()), digest(&wide));
+        assert_eq!(digest(&ArrayD::<i32>::zeros(IxDyn(&[2, 0]))), digest(&vec![Vec::<i32>::new(); 2]));
+        assert_eq!(digest(&Array2::<i32>::zeros((0, 3))), digest(&Vec::<Vec<i32>>::new()));
+    }
+}
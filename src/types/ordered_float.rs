@@ -0,0 +1,55 @@
+use ordered_float::{NotNan, OrderedFloat};
+
+use {ObjectHash, ObjectHasher};
+
+// The wrappers hash the same as the floats they wrap
+impl ObjectHash for OrderedFloat<f64> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.0.objecthash(hasher);
+    }
+}
+
+impl ObjectHash for OrderedFloat<f32> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.0.objecthash(hasher);
+    }
+}
+
+impl ObjectHash for NotNan<f64> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.into_inner().objecthash(hasher);
+    }
+}
+
+impl ObjectHash for NotNan<f32> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.into_inner().objecthash(hasher);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::f64;
+
+    use ordered_float::{NotNan, OrderedFloat};
+
+    use digest;
+
+    #[test]
+    fn wrapped_floats() {
+        assert_eq!(digest(&OrderedFloat(1.5f64)), digest(&1.5f64));
+        assert_eq!(digest(&OrderedFloat(1.5f32)), digest(&1.5f64));
+        assert_eq!(digest(&OrderedFloat(f64::NAN)), digest(&f64::NAN));
+        assert_eq!(digest(&NotNan::new(-0.25f64).unwrap()), digest(&-0.25f64));
+        assert_eq!(digest(&NotNan::new(10f32).unwrap()), digest(&10f64));
+
+        let set: BTreeSet<OrderedFloat<f64>> = vec![2.0, 1.0, 2.0].into_iter().map(OrderedFloat).collect();
+        assert_eq!(digest(&set), digest(&::as_set(&[1.0f64, 2.0])));
+    }
+}
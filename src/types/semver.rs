@@ -0,0 +1,44 @@
+use semver::{Version, VersionReq};
+
+use {ObjectHash, ObjectHasher};
+
+// Versions and requirements are hashed as their canonical string forms, so
+// requirements which are only written differently (with other spacing, say)
+// hash the same. Build metadata is part of a version's string, and therefore
+// of its digest, even though it doesn't affect precedence
+impl ObjectHash for Version {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.to_string().objecthash(hasher);
+    }
+}
+
+impl ObjectHash for VersionReq {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        self.to_string().objecthash(hasher);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use semver::{Version, VersionReq};
+
+    use digest;
+
+    #[test]
+    fn versions() {
+        assert_eq!(digest(&Version::new(1, 2, 3)), digest("1.2.3"));
+        assert_eq!(digest(&Version::parse("1.2.3-alpha.1+build.5").unwrap()),
+                   digest("1.2.3-alpha.1+build.5"));
+        assert!(digest(&Version::parse("1.2.3+build.5").unwrap()) != digest(&Version::new(1, 2, 3)));
+    }
+
+    #[test]
+    fn version_requirements() {
+        assert_eq!(digest(&VersionReq::parse(">= 1.0 , <2").unwrap()), digest(">=1.0, <2"));
+        assert_eq!(digest(&VersionReq::parse("1.2").unwrap()), digest("^1.2"));
+        assert_eq!(digest(&VersionReq::STAR), digest("*"));
+    }
+}
@@ -0,0 +1,111 @@
+use toml::Value;
+use toml::value::{Datetime, Offset};
+
+use {ObjectHash, ObjectHasher};
+use types::{hash_dict, Extension};
+
+impl ObjectHash for Value {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        match *self {
+            Value::String(ref s) => s.objecthash(hasher),
+            Value::Integer(ref i) => i.objecthash(hasher),
+            Value::Float(ref f) => f.objecthash(hasher),
+            Value::Boolean(ref b) => b.objecthash(hasher),
+            Value::Datetime(ref d) => d.objecthash(hasher),
+            Value::Array(ref a) => a.objecthash(hasher),
+            Value::Table(ref t) => hash_dict(t.iter(), hasher),
+        }
+    }
+}
+
+impl ObjectHash for Datetime {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        let mut text = String::with_capacity(35);
+
+        if let Some(ref date) = self.date {
+            text.push_str(&format!("{:04}-{:02}-{:02}", date.year, date.month, date.day));
+        }
+
+        if let Some(ref time) = self.time {
+            if self.date.is_some() {
+                text.push('T');
+            }
+
+            text.push_str(&format!("{:02}:{:02}:{:02}",
+                                   time.hour,
+                                   time.minute,
+                                   time.second.unwrap_or(0)));
+
+            let nanosecond = time.nanosecond.unwrap_or(0);
+            if nanosecond != 0 {
+                text.push_str(format!(".{:09}", nanosecond).trim_end_matches('0'));
+            }
+        }
+
+        match self.offset {
+            Some(Offset::Z) | Some(Offset::Custom { minutes: 0 }) => text.push('Z'),
+            Some(Offset::Custom { minutes }) => {
+                let sign = if minutes < 0 { '-' } else { '+' };
+                let minutes = minutes.abs();
+                text.push_str(&format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60));
+            }
+            None => (),
+        }
+
+        objecthash_extension!(hasher, Extension::Timestamp, text.as_bytes());
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use toml;
+
+    use {hasher, ObjectHash, ObjectHasher};
+    use rustc_serialize::hex::ToHex;
+
+    macro_rules! h {
+        ($toml:expr) => {
+            {
+                let value: toml::Table = toml::from_str($toml).unwrap();
+                let mut hasher = hasher::default();
+                toml::Value::Table(value).objecthash(&mut hasher);
+                hasher.finish().as_ref().to_hex()
+            }
+        };
+    }
+
+    #[test]
+    fn documents() {
+        assert_eq!(h!(""), "18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4");
+        assert_eq!(h!("foo = 1"), "bf4c58f5e308e31e2cd64bdbf7a01b9b595a13602438be5e912c7d94f6d8177a");
+        assert_eq!(h!("k1 = \"v1\"\nk2 = \"v2\"\nk3 = \"v3\""),
+                   "ddd65f1f7568269a30df7cafc26044537dc2f02a1a0d830da61762fc3e687057");
+
+        // Formatting and key order don't affect the digest
+        assert_eq!(h!("[package]\nname = \"foo\"\nversion = \"0.1.0\"\n\n[dependencies]\nbar = \"1\"\n"),
+                   h!("dependencies = { bar = '1' }\n\n[package]\n  version = \"0.1.0\"\n  name = \"foo\""));
+    }
+
+    #[test]
+    fn values() {
+        // {"foo": ["baz", true, 1.5]}
+        assert_eq!(h!("foo = [\"baz\", true, 1.5]"),
+                   "c46e1e862b71250ad8d6cc6910480162c176069c622d2defad3eb5179bb47bb4");
+    }
+
+    #[test]
+    fn datetimes() {
+        // {"t": <timestamp "1979-05-27T07:32:00Z">}
+        let digest = "fbe6db76650311ff9105dc8e4f3a1e17d6684f897a830bf4568e2ecdc4b91b4c";
+        assert_eq!(h!("t = 1979-05-27T07:32:00Z"), digest);
+        assert_eq!(h!("t = 1979-05-27t07:32:00+00:00"), digest);
+        assert_eq!(h!("t = 1979-05-27 07:32:00.000Z"), digest);
+
+        assert_eq!(h!("t = 1979-05-27T00:32:00.999-07:00"), h!("t = 1979-05-27T00:32:00.999000-07:00"));
+        assert!(h!("t = 1979-05-27T00:32:00-07:00") != h!("t = 1979-05-27T00:32:00"));
+        assert_eq!(h!("t = 07:32:00"), h!("t = 07:32"));
+    }
+}
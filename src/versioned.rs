@@ -0,0 +1,132 @@
+//! Values hashed along with the name of their schema
+//!
+//! Two versions of a type can hash the same value differently, or different
+//! values the same, so a digest is only meaningful together with the schema
+//! it was computed under. `Versioned<T>` makes the schema part of the digest:
+//! it hashes as the dict
+//!
+//! ```text
+//! {"schema": <schema>, "payload": <value>}
+//! ```
+//!
+//! so bumping the schema name (e.g. from `"invoice/1"` to `"invoice/2"`)
+//! changes the digest of every value, and a digest can't be mistaken for one
+//! of the same data under another schema. Structs can hash themselves this
+//! way with the `schema = ...` form of `objecthash_struct!`, or with
+//! `#[objecthash(schema = ...)]` when deriving `ObjectHash`.
+
+use {ObjectHash, ObjectHasher};
+use types::{dict_member, hash_dict_members};
+
+/// Key of the schema name in the dict a versioned value is hashed as
+pub const SCHEMA_KEY: &str = "schema";
+
+/// Key of the value in the dict a versioned value is hashed as
+pub const PAYLOAD_KEY: &str = "payload";
+
+/// A value together with the name and version of its schema
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Versioned<T> {
+    pub schema: String,
+    pub payload: T,
+}
+
+impl<T: ObjectHash> Versioned<T> {
+    pub fn new<S: Into<String>>(schema: S, payload: T) -> Self {
+        Versioned {
+            schema: schema.into(),
+            payload,
+        }
+    }
+}
+
+impl<T: ObjectHash> ObjectHash for Versioned<T> {
+    #[inline]
+    fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+        hash_versioned(&self.schema, hasher, |h| self.payload.objecthash(h));
+    }
+}
+
+/// Hash whatever the given function hashes as the payload of a value with
+/// the given schema
+pub fn hash_versioned<H, F>(schema: &str, hasher: &mut H, payload: F)
+    where H: ObjectHasher,
+          F: Fn(&mut H)
+{
+    let digest = |hasher: &H, value: &dyn Fn(&mut H)| {
+        let mut nested_hasher = hasher.nested();
+        value(&mut nested_hasher);
+        nested_hasher.finish()
+    };
+
+    let schema_key = digest(hasher, &|h| SCHEMA_KEY.objecthash(h));
+    let schema = digest(hasher, &|h| schema.objecthash(h));
+    let payload_key = digest(hasher, &|h| PAYLOAD_KEY.objecthash(h));
+    let payload = digest(hasher, &payload);
+
+    let mut members = [dict_member(&schema_key, &schema), dict_member(&payload_key, &payload)];
+    hash_dict_members(&mut members, hasher);
+}
+
+#[cfg(test)]
+#[cfg(feature = "objecthash-ring")]
+mod tests {
+    use super::Versioned;
+    use {digest, hasher, ObjectHash, ObjectHasher};
+
+    struct Invoice {
+        total: u64,
+    }
+
+    impl ObjectHash for Invoice {
+        fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+            objecthash_struct!(hasher, schema = "invoice/2", "total" => &self.total)
+        }
+    }
+
+    struct Unversioned<'a>(&'a Invoice);
+
+    impl<'a> ObjectHash for Unversioned<'a> {
+        fn objecthash<H: ObjectHasher>(&self, hasher: &mut H) {
+            objecthash_struct!(hasher, "total" => &self.0.total)
+        }
+    }
+
+    #[test]
+    fn versioned_values() {
+        let mut expected = hasher::default();
+        objecthash_struct!(expected, "schema" => "invoice/2", "payload" => &vec![1, 2]);
+
+        assert_eq!(digest(&Versioned::new("invoice/2", vec![1, 2])), expected.finish());
+        assert!(digest(&Versioned::new("invoice/3", vec![1, 2])) != digest(&Versioned::new("invoice/2", vec![1, 2])));
+        assert!(digest(&Versioned::new("invoice/2", vec![1, 2])) != digest(&vec![1, 2]));
+    }
+
+    #[test]
+    fn versioned_structs() {
+        let invoice = Invoice { total: 100 };
+        assert_eq!(digest(&invoice), digest(&Versioned::new("invoice/2", Unversioned(&invoice))));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_structs() {
+        #[derive(ObjectHash)]
+        #[objecthash(schema = "invoice/2")]
+        struct Derived<T> {
+            total: u64,
+            #[objecthash(rename = "lines")]
+            items: Vec<T>,
+        }
+
+        #[derive(ObjectHash)]
+        struct Empty {}
+
+        let derived = Derived { total: 100, items: vec!["foo"] };
+        let mut expected = hasher::default();
+        objecthash_struct!(&mut expected, schema = "invoice/2", "total" => &100, "lines" => &vec!["foo"]);
+        assert_eq!(digest(&derived), expected.finish());
+
+        assert_eq!(digest(&Empty {}), digest(&::std::collections::BTreeMap::<String, u64>::new()));
+    }
+}